@@ -24,6 +24,7 @@ async fn main() -> io::Result<()> {
     let TrackedChild {
         mut tokio_child,
         accesses_future,
+        ..
     } = command.spawn().await?;
 
     let acceses = accesses_future.await?;
@@ -51,6 +52,10 @@ async fn main() -> io::Result<()> {
                     AccessMode::ReadWrite => b"readwrite",
                     AccessMode::Write => b"write",
                     AccessMode::ReadDir => b"readdir",
+                    AccessMode::Rename => b"rename",
+                    AccessMode::Remove => b"remove",
+                    AccessMode::CreateDir => b"createdir",
+                    AccessMode::Link => b"link",
                 },
             ])
             .await?;
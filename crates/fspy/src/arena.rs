@@ -24,9 +24,21 @@ impl PathAccessArena {
     pub fn add(&mut self, access: PathAccess<'_>) {
         self.with_mut(|fields| {
             let path = access.path.clone_in(fields.bump);
+            let dest = access.dest.map(|dest| dest.clone_in(fields.bump));
+            let original_path = access
+                .original_path
+                .map(|original_path| original_path.clone_in(fields.bump));
+            let original_dest = access
+                .original_dest
+                .map(|original_dest| original_dest.clone_in(fields.bump));
             let path_access = PathAccess {
                 mode: access.mode,
                 path,
+                dest,
+                outcome: access.outcome,
+                follow_symlinks: access.follow_symlinks,
+                original_path,
+                original_dest,
             };
             fields.accesses.push(path_access);
         });
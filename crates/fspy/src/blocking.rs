@@ -0,0 +1,112 @@
+//! Tracking children from synchronous code, without a tokio runtime.
+
+use std::{
+    io,
+    panic::resume_unwind,
+    process::Child,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+
+use tokio::runtime;
+
+#[cfg(unix)]
+use crate::AbortHandle;
+use crate::{Command, PathAccessIterable, TrackedOutput, os_impl::spawn_impl};
+
+/// A child spawned by `Command::spawn_blocking`.
+pub struct TrackedChild {
+    pub std_child: Child,
+    pub accesses: PendingAccesses,
+    #[cfg(unix)]
+    abort_handle: AbortHandle,
+}
+
+impl TrackedChild {
+    /// Waits for the child to exit and for the accesses, while reading all of stdout and stderr if they are piped.
+    pub fn wait_with_output(self) -> io::Result<TrackedOutput> {
+        // Accesses are collected on their own thread meanwhile, so draining the pipes first can't block it.
+        let output = self.std_child.wait_with_output()?;
+        Ok(TrackedOutput {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            accesses: self.accesses.wait()?,
+        })
+    }
+
+    /// Returns a handle to kill the child and its descendants, and to complete the accesses without them.
+    #[cfg(unix)]
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort_handle.clone()
+    }
+}
+
+/// The accesses of a tracked child and its descendants, collected until all of them exit.
+pub struct PendingAccesses {
+    result_receiver: mpsc::Receiver<io::Result<PathAccessIterable>>,
+    collecting: JoinHandle<()>,
+}
+
+impl PendingAccesses {
+    /// Blocks until all the tracked processes exit, and returns their accesses.
+    pub fn wait(self) -> io::Result<PathAccessIterable> {
+        match self.result_receiver.recv() {
+            Ok(result) => result,
+            // The sender is only dropped without sending if the thread panics.
+            Err(mpsc::RecvError) => resume_unwind(self.collecting.join().unwrap_err()),
+        }
+    }
+}
+
+pub(crate) fn spawn(command: Command) -> io::Result<TrackedChild> {
+    let runtime = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let (child_sender, child_receiver) = mpsc::sync_channel(1);
+    let (result_sender, result_receiver) = mpsc::sync_channel(1);
+
+    // The ipc receive loop and the seccomp supervisor run on this thread, in its own runtime,
+    // so the caller doesn't need one and can even be in the middle of another.
+    let collecting = thread::Builder::new()
+        .name("fspy".to_owned())
+        .spawn(move || {
+            runtime.block_on(async move {
+                let spawned = match spawn_impl::<Child>(command).await {
+                    Ok(spawned) => spawned,
+                    Err(err) => {
+                        let _ = child_sender.send(Err(err));
+                        return;
+                    }
+                };
+                #[cfg(unix)]
+                let _ = child_sender.send(Ok((spawned.child, spawned.abort_handle)));
+                #[cfg(not(unix))]
+                let _ = child_sender.send(Ok(spawned.child));
+                let _ = result_sender.send(spawned.accesses_future.await);
+                // Accesses may complete before all the tracked processes exit,
+                // and those still alive need the collecting to go on.
+                #[cfg(unix)]
+                spawned.collecting_done.await;
+            })
+        })?;
+
+    let spawned = match child_receiver.recv() {
+        Ok(spawned) => spawned?,
+        // The sender is only dropped without sending if the thread panics.
+        Err(mpsc::RecvError) => resume_unwind(collecting.join().unwrap_err()),
+    };
+    #[cfg(unix)]
+    let (std_child, abort_handle) = spawned;
+    #[cfg(not(unix))]
+    let std_child = spawned;
+    Ok(TrackedChild {
+        std_child,
+        accesses: PendingAccesses {
+            result_receiver,
+            collecting,
+        },
+        #[cfg(unix)]
+        abort_handle,
+    })
+}
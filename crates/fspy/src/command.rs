@@ -1,5 +1,5 @@
 use crate::{
-    TrackedChild,
+    TrackedChild, TrackedOutput, blocking,
     os_impl::{self, spawn_impl},
 };
 
@@ -8,21 +8,53 @@ use std::{
     ffi::{OsStr, OsString},
     io,
     path::{Path, PathBuf},
-    process::Stdio,
+    process::{Child as StdChild, Command as StdCommand, ExitStatus, Output, Stdio},
+    time::Duration,
 };
 
 #[cfg(unix)]
-use fspy_shared_unix::exec::Exec;
-use tokio::process::Command as TokioCommand;
+use fspy_shared_unix::{
+    access_policy::AccessPolicy, exec::Exec, path::PathNormalization, path_filter::PathFilter,
+};
+use futures_util::future::BoxFuture;
+use tokio::process::{Child as TokioChild, Command as TokioCommand};
 
+/// Like `std::process::Command`, the environment of the current process is inherited by default,
+/// with changes made by `env`, `envs`, `env_remove` and `env_clear`.
 #[derive(Debug)]
 pub struct Command {
     pub(crate) program: OsString,
     pub(crate) args: Vec<OsString>,
-    pub(crate) envs: HashMap<OsString, OsString>,
+    /// Variables set (`Some`) or removed (`None`) on top of the inherited environment.
+    pub(crate) envs: HashMap<OsString, Option<OsString>>,
+    /// Whether the environment of the current process is not inherited.
+    pub(crate) env_clear: bool,
     pub(crate) cwd: Option<PathBuf>,
     #[cfg(unix)]
     pub(crate) arg0: Option<OsString>,
+    #[cfg(unix)]
+    pub(crate) path_filter: PathFilter,
+    #[cfg(unix)]
+    pub(crate) path_normalization: PathNormalization,
+    #[cfg(unix)]
+    pub(crate) access_policy: Option<AccessPolicy>,
+    #[cfg(unix)]
+    pub(crate) record_env_values: bool,
+    #[cfg(unix)]
+    pub(crate) uid: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) gid: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) process_group: Option<i32>,
+    #[cfg(unix)]
+    pub(crate) pre_exec_hooks: PreExecHooks,
+    pub(crate) kill_on_drop: bool,
+    #[cfg(unix)]
+    pub(crate) timeout: Option<Duration>,
+    #[cfg(unix)]
+    pub(crate) wait_for_descendants: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) cgroup_parent: Option<PathBuf>,
 
     pub(crate) stderr: Option<Stdio>,
     pub(crate) stdout: Option<Stdio>,
@@ -31,6 +63,21 @@ pub struct Command {
     pub(crate) spy_inner: os_impl::SpyInner,
 }
 
+#[cfg(unix)]
+type PreExecHook = Box<dyn FnMut() -> io::Result<()> + Send + Sync>;
+
+/// The closures registered by `Command::pre_exec`.
+#[cfg(unix)]
+#[derive(Default)]
+pub(crate) struct PreExecHooks(Vec<PreExecHook>);
+
+#[cfg(unix)]
+impl std::fmt::Debug for PreExecHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PreExecHooks({})", self.0.len())
+    }
+}
+
 impl Command {
     #[cfg(unix)]
     pub fn get_exec(&self) -> Exec {
@@ -55,8 +102,8 @@ impl Command {
                 )
                 .collect(),
             envs: self
-                .envs
-                .iter()
+                .resolved_envs()
+                .into_iter()
                 .map(|(name, value)| (name.as_bytes().into(), Some(value.as_bytes().into())))
                 .collect(),
         }
@@ -73,20 +120,49 @@ impl Command {
             .into_iter()
             .map(|arg| OsString::from_vec(arg.into()))
             .collect();
+        // The environment of `exec` is complete, so nothing is inherited.
+        self.env_clear = true;
         self.envs = exec
             .envs
             .into_iter()
             .map(|(name, value)| {
                 (
                     OsString::from_vec(name.into()),
-                    OsString::from_vec(value.unwrap_or_default().into()),
+                    Some(OsString::from_vec(value.unwrap_or_default().into())),
                 )
             })
             .collect()
     }
 
+    /// The environment the child will have: the inherited one (unless cleared) with the changes applied.
+    fn resolved_envs(&self) -> HashMap<OsString, OsString> {
+        let mut envs = if self.env_clear {
+            HashMap::new()
+        } else {
+            std::env::vars_os().collect()
+        };
+        for (name, value) in &self.envs {
+            match value {
+                Some(value) => envs.insert(name.clone(), value.clone()),
+                None => envs.remove(name),
+            };
+        }
+        envs
+    }
+
     pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
-        self.envs.remove(key.as_ref());
+        if self.env_clear {
+            self.envs.remove(key.as_ref());
+        } else {
+            self.envs.insert(key.as_ref().to_os_string(), None);
+        }
+        self
+    }
+
+    /// Clears the environment, including the variables that would be inherited.
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.envs.clear();
+        self.env_clear = true;
         self
     }
     pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
@@ -107,8 +183,10 @@ impl Command {
         K: AsRef<OsStr>,
         V: AsRef<OsStr>,
     {
-        self.envs
-            .insert(key.as_ref().to_os_string(), val.as_ref().to_os_string());
+        self.envs.insert(
+            key.as_ref().to_os_string(),
+            Some(val.as_ref().to_os_string()),
+        );
         self
     }
 
@@ -118,10 +196,12 @@ impl Command {
         K: AsRef<OsStr>,
         V: AsRef<OsStr>,
     {
-        self.envs.extend(
-            vars.into_iter()
-                .map(|(key, val)| (key.as_ref().to_os_string(), val.as_ref().to_os_string())),
-        );
+        self.envs.extend(vars.into_iter().map(|(key, val)| {
+            (
+                key.as_ref().to_os_string(),
+                Some(val.as_ref().to_os_string()),
+            )
+        }));
         self
     }
     pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
@@ -153,15 +233,203 @@ impl Command {
         self
     }
 
+    /// Sets which accesses are recorded. Defaults to `PathFilter::default()`.
+    ///
+    /// The filter applies to all the descendant processes, and excluded accesses are dropped
+    /// in the processes themselves, which is cheaper than filtering the results.
+    #[cfg(unix)]
+    pub fn path_filter(&mut self, path_filter: PathFilter) -> &mut Command {
+        self.path_filter = path_filter;
+        self
+    }
+
+    /// Sets how the paths of accesses are normalized. They are not by default, and recorded
+    /// as the traced processes spelled them, only made absolute.
+    ///
+    /// Paths are normalized before they are matched by the path filter.
+    #[cfg(unix)]
+    pub fn path_normalization(&mut self, path_normalization: PathNormalization) -> &mut Command {
+        self.path_normalization = path_normalization;
+        self
+    }
+
+    /// Enforces `access_policy`: accesses it denies fail, and are recorded as violations
+    /// (see `PathAccessIterable::violations`) instead of accesses. Accesses are only observed by default.
+    ///
+    /// Violations are recorded regardless of the path filter.
+    #[cfg(unix)]
+    pub fn access_policy(&mut self, access_policy: AccessPolicy) -> &mut Command {
+        self.access_policy = Some(access_policy);
+        self
+    }
+
+    /// Whether the values of environment variables are recorded along with their names,
+    /// in `EnvRead::value`. They are not by default, as they may be secrets.
+    #[cfg(unix)]
+    pub fn record_env_values(&mut self, record_env_values: bool) -> &mut Command {
+        self.record_env_values = record_env_values;
+        self
+    }
+
+    /// Sets the user id of the child, like `std::os::unix::process::CommandExt::uid`.
+    #[cfg(unix)]
+    pub fn uid(&mut self, id: u32) -> &mut Command {
+        self.uid = Some(id);
+        self
+    }
+
+    /// Sets the group id of the child, like `std::os::unix::process::CommandExt::gid`.
+    #[cfg(unix)]
+    pub fn gid(&mut self, id: u32) -> &mut Command {
+        self.gid = Some(id);
+        self
+    }
+
+    /// Puts the child in the process group `pgroup`, or in a new group led by itself if it's 0,
+    /// like `std::os::unix::process::CommandExt::process_group`.
+    #[cfg(unix)]
+    pub fn process_group(&mut self, pgroup: i32) -> &mut Command {
+        self.process_group = Some(pgroup);
+        self
+    }
+
+    /// Schedules a closure to run in the child just before it execs,
+    /// like `std::os::unix::process::CommandExt::pre_exec`.
+    ///
+    /// Closures run in the order they are added, and before fspy sets up the tracking of the child,
+    /// so that what they do isn't recorded.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `std::os::unix::process::CommandExt::pre_exec`:
+    /// the closure runs in a forked process, and may only call async-signal-safe functions.
+    #[cfg(unix)]
+    pub unsafe fn pre_exec<F>(&mut self, f: F) -> &mut Command
+    where
+        F: FnMut() -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.pre_exec_hooks.0.push(Box::new(f));
+        self
+    }
+
+    /// Kills the child when `TrackedChild::tokio_child` is dropped, like `tokio::process::Command::kill_on_drop`.
+    /// It doesn't apply to `spawn_blocking`, as `std::process::Child` is never killed on drop.
+    pub fn kill_on_drop(&mut self, kill_on_drop: bool) -> &mut Command {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Aborts the child after `timeout`, as `AbortHandle::abort` does, if its accesses aren't completed by then.
+    #[cfg(unix)]
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Command {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether the accesses complete only after all the tracked processes exit, which is the default.
+    ///
+    /// If it's false, the accesses complete as soon as the root process exits, with the processes still alive,
+    /// such as daemons started by the child, in `PathAccessIterable::survivors`.
+    /// They are left running, but their accesses from then on are not recorded.
+    #[cfg(unix)]
+    pub fn wait_for_descendants(&mut self, wait_for_descendants: bool) -> &mut Command {
+        self.wait_for_descendants = wait_for_descendants;
+        self
+    }
+
+    /// Contains the child and all its descendants in a new cgroup v2 created under `parent`,
+    /// which has to be delegated to the current user, such as the one from `fspy::current_cgroup`.
+    ///
+    /// Unlike the ipc socket, the cgroup can't be left by closing fds or double-forking.
+    /// The accesses complete only after it's empty, `AbortHandle::abort` kills everything in it,
+    /// and `PathAccessIterable::resource_usage` reports what it used.
+    /// It's removed once the tracked processes exit, unless some are left running.
+    #[cfg(target_os = "linux")]
+    pub fn cgroup<P: AsRef<Path>>(&mut self, parent: P) -> &mut Command {
+        self.cgroup_parent = Some(parent.as_ref().to_owned());
+        self
+    }
+
+    pub fn get_program(&self) -> &OsStr {
+        &self.program
+    }
+
+    /// Returns the arguments, not including the program.
+    pub fn get_args(&self) -> impl Iterator<Item = &OsStr> {
+        self.args.iter().map(OsString::as_os_str)
+    }
+
+    /// Returns the variables explicitly set or removed (as `None`), in no particular order.
+    /// Inherited variables are not included, like `std::process::Command::get_envs`.
+    pub fn get_envs(&self) -> impl Iterator<Item = (&OsStr, Option<&OsStr>)> {
+        self.envs
+            .iter()
+            .map(|(name, value)| (name.as_os_str(), value.as_deref()))
+    }
+
+    pub fn get_current_dir(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Runs the command and waits for it to exit, like `tokio::process::Command::status`.
+    /// The accesses are not waited for.
+    pub async fn status(self) -> io::Result<ExitStatus> {
+        let mut tracked_child = self.spawn().await?;
+        tracked_child.tokio_child.wait().await
+    }
+
+    /// Runs the command and collects its output, like `tokio::process::Command::output`.
+    /// stdout and stderr are captured and stdin is null, unless they are set otherwise.
+    /// The accesses are not waited for.
+    pub async fn output(mut self) -> io::Result<Output> {
+        self.default_to_captured_output();
+        let tracked_child = self.spawn().await?;
+        tracked_child.tokio_child.wait_with_output().await
+    }
+
+    /// Runs the command and collects its output, exit status and accesses.
+    /// stdout and stderr are captured and stdin is null, unless they are set otherwise.
+    pub async fn output_with_accesses(mut self) -> io::Result<TrackedOutput> {
+        self.default_to_captured_output();
+        self.spawn().await?.wait_with_output().await
+    }
+
+    /// Same as `output_with_accesses`, without a tokio runtime.
+    pub fn output_with_accesses_blocking(mut self) -> io::Result<TrackedOutput> {
+        self.default_to_captured_output();
+        self.spawn_blocking()?.wait_with_output()
+    }
+
+    fn default_to_captured_output(&mut self) {
+        self.stdin.get_or_insert_with(Stdio::null);
+        self.stdout.get_or_insert_with(Stdio::piped);
+        self.stderr.get_or_insert_with(Stdio::piped);
+    }
+
     pub async fn spawn(self) -> io::Result<TrackedChild> {
-        spawn_impl(self).await
+        let spawned = spawn_impl::<TokioChild>(self).await?;
+        Ok(TrackedChild {
+            tokio_child: spawned.child,
+            accesses_future: spawned.accesses_future,
+            #[cfg(unix)]
+            live_accesses: spawned.live_accesses,
+            #[cfg(unix)]
+            abort_handle: spawned.abort_handle,
+        })
+    }
+
+    /// Spawns the command without a tokio runtime, for build scripts and other synchronous code.
+    ///
+    /// Accesses are collected by a dedicated thread until all the tracked processes exit.
+    pub fn spawn_blocking(self) -> io::Result<blocking::TrackedChild> {
+        blocking::spawn(self)
     }
 
     /// Resolve program name to full path using `PATH` and cwd.
     pub fn resolve_program(&mut self) -> io::Result<()> {
         self.program = which::which_in(
             self.program.as_os_str(),
-            self.envs.get(OsStr::new("PATH")),
+            self.resolved_envs().get(OsStr::new("PATH")),
             if let Some(cwd) = &self.cwd {
                 cwd.clone()
             } else {
@@ -173,32 +441,112 @@ impl Command {
         Ok(())
     }
 
-    pub(crate) fn into_tokio_command(self) -> TokioCommand {
-        let mut tokio_cmd = TokioCommand::new(self.program);
+    pub(crate) fn into_std_command(self) -> StdCommand {
+        let mut std_cmd = StdCommand::new(self.program);
         if let Some(cwd) = &self.cwd {
-            tokio_cmd.current_dir(cwd);
+            std_cmd.current_dir(cwd);
         }
 
         #[cfg(unix)]
-        if let Some(arg0) = self.arg0 {
-            tokio_cmd.arg0(arg0);
+        {
+            use std::os::unix::process::CommandExt as _;
+            if let Some(arg0) = self.arg0 {
+                std_cmd.arg0(arg0);
+            }
+            if let Some(uid) = self.uid {
+                std_cmd.uid(uid);
+            }
+            if let Some(gid) = self.gid {
+                std_cmd.gid(gid);
+            }
+            if let Some(pgroup) = self.process_group {
+                std_cmd.process_group(pgroup);
+            }
+            for hook in self.pre_exec_hooks.0 {
+                // Safety: the caller of `Command::pre_exec` upholds its contract.
+                unsafe { std_cmd.pre_exec(hook) };
+            }
+        }
+        std_cmd.args(self.args);
+        if self.env_clear {
+            std_cmd.env_clear();
+        }
+        for (name, value) in self.envs {
+            match value {
+                Some(value) => std_cmd.env(name, value),
+                None => std_cmd.env_remove(name),
+            };
         }
-        tokio_cmd.args(self.args);
-        tokio_cmd.env_clear();
-        tokio_cmd.envs(self.envs);
 
         if let Some(stdin) = self.stdin {
-            tokio_cmd.stdin(stdin);
+            std_cmd.stdin(stdin);
         }
 
         if let Some(stdout) = self.stdout {
-            tokio_cmd.stdout(stdout);
+            std_cmd.stdout(stdout);
         }
 
         if let Some(stderr) = self.stderr {
-            tokio_cmd.stderr(stderr);
+            std_cmd.stderr(stderr);
         }
 
-        tokio_cmd
+        std_cmd
     }
 }
+
+/// The child handles that `spawn_impl` can spawn:
+/// `tokio::process::Child` for `Command::spawn`, and `std::process::Child` for `Command::spawn_blocking`.
+pub(crate) trait SpawnChild: Sized + Send + 'static {
+    /// Spawns the child with `spawn`, which can set up the process before it's wrapped.
+    fn spawn_with(
+        command: StdCommand,
+        kill_on_drop: bool,
+        spawn: impl FnOnce(&mut StdCommand) -> io::Result<StdChild>,
+    ) -> io::Result<Self>;
+
+    fn id(&self) -> Option<u32>;
+}
+
+impl SpawnChild for TokioChild {
+    fn spawn_with(
+        command: StdCommand,
+        kill_on_drop: bool,
+        spawn: impl FnOnce(&mut StdCommand) -> io::Result<StdChild>,
+    ) -> io::Result<Self> {
+        TokioCommand::from(command)
+            .kill_on_drop(kill_on_drop)
+            .spawn_with(spawn)
+    }
+
+    fn id(&self) -> Option<u32> {
+        self.id()
+    }
+}
+
+impl SpawnChild for StdChild {
+    fn spawn_with(
+        mut command: StdCommand,
+        _kill_on_drop: bool,
+        spawn: impl FnOnce(&mut StdCommand) -> io::Result<StdChild>,
+    ) -> io::Result<Self> {
+        spawn(&mut command)
+    }
+
+    fn id(&self) -> Option<u32> {
+        Some(self.id())
+    }
+}
+
+/// What `spawn_impl` returns, before it's wrapped in `TrackedChild` or `blocking::TrackedChild`.
+pub(crate) struct Spawned<C> {
+    pub child: C,
+    pub accesses_future: BoxFuture<'static, io::Result<os_impl::PathAccessIterable>>,
+    #[cfg(unix)]
+    pub live_accesses: os_impl::LiveAccesses,
+    #[cfg(unix)]
+    pub abort_handle: os_impl::AbortHandle,
+    /// Completes when the collecting of accesses stops, which may be after `accesses_future` completes
+    /// if it completes early. It must keep running until then for the tracked processes still alive.
+    #[cfg(unix)]
+    pub collecting_done: BoxFuture<'static, ()>,
+}
@@ -16,23 +16,96 @@ mod os_impl;
 #[path = "./windows/mod.rs"]
 mod os_impl;
 
+pub mod blocking;
 mod command;
 mod arena;
+mod owned;
+mod process;
+mod report;
+mod summary;
 
-use std::{env::temp_dir, ffi::OsStr, fs::create_dir, io, sync::OnceLock};
+use std::{
+    env::temp_dir, ffi::OsStr, fs::create_dir, io, process::ExitStatus, sync::OnceLock,
+};
 
-use allocator_api2::vec::Vec;
 pub use command::Command;
-use futures_util::future::{BoxFuture};
+use futures_util::future::{BoxFuture, try_join};
+#[cfg(unix)]
+use futures_util::stream::{BoxStream, StreamExt as _};
 use os_impl::SpyInner;
 use tokio::process::Child;
 pub use fspy_shared::ipc::PathAccess;
 pub use fspy_shared::ipc::AccessMode;
+pub use fspy_shared::ipc::AccessOutcome;
+#[cfg(unix)]
+pub use fspy_shared::ipc::{CwdChange, EnvRead};
+#[cfg(unix)]
+pub use fspy_shared_unix::{
+    access_policy::AccessPolicy, path::PathNormalization, path_filter::PathFilter,
+};
 pub use os_impl::PathAccessIterable;
+#[cfg(unix)]
+pub use os_impl::{AbortHandle, Completion};
+#[cfg(target_os = "linux")]
+pub use os_impl::{ResourceUsage, current_cgroup};
+pub use owned::OwnedPathAccess;
+pub use process::{Process, ProcessTree};
+pub use report::{
+    REPORT_VERSION, Report, ReportAccess, ReportEnvRead, ReportExitStatus, ReportProcess,
+    ReportTiming,
+};
+pub use summary::{AccessSet, AccessSummary};
 
 pub struct TrackedChild {
     pub tokio_child: Child,
     pub accesses_future: BoxFuture<'static, io::Result<os_impl::PathAccessIterable>>,
+    #[cfg(unix)]
+    live_accesses: os_impl::LiveAccesses,
+    #[cfg(unix)]
+    abort_handle: AbortHandle,
+}
+
+impl TrackedChild {
+    /// Returns a stream of accesses of the child and its descendants, as they happen.
+    ///
+    /// The stream starts from the first access, no matter when it's created, and ends when all the tracked processes exit.
+    /// It yields the same accesses as `accesses_future` resolves to, which can still be used alongside.
+    /// Errors of collecting accesses are only reported by `accesses_future`.
+    #[cfg(unix)]
+    pub fn access_stream(&self) -> BoxStream<'static, OwnedPathAccess> {
+        self.live_accesses.stream().boxed()
+    }
+
+    /// Returns a handle to kill the child and its descendants, and to complete `accesses_future` without them.
+    #[cfg(unix)]
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort_handle.clone()
+    }
+
+    /// Waits for the child to exit and for the accesses, while reading all of stdout and stderr if they are piped.
+    ///
+    /// The pipes are drained at the same time as the accesses are collected,
+    /// so a child blocked on writing to a full pipe can't keep the accesses from completing.
+    pub async fn wait_with_output(self) -> io::Result<TrackedOutput> {
+        let (output, accesses) =
+            try_join(self.tokio_child.wait_with_output(), self.accesses_future).await?;
+        Ok(TrackedOutput {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            accesses,
+        })
+    }
+}
+
+/// The output of a tracked child, along with the accesses of it and its descendants.
+pub struct TrackedOutput {
+    pub status: ExitStatus,
+    /// Empty unless stdout is piped.
+    pub stdout: Vec<u8>,
+    /// Empty unless stderr is piped.
+    pub stderr: Vec<u8>,
+    pub accesses: PathAccessIterable,
 }
 
 pub struct Spy(SpyInner);
@@ -55,10 +128,34 @@ impl Spy {
         Command {
             program: program.as_ref().to_os_string(),
             envs: Default::default(),
+            env_clear: false,
             args: vec![],
             cwd: None,
             #[cfg(unix)]
             arg0: None,
+            #[cfg(unix)]
+            path_filter: Default::default(),
+            #[cfg(unix)]
+            path_normalization: Default::default(),
+            #[cfg(unix)]
+            access_policy: None,
+            #[cfg(unix)]
+            record_env_values: false,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            process_group: None,
+            #[cfg(unix)]
+            pre_exec_hooks: Default::default(),
+            kill_on_drop: false,
+            #[cfg(unix)]
+            timeout: None,
+            #[cfg(unix)]
+            wait_for_descendants: true,
+            #[cfg(target_os = "linux")]
+            cgroup_parent: None,
             spy_inner: self.0.clone(),
             stderr: None,
             stdout: None,
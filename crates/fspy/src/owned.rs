@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use fspy_shared::ipc::{AccessMode, AccessOutcome, PathAccess};
+
+/// An owned version of `PathAccess`, for accesses that outlive the buffers they are decoded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedPathAccess {
+    /// The pid of the process that the access is from.
+    pub pid: u32,
+    pub mode: AccessMode,
+    pub path: PathBuf,
+    pub dest: Option<PathBuf>,
+    pub outcome: AccessOutcome,
+    pub follow_symlinks: bool,
+    /// The paths as spelled by the traced process, see `PathNormalization::keep_original`.
+    pub original_path: Option<PathBuf>,
+    pub original_dest: Option<PathBuf>,
+}
+
+impl OwnedPathAccess {
+    pub fn new(pid: u32, path_access: PathAccess<'_>) -> Self {
+        Self {
+            pid,
+            mode: path_access.mode,
+            path: path_access.path.to_cow_os_str().into_owned().into(),
+            dest: path_access
+                .dest
+                .map(|dest| dest.to_cow_os_str().into_owned().into()),
+            outcome: path_access.outcome,
+            follow_symlinks: path_access.follow_symlinks,
+            original_path: path_access
+                .original_path
+                .map(|original_path| original_path.to_cow_os_str().into_owned().into()),
+            original_dest: path_access
+                .original_dest
+                .map(|original_dest| original_dest.to_cow_os_str().into_owned().into()),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+use fspy_shared::ipc::ProcessInfo;
+
+/// A tracked process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    pub ppid: u32,
+    /// The path of the executable.
+    pub program: PathBuf,
+    /// The arguments including argv[0]. On Windows, it contains the command line as a single element.
+    pub args: Vec<OsString>,
+}
+
+impl Process {
+    pub(crate) fn from_info(pid: u32, process_info: ProcessInfo<'_>) -> Self {
+        #[cfg(unix)]
+        let args = {
+            use std::os::unix::ffi::OsStrExt as _;
+            let args = process_info.args.as_os_str().as_bytes();
+            let args = args.strip_suffix(b"\0").unwrap_or(args);
+            if args.is_empty() {
+                vec![]
+            } else {
+                args.split(|byte| *byte == b'\0')
+                    .map(|arg| std::ffi::OsStr::from_bytes(arg).to_os_string())
+                    .collect()
+            }
+        };
+        #[cfg(windows)]
+        let args = vec![process_info.args.to_os_string()];
+        Self {
+            pid,
+            ppid: process_info.ppid,
+            program: process_info.program.to_cow_os_str().into_owned().into(),
+            args,
+        }
+    }
+}
+
+/// The tracked processes, which form a tree by their parent pids.
+#[derive(Debug, Clone)]
+pub struct ProcessTree {
+    root_pid: u32,
+    processes: HashMap<u32, Process>,
+}
+
+impl ProcessTree {
+    pub(crate) fn new(root_pid: u32) -> Self {
+        Self {
+            root_pid,
+            processes: HashMap::new(),
+        }
+    }
+
+    /// Adds `process`, replacing the one with the same pid.
+    /// Processes should be inserted in the order they are reported,
+    /// so that a pid maps to the last program it executed.
+    pub(crate) fn insert(&mut self, process: Process) {
+        self.processes.insert(process.pid, process);
+    }
+
+    pub fn root_pid(&self) -> u32 {
+        self.root_pid
+    }
+
+    pub fn root(&self) -> Option<&Process> {
+        self.get(self.root_pid)
+    }
+
+    pub fn get(&self, pid: u32) -> Option<&Process> {
+        self.processes.get(&pid)
+    }
+
+    pub fn children(&self, pid: u32) -> impl Iterator<Item = &Process> {
+        self.processes
+            .values()
+            .filter(move |process| process.ppid == pid && process.pid != pid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Process> {
+        self.processes.values()
+    }
+}
@@ -0,0 +1,883 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::{Duration, SystemTime},
+};
+
+use bincode::{
+    Decode, Encode,
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+};
+use fspy_shared::ipc::{AccessMode, AccessOutcome, EnvRead, PathAccess};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{AccessSummary, PathAccessIterable};
+
+/// The version of the report format written by this version of fspy.
+/// It's bumped on any change to the encoded types, and `Report::decode` keeps reading older versions.
+pub const REPORT_VERSION: u32 = 6;
+
+/// The header of the binary encoding, followed by the version as a little-endian `u32`.
+const BINARY_MAGIC: &[u8; 8] = b"FSPYREP\0";
+
+const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
+
+/// An owned record of a traced command, which can be persisted and loaded later.
+///
+/// It's encoded either as JSON (`to_json`), which is readable and stable across platforms,
+/// or as compact binary (`to_binary`). Both start with the format version, and `decode` accepts
+/// either encoding in any version up to `REPORT_VERSION`.
+/// Paths, programs, environment variables and arguments are encoded losslessly: those that are not
+/// valid UTF-8 are encoded as their bytes, which in JSON are arrays of numbers instead of strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct Report {
+    /// The pid of the traced command.
+    pub root_pid: u32,
+    pub accesses: Vec<ReportAccess>,
+    /// The accesses denied by the access policy, see `Command::access_policy`.
+    pub violations: Vec<ReportAccess>,
+    /// The first read of each environment variable in each process.
+    pub env_reads: Vec<ReportEnvRead>,
+    /// The tracked processes, ordered by pid.
+    pub processes: Vec<ReportProcess>,
+    pub exit_status: Option<ReportExitStatus>,
+    pub timing: Option<ReportTiming>,
+}
+
+/// Version 1, before reads of environment variables were recorded.
+#[derive(Deserialize, Decode)]
+struct ReportV1 {
+    root_pid: u32,
+    accesses: Vec<ReportAccessV2>,
+    processes: Vec<ReportProcess>,
+    exit_status: Option<ReportExitStatus>,
+    timing: Option<ReportTiming>,
+}
+
+impl From<ReportV1> for Report {
+    fn from(report: ReportV1) -> Self {
+        Self {
+            root_pid: report.root_pid,
+            accesses: report.accesses.into_iter().map(Into::into).collect(),
+            violations: vec![],
+            env_reads: vec![],
+            processes: report.processes,
+            exit_status: report.exit_status,
+            timing: report.timing,
+        }
+    }
+}
+
+/// Version 2, before the original paths of normalized accesses were recorded.
+#[derive(Deserialize, Decode)]
+struct ReportV2 {
+    root_pid: u32,
+    accesses: Vec<ReportAccessV2>,
+    env_reads: Vec<ReportEnvRead>,
+    processes: Vec<ReportProcess>,
+    exit_status: Option<ReportExitStatus>,
+    timing: Option<ReportTiming>,
+}
+
+impl From<ReportV2> for Report {
+    fn from(report: ReportV2) -> Self {
+        Self {
+            root_pid: report.root_pid,
+            accesses: report.accesses.into_iter().map(Into::into).collect(),
+            violations: vec![],
+            env_reads: report.env_reads,
+            processes: report.processes,
+            exit_status: report.exit_status,
+            timing: report.timing,
+        }
+    }
+}
+
+/// Version 3, before violations of access policies were recorded.
+#[derive(Deserialize, Decode)]
+struct ReportV3 {
+    root_pid: u32,
+    accesses: Vec<ReportAccess>,
+    env_reads: Vec<ReportEnvRead>,
+    processes: Vec<ReportProcess>,
+    exit_status: Option<ReportExitStatus>,
+    timing: Option<ReportTiming>,
+}
+
+impl From<ReportV3> for Report {
+    fn from(report: ReportV3) -> Self {
+        Self {
+            root_pid: report.root_pid,
+            accesses: report.accesses,
+            violations: vec![],
+            env_reads: report.env_reads,
+            processes: report.processes,
+            exit_status: report.exit_status,
+            timing: report.timing,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportAccess {
+    pub pid: u32,
+    pub mode: AccessMode,
+    #[serde(with = "os_string")]
+    pub path: PathBuf,
+    #[serde(with = "os_string::option")]
+    pub dest: Option<PathBuf>,
+    pub outcome: AccessOutcome,
+    pub follow_symlinks: bool,
+    /// The paths as spelled by the traced process, see `PathNormalization::keep_original`.
+    #[serde(with = "os_string::option")]
+    pub original_path: Option<PathBuf>,
+    #[serde(with = "os_string::option")]
+    pub original_dest: Option<PathBuf>,
+}
+
+/// `ReportAccess` in versions 1 and 2.
+#[derive(Deserialize, Decode)]
+struct ReportAccessV2 {
+    pid: u32,
+    mode: AccessMode,
+    path: PathBuf,
+    dest: Option<PathBuf>,
+    outcome: AccessOutcome,
+    follow_symlinks: bool,
+}
+
+impl From<ReportAccessV2> for ReportAccess {
+    fn from(access: ReportAccessV2) -> Self {
+        Self {
+            pid: access.pid,
+            mode: access.mode,
+            path: access.path,
+            dest: access.dest,
+            outcome: access.outcome,
+            follow_symlinks: access.follow_symlinks,
+            original_path: None,
+            original_dest: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportEnvRead {
+    pub pid: u32,
+    #[serde(with = "os_string")]
+    pub name: OsString,
+    pub is_set: bool,
+    /// Only recorded with `Command::record_env_values`.
+    #[serde(with = "os_string::option")]
+    pub value: Option<OsString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportProcess {
+    pub pid: u32,
+    pub ppid: u32,
+    #[serde(with = "os_string")]
+    pub program: PathBuf,
+    /// The arguments including argv[0].
+    #[serde(with = "os_string::vec")]
+    pub args: Vec<OsString>,
+}
+
+/// Encodes `OsString`s and `PathBuf`s as strings if they are valid UTF-8, and as their bytes otherwise.
+///
+/// Strings that are valid UTF-8 are encoded like `String`, and bincode encodes bytes like strings too,
+/// so only the strings that are not valid UTF-8 are encoded differently in JSON.
+mod os_string {
+    use std::ffi::{OsStr, OsString};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn to_bytes(os_str: &OsStr) -> &[u8] {
+        os_str.as_encoded_bytes()
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> OsString {
+        #[cfg(unix)]
+        let os_string = std::os::unix::ffi::OsStringExt::from_vec(bytes);
+        // On Windows, only unpaired surrogates in UTF-16 strings are not valid UTF-8,
+        // which are replaced.
+        #[cfg(not(unix))]
+        let os_string = String::from_utf8_lossy(&bytes).into_owned().into();
+        os_string
+    }
+
+    struct Ref<'a>(&'a OsStr);
+
+    impl Serialize for Ref<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0.to_str() {
+                Some(str) => serializer.serialize_str(str),
+                None => serializer.serialize_bytes(to_bytes(self.0)),
+            }
+        }
+    }
+
+    struct Owned(OsString);
+
+    impl<'de> Deserialize<'de> for Owned {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            #[derive(Deserialize)]
+            #[serde(untagged)]
+            enum StringOrBytes {
+                String(String),
+                Bytes(Vec<u8>),
+            }
+            Ok(Self(match StringOrBytes::deserialize(deserializer)? {
+                StringOrBytes::String(string) => string.into(),
+                StringOrBytes::Bytes(bytes) => from_bytes(bytes),
+            }))
+        }
+    }
+
+    pub fn serialize<S: Serializer, T: AsRef<OsStr>>(
+        os_string: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Ref(os_string.as_ref()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: From<OsString>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Ok(Owned::deserialize(deserializer)?.0.into())
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer, T: AsRef<OsStr>>(
+            os_string: &Option<T>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            os_string
+                .as_ref()
+                .map(|os_string| Ref(os_string.as_ref()))
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: From<OsString>>(
+            deserializer: D,
+        ) -> Result<Option<T>, D::Error> {
+            Ok(Option::<Owned>::deserialize(deserializer)?.map(|owned| owned.0.into()))
+        }
+    }
+
+    pub mod vec {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            os_strings: &[OsString],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(os_strings.iter().map(|os_string| Ref(os_string)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<OsString>, D::Error> {
+            let owned = Vec::<Owned>::deserialize(deserializer)?;
+            Ok(owned.into_iter().map(|owned| owned.0).collect())
+        }
+    }
+}
+
+// `Encode` and `Decode` of the types with `OsString`s and `PathBuf`s, which bincode only supports
+// if they are valid UTF-8, encode the fields in order like the derived ones, with their bytes.
+
+impl Encode for ReportAccess {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.pid.encode(encoder)?;
+        self.mode.encode(encoder)?;
+        os_string::to_bytes(self.path.as_os_str()).encode(encoder)?;
+        encode_option_path(self.dest.as_deref(), encoder)?;
+        self.outcome.encode(encoder)?;
+        self.follow_symlinks.encode(encoder)?;
+        encode_option_path(self.original_path.as_deref(), encoder)?;
+        encode_option_path(self.original_dest.as_deref(), encoder)
+    }
+}
+
+fn encode_option_path<E: Encoder>(path: Option<&Path>, encoder: &mut E) -> Result<(), EncodeError> {
+    path.map(|path| os_string::to_bytes(path.as_os_str()))
+        .encode(encoder)
+}
+
+impl<Context> Decode<Context> for ReportAccess {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            pid: Decode::decode(decoder)?,
+            mode: Decode::decode(decoder)?,
+            path: os_string::from_bytes(Decode::decode(decoder)?).into(),
+            dest: decode_option_path(decoder)?,
+            outcome: Decode::decode(decoder)?,
+            follow_symlinks: Decode::decode(decoder)?,
+            original_path: decode_option_path(decoder)?,
+            original_dest: decode_option_path(decoder)?,
+        })
+    }
+}
+bincode::impl_borrow_decode!(ReportAccess);
+
+fn decode_option_path<D: Decoder>(decoder: &mut D) -> Result<Option<PathBuf>, DecodeError> {
+    Ok(Option::<Vec<u8>>::decode(decoder)?.map(|bytes| os_string::from_bytes(bytes).into()))
+}
+
+impl Encode for ReportEnvRead {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.pid.encode(encoder)?;
+        os_string::to_bytes(&self.name).encode(encoder)?;
+        self.is_set.encode(encoder)?;
+        self.value
+            .as_deref()
+            .map(os_string::to_bytes)
+            .encode(encoder)
+    }
+}
+
+impl<Context> Decode<Context> for ReportEnvRead {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            pid: Decode::decode(decoder)?,
+            name: os_string::from_bytes(Decode::decode(decoder)?),
+            is_set: Decode::decode(decoder)?,
+            value: Option::<Vec<u8>>::decode(decoder)?.map(os_string::from_bytes),
+        })
+    }
+}
+bincode::impl_borrow_decode!(ReportEnvRead);
+
+impl Encode for ReportProcess {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.pid.encode(encoder)?;
+        self.ppid.encode(encoder)?;
+        os_string::to_bytes(self.program.as_os_str()).encode(encoder)?;
+        let args: Vec<&[u8]> = self
+            .args
+            .iter()
+            .map(|arg| os_string::to_bytes(arg))
+            .collect();
+        args.encode(encoder)
+    }
+}
+
+impl<Context> Decode<Context> for ReportProcess {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            pid: Decode::decode(decoder)?,
+            ppid: Decode::decode(decoder)?,
+            program: os_string::from_bytes(Decode::decode(decoder)?).into(),
+            args: Vec::<Vec<u8>>::decode(decoder)?
+                .into_iter()
+                .map(os_string::from_bytes)
+                .collect(),
+        })
+    }
+}
+bincode::impl_borrow_decode!(ReportProcess);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ReportExitStatus {
+    Code(i32),
+    /// Terminated by the signal (Unix only).
+    Signal(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct ReportTiming {
+    pub started_at: SystemTime,
+    pub duration: Duration,
+}
+
+/// Only the version field of JSON reports, to pick the type to deserialize the rest as.
+#[derive(Deserialize)]
+struct JsonVersion {
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct JsonReportRef<'a> {
+    version: u32,
+    #[serde(flatten)]
+    report: &'a Report,
+}
+
+#[derive(Deserialize)]
+struct JsonReport<R> {
+    #[serde(flatten)]
+    report: R,
+}
+
+fn decode_json<R: DeserializeOwned>(json: &[u8]) -> io::Result<R> {
+    let JsonReport { report } = serde_json::from_slice(json).map_err(invalid_data)?;
+    Ok(report)
+}
+
+fn decode_binary<R: Decode<()>>(body: &[u8]) -> io::Result<R> {
+    let (report, len) = bincode::decode_from_slice(body, BINCODE_CONFIG).map_err(invalid_data)?;
+    if len != body.len() {
+        return Err(invalid_data("trailing bytes after the binary fspy report"));
+    }
+    Ok(report)
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn unsupported_version(version: u32) -> io::Error {
+    invalid_data(format!(
+        "unsupported report version {} (the latest supported is {})",
+        version, REPORT_VERSION
+    ))
+}
+
+impl ReportAccess {
+    pub fn new(pid: u32, path_access: PathAccess<'_>) -> Self {
+        Self {
+            pid,
+            mode: path_access.mode,
+            path: path_access.path.to_cow_os_str().into_owned().into(),
+            dest: path_access
+                .dest
+                .map(|dest| dest.to_cow_os_str().into_owned().into()),
+            outcome: path_access.outcome,
+            follow_symlinks: path_access.follow_symlinks,
+            original_path: path_access
+                .original_path
+                .map(|original_path| original_path.to_cow_os_str().into_owned().into()),
+            original_dest: path_access
+                .original_dest
+                .map(|original_dest| original_dest.to_cow_os_str().into_owned().into()),
+        }
+    }
+}
+
+impl ReportEnvRead {
+    pub fn new(pid: u32, env_read: EnvRead<'_>) -> Self {
+        Self {
+            pid,
+            name: env_read.name.to_cow_os_str().into_owned(),
+            is_set: env_read.is_set,
+            value: env_read
+                .value
+                .map(|value| value.to_cow_os_str().into_owned()),
+        }
+    }
+}
+
+impl<'a> Extend<&'a ReportAccess> for AccessSummary {
+    fn extend<T: IntoIterator<Item = &'a ReportAccess>>(&mut self, iter: T) {
+        for access in iter {
+            self.add_with_dest(access.mode, &access.path, access.dest.as_deref());
+        }
+    }
+}
+
+impl From<ExitStatus> for ReportExitStatus {
+    fn from(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+            return Self::Signal(signal);
+        }
+        Self::Code(status.code().unwrap_or(-1))
+    }
+}
+
+impl Report {
+    /// Creates a report of the accesses and the processes. The exit status and the timing are not known
+    /// from the accesses, and can be set afterwards.
+    pub fn new(accesses: &PathAccessIterable) -> Self {
+        let process_tree = accesses.process_tree();
+        let mut processes = process_tree
+            .iter()
+            .map(|process| ReportProcess {
+                pid: process.pid,
+                ppid: process.ppid,
+                program: process.program.clone(),
+                args: process.args.clone(),
+            })
+            .collect::<Vec<_>>();
+        processes.sort_by_key(|process| process.pid);
+        Self {
+            root_pid: process_tree.root_pid(),
+            accesses: accesses
+                .iter_with_pid()
+                .map(|(pid, path_access)| ReportAccess::new(pid, path_access))
+                .collect(),
+            #[cfg(unix)]
+            violations: accesses
+                .violations()
+                .map(|(pid, path_access)| ReportAccess::new(pid, path_access))
+                .collect(),
+            // Access policies are only supported on Unix.
+            #[cfg(not(unix))]
+            violations: vec![],
+            #[cfg(unix)]
+            env_reads: accesses
+                .env_reads()
+                .map(|(pid, env_read)| ReportEnvRead::new(pid, env_read))
+                .collect(),
+            // Reads of environment variables are only intercepted on Unix.
+            #[cfg(not(unix))]
+            env_reads: vec![],
+            processes,
+            exit_status: None,
+            timing: None,
+        }
+    }
+
+    pub fn to_json(&self) -> io::Result<String> {
+        serde_json::to_string(&JsonReportRef {
+            version: REPORT_VERSION,
+            report: self,
+        })
+        .map_err(invalid_data)
+    }
+
+    pub fn from_json(json: &[u8]) -> io::Result<Self> {
+        let JsonVersion { version } = serde_json::from_slice(json).map_err(invalid_data)?;
+        match version {
+            // Older versions are converted to the current `Report` here.
+            1 => Ok(decode_json::<ReportV1>(json)?.into()),
+            2 => Ok(decode_json::<ReportV2>(json)?.into()),
+            3 => Ok(decode_json::<ReportV3>(json)?.into()),
+            // Version 4 only differs in encoding strings that are not valid UTF-8 lossily, as strings,
+            // and version 5 in not encoding paths that are not valid UTF-8.
+            4 | 5 | REPORT_VERSION => decode_json(json),
+            _ => Err(unsupported_version(version)),
+        }
+    }
+
+    pub fn to_binary(&self) -> io::Result<Vec<u8>> {
+        let mut binary = BINARY_MAGIC.to_vec();
+        binary.extend_from_slice(&REPORT_VERSION.to_le_bytes());
+        bincode::encode_into_std_write(self, &mut binary, BINCODE_CONFIG).map_err(invalid_data)?;
+        Ok(binary)
+    }
+
+    pub fn from_binary(binary: &[u8]) -> io::Result<Self> {
+        let Some(body) = binary.strip_prefix(BINARY_MAGIC) else {
+            return Err(invalid_data("not a binary fspy report"));
+        };
+        let Some((version, body)) = body.split_first_chunk::<4>() else {
+            return Err(invalid_data("truncated binary fspy report"));
+        };
+        let version = u32::from_le_bytes(*version);
+        match version {
+            // Older versions are converted to the current `Report` here.
+            1 => Ok(decode_binary::<ReportV1>(body)?.into()),
+            2 => Ok(decode_binary::<ReportV2>(body)?.into()),
+            3 => Ok(decode_binary::<ReportV3>(body)?.into()),
+            4 | 5 | REPORT_VERSION => decode_binary(body),
+            _ => Err(unsupported_version(version)),
+        }
+    }
+
+    /// Reads a report in either encoding.
+    pub fn decode(data: &[u8]) -> io::Result<Self> {
+        if data.starts_with(BINARY_MAGIC) {
+            Self::from_binary(data)
+        } else {
+            Self::from_json(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            root_pid: 10,
+            accesses: vec![
+                ReportAccess::new(10, PathAccess::read("/a")),
+                ReportAccess::new(
+                    11,
+                    PathAccess::with_dest(AccessMode::Rename, "/b", "/c")
+                        .with_outcome(AccessOutcome::Failed(2))
+                        .with_follow_symlinks(false),
+                ),
+                ReportAccess {
+                    original_path: Some("/e/../a".into()),
+                    ..ReportAccess::new(11, PathAccess::read("/a"))
+                },
+            ],
+            violations: vec![ReportAccess::new(
+                11,
+                PathAccess::read("/etc/passwd").with_outcome(AccessOutcome::Failed(13)),
+            )],
+            env_reads: vec![
+                ReportEnvRead {
+                    pid: 10,
+                    name: "HOME".into(),
+                    is_set: true,
+                    value: None,
+                },
+                ReportEnvRead {
+                    pid: 11,
+                    name: "CC".into(),
+                    is_set: false,
+                    value: None,
+                },
+            ],
+            processes: vec![
+                ReportProcess {
+                    pid: 10,
+                    ppid: 1,
+                    program: "/bin/sh".into(),
+                    args: vec!["sh".into(), "-c".into(), "true".into()],
+                },
+                ReportProcess {
+                    pid: 11,
+                    ppid: 10,
+                    program: "/bin/true".into(),
+                    args: vec!["true".into()],
+                },
+            ],
+            exit_status: Some(ReportExitStatus::Code(0)),
+            timing: Some(ReportTiming {
+                started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                duration: Duration::from_millis(1500),
+            }),
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let report = report();
+        let json = report.to_json().unwrap();
+        assert!(json.starts_with(&format!(r#"{{"version":{},"#, REPORT_VERSION)));
+        assert_eq!(Report::decode(json.as_bytes()).unwrap(), report);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let report = report();
+        let binary = report.to_binary().unwrap();
+        assert!(binary.len() < report.to_json().unwrap().len());
+        assert_eq!(Report::decode(&binary).unwrap(), report);
+    }
+
+    /// The report without violations, which versions before 4 don't have.
+    fn report_v3() -> Report {
+        Report {
+            violations: vec![],
+            ..report()
+        }
+    }
+
+    /// The JSON of `report` in version 3, which has the same fields without violations.
+    fn json_v3(report: &Report, version: u32) -> serde_json::Value {
+        let mut json = serde_json::to_value(report).unwrap();
+        let json_object = json.as_object_mut().unwrap();
+        json_object.remove("violations");
+        json_object.insert("version".into(), version.into());
+        json
+    }
+
+    #[test]
+    fn version_3() {
+        let report = report_v3();
+
+        let json = json_v3(&report, 3);
+        assert_eq!(Report::decode(json.to_string().as_bytes()).unwrap(), report);
+
+        let mut binary = BINARY_MAGIC.to_vec();
+        binary.extend_from_slice(&3u32.to_le_bytes());
+        let fields = (
+            report.root_pid,
+            &report.accesses,
+            &report.env_reads,
+            &report.processes,
+            report.exit_status,
+            report.timing,
+        );
+        bincode::encode_into_std_write(fields, &mut binary, BINCODE_CONFIG).unwrap();
+        assert_eq!(Report::decode(&binary).unwrap(), report);
+    }
+
+    /// The report without the original paths, which versions before 3 don't have.
+    fn report_v2() -> Report {
+        let mut report = report_v3();
+        for access in &mut report.accesses {
+            access.original_path = None;
+            access.original_dest = None;
+        }
+        report
+    }
+
+    /// The JSON of `report` in an older version, which has the same fields without the original paths.
+    fn json_v2(report: &Report, version: u32) -> serde_json::Value {
+        let mut json = json_v3(report, version);
+        for access in json["accesses"].as_array_mut().unwrap() {
+            let access_object = access.as_object_mut().unwrap();
+            access_object.remove("original_path");
+            access_object.remove("original_dest");
+        }
+        json
+    }
+
+    /// `ReportAccess` in an older version, which is encoded like the same fields without the original paths.
+    type AccessV2<'a> = (
+        u32,
+        AccessMode,
+        &'a PathBuf,
+        &'a Option<PathBuf>,
+        AccessOutcome,
+        bool,
+    );
+
+    fn accesses_v2(report: &Report) -> Vec<AccessV2<'_>> {
+        report
+            .accesses
+            .iter()
+            .map(|access| {
+                (
+                    access.pid,
+                    access.mode,
+                    &access.path,
+                    &access.dest,
+                    access.outcome,
+                    access.follow_symlinks,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn version_1() {
+        let report = Report {
+            env_reads: vec![],
+            ..report_v2()
+        };
+
+        let mut json = json_v2(&report, 1);
+        json.as_object_mut().unwrap().remove("env_reads");
+        assert_eq!(Report::decode(json.to_string().as_bytes()).unwrap(), report);
+
+        // Version 1 is encoded like the same fields without `env_reads`.
+        let mut binary = BINARY_MAGIC.to_vec();
+        binary.extend_from_slice(&1u32.to_le_bytes());
+        let fields = (
+            report.root_pid,
+            accesses_v2(&report),
+            &report.processes,
+            report.exit_status,
+            report.timing,
+        );
+        bincode::encode_into_std_write(fields, &mut binary, BINCODE_CONFIG).unwrap();
+        assert_eq!(Report::decode(&binary).unwrap(), report);
+    }
+
+    #[test]
+    fn version_2() {
+        let report = report_v2();
+
+        let json = json_v2(&report, 2);
+        assert_eq!(Report::decode(json.to_string().as_bytes()).unwrap(), report);
+
+        let mut binary = BINARY_MAGIC.to_vec();
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        let fields = (
+            report.root_pid,
+            accesses_v2(&report),
+            &report.env_reads,
+            &report.processes,
+            report.exit_status,
+            report.timing,
+        );
+        bincode::encode_into_std_write(fields, &mut binary, BINCODE_CONFIG).unwrap();
+        assert_eq!(Report::decode(&binary).unwrap(), report);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_strings() {
+        use std::os::unix::ffi::OsStringExt as _;
+
+        let report = report();
+        let report = Report {
+            accesses: vec![
+                ReportAccess::new(
+                    10,
+                    PathAccess::with_dest(AccessMode::Rename, "/b", "/c")
+                        .with_follow_symlinks(false),
+                ),
+                ReportAccess {
+                    path: OsString::from_vec(b"/\xff".to_vec()).into(),
+                    dest: Some(OsString::from_vec(b"/\xfe".to_vec()).into()),
+                    original_path: Some(OsString::from_vec(b"\xff".to_vec()).into()),
+                    original_dest: Some(OsString::from_vec(b"\xfe".to_vec()).into()),
+                    ..report.accesses[1].clone()
+                },
+            ],
+            env_reads: vec![ReportEnvRead {
+                pid: 10,
+                name: OsString::from_vec(b"A\xff".to_vec()),
+                is_set: true,
+                value: Some(OsString::from_vec(b"\xfe".to_vec())),
+            }],
+            processes: vec![ReportProcess {
+                program: OsString::from_vec(b"/bin/\xff".to_vec()).into(),
+                args: vec![OsString::from_vec(b"\xff".to_vec()), "a".into()],
+                ..report.processes[0].clone()
+            }],
+            ..report
+        };
+        let json = report.to_json().unwrap();
+        assert!(json.contains(r#""name":[65,255]"#), "{}", json);
+        assert!(json.contains(r#""args":[[255],"a"]"#), "{}", json);
+        assert!(json.contains(r#""path":[47,255]"#), "{}", json);
+        assert!(json.contains(r#""original_dest":[254]"#), "{}", json);
+        assert!(
+            json.contains(r#""program":[47,98,105,110,47,255]"#),
+            "{}",
+            json
+        );
+        assert_eq!(Report::decode(json.as_bytes()).unwrap(), report);
+        assert_eq!(
+            Report::decode(&report.to_binary().unwrap()).unwrap(),
+            report
+        );
+    }
+
+    #[test]
+    fn versions_4_and_5() {
+        // Versions 4 and 5 have the same fields, with all the strings valid UTF-8.
+        let report = report();
+
+        for version in [4u32, 5] {
+            let mut json = serde_json::to_value(&report).unwrap();
+            json.as_object_mut()
+                .unwrap()
+                .insert("version".into(), version.into());
+            assert_eq!(Report::decode(json.to_string().as_bytes()).unwrap(), report);
+
+            let mut binary = report.to_binary().unwrap();
+            binary[BINARY_MAGIC.len()..][..4].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(Report::decode(&binary).unwrap(), report);
+        }
+    }
+
+    #[test]
+    fn newer_version() {
+        let newer_version = REPORT_VERSION + 1;
+        let error =
+            Report::decode(format!(r#"{{"version":{}}}"#, newer_version).as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut binary = report().to_binary().unwrap();
+        binary[BINARY_MAGIC.len()..][..4].copy_from_slice(&newer_version.to_le_bytes());
+        assert!(Report::decode(&binary).is_err());
+    }
+}
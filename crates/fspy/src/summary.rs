@@ -0,0 +1,289 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    ops::{BitOr, BitOrAssign},
+    path::{Path, PathBuf},
+};
+
+use fspy_shared::ipc::{AccessMode, PathAccess};
+
+use crate::OwnedPathAccess;
+
+/// The kinds of accesses made to a path.
+///
+/// Access sets form a lattice ordered by inclusion: combining two sets with `|` gives the smallest set
+/// containing both, and combining is commutative, associative and idempotent, so the result doesn't depend
+/// on the order or the number of accesses. `AccessMode::ReadWrite` is the same as `Read | Write`.
+///
+/// The destination of a rename or a link is counted as written, because a directory entry is created there.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AccessSet(u8);
+
+impl AccessSet {
+    pub const EMPTY: Self = Self(0);
+    pub const READ: Self = Self(1 << 0);
+    pub const WRITE: Self = Self(1 << 1);
+    pub const READ_DIR: Self = Self(1 << 2);
+    pub const RENAME: Self = Self(1 << 3);
+    pub const REMOVE: Self = Self(1 << 4);
+    pub const CREATE_DIR: Self = Self(1 << 5);
+    pub const LINK: Self = Self(1 << 6);
+
+    const NAMES: [(Self, &str); 7] = [
+        (Self::READ, "Read"),
+        (Self::WRITE, "Write"),
+        (Self::READ_DIR, "ReadDir"),
+        (Self::RENAME, "Rename"),
+        (Self::REMOVE, "Remove"),
+        (Self::CREATE_DIR, "CreateDir"),
+        (Self::LINK, "Link"),
+    ];
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all the accesses in `other` are in `self`.
+    pub fn contains(self, other: impl Into<Self>) -> bool {
+        let other = other.into();
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the path is only read, and not changed in any way.
+    pub fn is_read_only(self) -> bool {
+        (Self::READ | Self::READ_DIR).contains(self)
+    }
+}
+
+impl From<AccessMode> for AccessSet {
+    fn from(mode: AccessMode) -> Self {
+        match mode {
+            AccessMode::Read => Self::READ,
+            AccessMode::Write => Self::WRITE,
+            AccessMode::ReadWrite => Self::READ | Self::WRITE,
+            AccessMode::ReadDir => Self::READ_DIR,
+            AccessMode::Rename => Self::RENAME,
+            AccessMode::Remove => Self::REMOVE,
+            AccessMode::CreateDir => Self::CREATE_DIR,
+            AccessMode::Link => Self::LINK,
+        }
+    }
+}
+
+impl BitOr for AccessSet {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for AccessSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Debug for AccessSet {
+    /// Formats like `Read | Write`, or `Empty`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("Empty");
+        }
+        let mut names = Self::NAMES
+            .iter()
+            .filter(|(set, _)| self.contains(*set))
+            .map(|(_, name)| *name);
+        if let Some(first) = names.next() {
+            f.write_str(first)?;
+        }
+        for name in names {
+            write!(f, " | {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Accesses merged by path, so that each path appears once with all the kinds of accesses made to it.
+///
+/// It can be built from the accesses of `PathAccessIterable::iter`, `TrackedChild::access_stream`
+/// or `Report::accesses` with `collect` or `extend`.
+/// Paths are compared as they are recorded, without resolving `..` or symlinks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessSummary {
+    root: Option<PathBuf>,
+    paths: BTreeMap<PathBuf, AccessSet>,
+}
+
+impl AccessSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a summary that only contains paths under `root`, keyed by their paths relative to it.
+    /// Accesses outside of `root` are ignored. `root` itself is keyed by the empty path.
+    pub fn relative_to(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Some(root.into()),
+            paths: BTreeMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Adds `access` to the summary of the path (or the relative path if `root` is set).
+    pub fn add(&mut self, path: &Path, access: impl Into<AccessSet>) {
+        let path = match &self.root {
+            Some(root) => match path.strip_prefix(root) {
+                Ok(relative_path) => relative_path,
+                Err(_) => return,
+            },
+            None => path,
+        };
+        let access = access.into();
+        if let Some(accesses) = self.paths.get_mut(path) {
+            *accesses |= access;
+        } else {
+            self.paths.insert(path.to_owned(), access);
+        }
+    }
+
+    pub(crate) fn add_with_dest(&mut self, mode: AccessMode, path: &Path, dest: Option<&Path>) {
+        self.add(path, mode);
+        if let Some(dest) = dest {
+            self.add(dest, AccessSet::WRITE);
+        }
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<AccessSet> {
+        self.paths.get(path.as_ref()).copied()
+    }
+
+    /// Iterates the paths in order along with their accesses.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, AccessSet)> {
+        self.paths
+            .iter()
+            .map(|(path, accesses)| (path.as_path(), *accesses))
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+impl<'a> Extend<PathAccess<'a>> for AccessSummary {
+    fn extend<T: IntoIterator<Item = PathAccess<'a>>>(&mut self, iter: T) {
+        for path_access in iter {
+            let dest = path_access.dest.map(|dest| dest.to_cow_os_str());
+            self.add_with_dest(
+                path_access.mode,
+                Path::new(&path_access.path.to_cow_os_str()),
+                dest.as_deref().map(Path::new),
+            );
+        }
+    }
+}
+
+impl<'a> Extend<&'a OwnedPathAccess> for AccessSummary {
+    fn extend<T: IntoIterator<Item = &'a OwnedPathAccess>>(&mut self, iter: T) {
+        for path_access in iter {
+            self.add_with_dest(
+                path_access.mode,
+                &path_access.path,
+                path_access.dest.as_deref(),
+            );
+        }
+    }
+}
+
+impl Extend<OwnedPathAccess> for AccessSummary {
+    fn extend<T: IntoIterator<Item = OwnedPathAccess>>(&mut self, iter: T) {
+        for path_access in iter {
+            self.extend([&path_access]);
+        }
+    }
+}
+
+impl<'a> FromIterator<PathAccess<'a>> for AccessSummary {
+    fn from_iter<T: IntoIterator<Item = PathAccess<'a>>>(iter: T) -> Self {
+        let mut summary = Self::new();
+        summary.extend(iter);
+        summary
+    }
+}
+
+impl FromIterator<OwnedPathAccess> for AccessSummary {
+    fn from_iter<T: IntoIterator<Item = OwnedPathAccess>>(iter: T) -> Self {
+        let mut summary = Self::new();
+        summary.extend(iter);
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_set_lattice() {
+        let read_write = AccessSet::from(AccessMode::ReadWrite);
+        assert_eq!(read_write, AccessSet::READ | AccessSet::WRITE);
+        assert!(read_write.contains(AccessMode::Read));
+        assert!(read_write.contains(AccessMode::Write));
+        assert!(!read_write.contains(AccessMode::ReadDir));
+        // Combining is idempotent and doesn't depend on the order.
+        assert_eq!(read_write | AccessSet::READ, read_write);
+        assert_eq!(
+            AccessSet::WRITE | AccessSet::READ_DIR,
+            AccessSet::READ_DIR | AccessSet::WRITE
+        );
+        assert!(AccessSet::EMPTY.is_read_only());
+        assert!((AccessSet::READ | AccessSet::READ_DIR).is_read_only());
+        assert!(!read_write.is_read_only());
+        assert_eq!(format!("{:?}", read_write), "Read | Write");
+        assert_eq!(format!("{:?}", AccessSet::EMPTY), "Empty");
+    }
+
+    #[test]
+    fn merge_by_path() {
+        let mut summary = AccessSummary::new();
+        summary.extend([
+            PathAccess::read("/a"),
+            PathAccess::read_dir("/b"),
+            PathAccess {
+                mode: AccessMode::Write,
+                ..PathAccess::read("/a")
+            },
+            PathAccess::with_dest(AccessMode::Rename, "/c", "/d"),
+        ]);
+        assert_eq!(summary.len(), 4);
+        assert_eq!(summary.get("/a"), Some(AccessSet::READ | AccessSet::WRITE));
+        assert_eq!(summary.get("/b"), Some(AccessSet::READ_DIR));
+        assert_eq!(summary.get("/c"), Some(AccessSet::RENAME));
+        assert_eq!(summary.get("/d"), Some(AccessSet::WRITE));
+    }
+
+    #[test]
+    fn relative_to_root() {
+        let mut summary = AccessSummary::relative_to("/root/dir");
+        summary.extend([
+            PathAccess::read("/root/dir/a"),
+            PathAccess::read_dir("/root/dir"),
+            PathAccess::read("/root/dir2/b"),
+            PathAccess::read("/etc/hosts"),
+        ]);
+        let paths = summary.iter().collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                (Path::new(""), AccessSet::READ_DIR),
+                (Path::new("a"), AccessSet::READ),
+            ]
+        );
+    }
+}
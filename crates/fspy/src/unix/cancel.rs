@@ -0,0 +1,193 @@
+//! Finalizing accesses before all the tracked processes exit, and killing the ones still alive.
+//!
+//! Tracked processes are found by the ipc socket that all of them inherit: it's what keeps the
+//! collecting of accesses from completing, so its holders are exactly the processes waited for.
+
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{Signal, kill, killpg},
+        wait::{Id, WaitPidFlag, WaitStatus, waitid},
+    },
+    unistd::Pid,
+};
+use tokio::{sync::Notify, time::sleep};
+
+/// How often the exit of the root process is checked when accesses are finalized on it.
+/// Waiting for the exit would reap the process, which is up to the owner of the child handle.
+const ROOT_EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Why the accesses of a tracked child were finalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    /// All the tracked processes exited, or at least closed the ipc socket they inherited.
+    AllExited,
+    /// The root process exited, and `Command::wait_for_descendants(false)` was set.
+    RootExited,
+    /// `AbortHandle::abort` was called.
+    Aborted,
+    /// The duration set by `Command::timeout` passed.
+    TimedOut,
+}
+
+/// Kills a tracked child and its descendants, and completes its accesses.
+///
+/// It can be cloned and used from any thread. Aborting after the accesses are completed does nothing.
+#[derive(Debug, Clone, Default)]
+pub struct AbortHandle(Arc<AbortState>);
+
+#[derive(Debug, Default)]
+struct AbortState {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl AbortHandle {
+    /// Kills all the tracked processes that are still alive with `SIGKILL`.
+    ///
+    /// On Linux, the processes are found by the ipc socket they inherited, even if they left the process group
+    /// or were reparented. Elsewhere, only the root process, or its process group if it was created by
+    /// `Command::process_group(0)`, is killed.
+    /// The accesses then complete with `Completion::Aborted`, and with the processes that couldn't be killed
+    /// in `PathAccessIterable::survivors`.
+    pub fn abort(&self) {
+        self.0.requested.store(true, Ordering::Release);
+        self.0.notify.notify_one();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.0.requested.load(Ordering::Acquire)
+    }
+
+    pub(crate) async fn aborted(&self) {
+        while !self.is_aborted() {
+            self.0.notify.notified().await;
+        }
+    }
+}
+
+/// Completes when the root process exits, without reaping it.
+pub(crate) async fn root_exited(root_pid: u32) {
+    while !has_exited(root_pid) {
+        sleep(ROOT_EXIT_POLL_INTERVAL).await;
+    }
+}
+
+fn has_exited(pid: u32) -> bool {
+    let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+    match waitid(Id::Pid(Pid::from_raw(pid as i32)), flags) {
+        Ok(WaitStatus::StillAlive) => false,
+        // Exited but not reaped, reaped already (ECHILD), or not waitable at all.
+        Ok(_) | Err(_) => true,
+    }
+}
+
+/// Whether `pid` is still a child of the current process, exited or not, so that its pid and pgid can't be reused.
+fn is_unreaped_child(pid: u32) -> bool {
+    let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+    waitid(Id::Pid(Pid::from_raw(pid as i32)), flags) != Err(Errno::ECHILD)
+}
+
+/// The processes that hold the ipc socket, or `None` if they can't be listed on this platform.
+#[cfg(target_os = "linux")]
+pub(crate) fn ipc_holders(ipc_socket: &IpcSocketId) -> Option<Vec<u32>> {
+    let current_pid = std::process::id();
+    let proc_dir = std::fs::read_dir("/proc").ok()?;
+    let holders = proc_dir
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| *pid != current_pid && ipc_socket.is_held_by(*pid))
+        .collect();
+    Some(holders)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn ipc_holders(_ipc_socket: &IpcSocketId) -> Option<Vec<u32>> {
+    None
+}
+
+/// Identifies the ipc socket in other processes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IpcSocketId {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    inode: u64,
+}
+
+impl IpcSocketId {
+    pub fn of(socket: impl std::os::fd::AsFd) -> io::Result<Self> {
+        let stat = nix::sys::stat::fstat(socket)?;
+        Ok(Self {
+            inode: stat.st_ino as u64,
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn is_held_by(&self, pid: u32) -> bool {
+        let link = format!("socket:[{}]", self.inode);
+        let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else {
+            return false;
+        };
+        fds.filter_map(Result::ok).any(|fd| {
+            std::fs::read_link(fd.path()).is_ok_and(|target| target.as_os_str() == link.as_str())
+        })
+    }
+}
+
+/// Kills the tracked processes as documented in `AbortHandle::abort`.
+/// Returns the processes that are still alive, or `None` if they can't be listed on this platform.
+pub(crate) fn kill_tracked(
+    root_pid: u32,
+    owns_process_group: bool,
+    ipc_socket: &IpcSocketId,
+) -> Option<Vec<u32>> {
+    if is_unreaped_child(root_pid) {
+        let _ = if owns_process_group {
+            killpg(Pid::from_raw(root_pid as i32), Signal::SIGKILL)
+        } else {
+            kill(Pid::from_raw(root_pid as i32), Signal::SIGKILL)
+        };
+    }
+    let holders = ipc_holders(ipc_socket)?;
+    #[cfg(target_os = "linux")]
+    let holders = holders
+        .into_iter()
+        .filter(|pid| !kill_holder(*pid, ipc_socket))
+        .collect();
+    Some(holders)
+}
+
+/// Kills `pid` if it holds the ipc socket.
+/// Returns false if it's still a holder and couldn't be killed.
+#[cfg(target_os = "linux")]
+fn kill_holder(pid: u32, ipc_socket: &IpcSocketId) -> bool {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if pidfd < 0 {
+        return Errno::last() == Errno::ESRCH;
+    }
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd as libc::c_int) };
+    // The pid may have been reused since the holders were listed.
+    // Checking again after the pidfd is opened makes sure the signal goes to a holder.
+    if !ipc_socket.is_held_by(pid) {
+        return true;
+    }
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            libc::SIGKILL,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    ret == 0 || Errno::last() == Errno::ESRCH
+}
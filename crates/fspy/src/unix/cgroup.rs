@@ -0,0 +1,188 @@
+//! Containing tracked processes in a cgroup v2, so that all of them can be found, waited for and killed,
+//! even the ones that close the ipc socket, and so that the resources they use can be measured.
+
+use std::{
+    fs::{self, File},
+    io,
+    os::fd::{AsFd, OwnedFd},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use nix::{
+    sys::{
+        signal::{Signal, kill},
+        statfs::{CGROUP2_SUPER_MAGIC, statfs},
+    },
+    unistd::{Pid, write},
+};
+use tokio::time::sleep;
+
+/// How often `cgroup.events` is read while waiting for the cgroup to be empty.
+const EMPTY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The resources used by the tracked processes, as accounted by their cgroup.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// The peak memory usage, if the memory controller is enabled for the cgroup.
+    pub memory_peak: Option<u64>,
+    /// The bytes read from block devices, if the io controller is enabled for the cgroup.
+    pub io_read_bytes: Option<u64>,
+    /// The bytes written to block devices, if the io controller is enabled for the cgroup.
+    pub io_write_bytes: Option<u64>,
+}
+
+/// Returns the directory of the cgroup v2 that the current process is in.
+pub fn current_cgroup() -> io::Result<PathBuf> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup")?;
+    let cgroup_path = cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "the current process is not in a cgroup v2",
+            )
+        })?;
+
+    // Fields of mountinfo: id, parent id, device, root, mount point, ..., " - ", fs type, ...
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    let (root, mount_point) = mountinfo
+        .lines()
+        .find_map(|line| {
+            let (mount, fs) = line.split_once(" - ")?;
+            if !fs.starts_with("cgroup2 ") {
+                return None;
+            }
+            let mut fields = mount.split(' ').skip(3);
+            Some((fields.next()?, fields.next()?))
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "cgroup2 is not mounted"))?;
+
+    let relative_path = Path::new(cgroup_path)
+        .strip_prefix(root)
+        .map_err(|_| io::Error::other("the cgroup of the current process is not mounted"))?;
+    Ok(Path::new(mount_point).join(relative_path))
+}
+
+/// A cgroup created for a tracked child, removed on drop if it's empty by then.
+#[derive(Debug)]
+pub(crate) struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn create_in(parent: &Path) -> io::Result<Self> {
+        if statfs(parent)?.filesystem_type() != CGROUP2_SUPER_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a cgroup v2 directory", parent.display()),
+            ));
+        }
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let path = parent.join(format!(
+            "fspy-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Opens `cgroup.procs` for `join` to be called in the child.
+    pub fn open_procs(&self) -> io::Result<OwnedFd> {
+        let procs = File::options()
+            .write(true)
+            .open(self.path.join("cgroup.procs"))?;
+        Ok(procs.into())
+    }
+
+    /// Moves the calling process into the cgroup of `procs`. It's async-signal-safe.
+    pub fn join(procs: impl AsFd) -> io::Result<()> {
+        write(procs, b"0")?;
+        Ok(())
+    }
+
+    pub fn pids(&self) -> io::Result<Vec<u32>> {
+        let procs = fs::read_to_string(self.path.join("cgroup.procs"))?;
+        Ok(procs.lines().filter_map(|pid| pid.parse().ok()).collect())
+    }
+
+    /// Whether any process is in the cgroup. Processes that exited but aren't reaped don't count.
+    fn is_populated(&self) -> io::Result<bool> {
+        let events = fs::read_to_string(self.path.join("cgroup.events"))?;
+        Ok(keyed_value(&events, "populated") != Some(0))
+    }
+
+    /// Completes when no process is in the cgroup, or when that can't be told anymore.
+    pub async fn emptied(&self) {
+        while self.is_populated().unwrap_or(false) {
+            sleep(EMPTY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Kills all the processes in the cgroup with `SIGKILL`.
+    pub fn kill(&self) -> io::Result<()> {
+        match fs::write(self.path.join("cgroup.kill"), "1") {
+            // `cgroup.kill` is only available since Linux 5.14.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                for pid in self.pids()? {
+                    let _ = kill(Pid::from_raw(pid as i32), Signal::SIGKILL);
+                }
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    pub fn resource_usage(&self) -> io::Result<ResourceUsage> {
+        let cpu_stat = fs::read_to_string(self.path.join("cpu.stat"))?;
+        let cpu_time = |key| Duration::from_micros(keyed_value(&cpu_stat, key).unwrap_or(0));
+
+        let memory_peak = fs::read_to_string(self.path.join("memory.peak"))
+            .ok()
+            .and_then(|peak| peak.trim().parse().ok());
+
+        // Each line is a device followed by its stats, such as `8:0 rbytes=1 wbytes=2 rios=3 wios=4`.
+        let io_stat = fs::read_to_string(self.path.join("io.stat")).ok();
+        let io_bytes = |key: &str| {
+            let io_stat = io_stat.as_ref()?;
+            let bytes = io_stat
+                .split_ascii_whitespace()
+                .filter_map(|stat| stat.split_once('='))
+                .filter(|(stat_key, _)| *stat_key == key)
+                .filter_map(|(_, value)| value.parse::<u64>().ok())
+                .sum();
+            Some(bytes)
+        };
+
+        Ok(ResourceUsage {
+            user_time: cpu_time("user_usec"),
+            system_time: cpu_time("system_usec"),
+            memory_peak,
+            io_read_bytes: io_bytes("rbytes"),
+            io_write_bytes: io_bytes("wbytes"),
+        })
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir(&self.path);
+    }
+}
+
+/// Finds the value of `key` in the content of a cgroup file made of `key value` lines.
+fn keyed_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (line_key, value) = line.split_once(' ')?;
+        if line_key == key {
+            value.parse().ok()
+        } else {
+            None
+        }
+    })
+}
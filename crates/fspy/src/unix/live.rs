@@ -0,0 +1,130 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use fspy_shared::ipc::{Event, shm};
+use futures_util::{Stream, stream};
+use memmap2::Mmap;
+use tokio::{sync::Notify, time::timeout};
+
+#[cfg(target_os = "linux")]
+use memmap2::MmapMut;
+#[cfg(target_os = "linux")]
+use nix::{
+    sys::memfd::{MFdFlags, memfd_create},
+    unistd::ftruncate,
+};
+#[cfg(target_os = "linux")]
+use std::io;
+
+use crate::OwnedPathAccess;
+
+/// How often streams check the chunks for new records.
+/// Tracked processes write records without notifying us, so there is nothing to wait on.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Default)]
+struct ShmChunksState {
+    mmaps: Vec<Arc<Mmap>>,
+    finished: bool,
+}
+
+/// The shm chunks that a tracked child and its descendants write accesses to.
+#[derive(Debug, Default)]
+pub(crate) struct ShmChunks {
+    state: Mutex<ShmChunksState>,
+    changed: Notify,
+}
+
+impl ShmChunks {
+    pub fn push(&self, mmap: Mmap) {
+        self.state.lock().unwrap().mmaps.push(Arc::new(mmap));
+        self.changed.notify_waiters();
+    }
+
+    /// Marks that all tracked processes have exited, so no more records will be written.
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+        self.changed.notify_waiters();
+    }
+
+    pub fn mmaps(&self) -> Vec<Arc<Mmap>> {
+        self.state.lock().unwrap().mmaps.clone()
+    }
+
+    /// Creates a chunk for recording accesses in the current process, and returns its writable mapping.
+    #[cfg(target_os = "linux")]
+    pub fn new_chunk(&self) -> io::Result<MmapMut> {
+        let memfd = memfd_create("fspy_shm", MFdFlags::MFD_CLOEXEC)?;
+        ftruncate(&memfd, shm::CHUNK_SIZE as libc::off_t)?;
+        let mmap_mut = unsafe { MmapMut::map_mut(&memfd) }?;
+        self.push(unsafe { Mmap::map(&memfd) }?);
+        Ok(mmap_mut)
+    }
+}
+
+/// What `TrackedChild` needs to create access streams.
+pub(crate) struct LiveAccesses {
+    pub shm_chunks: Arc<ShmChunks>,
+    /// Accesses recorded in the parent process while resolving the program.
+    pub exec_resolve_accesses: Vec<OwnedPathAccess>,
+}
+
+struct Tail {
+    shm_chunks: Arc<ShmChunks>,
+    cursors: Vec<(Arc<Mmap>, usize)>,
+    pending: VecDeque<OwnedPathAccess>,
+}
+
+impl Tail {
+    /// Reads the records written since the last call into `pending`.
+    /// Returns true if the chunks are finished, in which case all records have been read.
+    fn read_new_records(&mut self) -> bool {
+        let finished = {
+            let state = self.shm_chunks.state.lock().unwrap();
+            let new_mmaps = &state.mmaps[self.cursors.len()..];
+            self.cursors
+                .extend(new_mmaps.iter().map(|mmap| (Arc::clone(mmap), 0)));
+            state.finished
+        };
+        for (mmap, position) in &mut self.cursors {
+            while let Some(record) = shm::read_record(mmap, position) {
+                if let Event::Access(path_access) = record.event {
+                    self.pending
+                        .push_back(OwnedPathAccess::new(record.pid, path_access));
+                }
+            }
+        }
+        finished
+    }
+}
+
+impl LiveAccesses {
+    pub fn stream(&self) -> impl Stream<Item = OwnedPathAccess> + Send + 'static {
+        let tail = Tail {
+            shm_chunks: Arc::clone(&self.shm_chunks),
+            cursors: vec![],
+            pending: self.exec_resolve_accesses.iter().cloned().collect(),
+        };
+        stream::unfold(tail, |mut tail| async move {
+            loop {
+                if let Some(path_access) = tail.pending.pop_front() {
+                    return Some((path_access, tail));
+                }
+                // Created before reading so that a notification during the read is not missed.
+                let shm_chunks = Arc::clone(&tail.shm_chunks);
+                let changed = shm_chunks.changed.notified();
+                let finished = tail.read_new_records();
+                if !tail.pending.is_empty() {
+                    continue;
+                }
+                if finished {
+                    return None;
+                }
+                let _ = timeout(POLL_INTERVAL, changed).await;
+            }
+        })
+    }
+}
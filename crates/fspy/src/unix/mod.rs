@@ -5,6 +5,11 @@
 #[cfg(target_os = "linux")]
 mod syscall_handler;
 
+mod cancel;
+#[cfg(target_os = "linux")]
+mod cgroup;
+mod live;
+
 #[cfg(target_os = "macos")]
 mod macos_fixtures;
 
@@ -20,9 +25,10 @@ use fspy_shared_unix::{
 use memmap2::Mmap;
 
 #[cfg(target_os = "linux")]
-use seccomp_unotify::supervisor::supervise;
+use seccomp_unotify::supervisor::supervise_with;
 #[cfg(target_os = "macos")]
 use std::path::Path;
+use std::path::PathBuf;
 use std::{
     cell::RefCell,
     ffi::{CString, OsStr, OsString},
@@ -38,29 +44,51 @@ use std::{
             process::CommandExt,
         },
     },
+    pin::pin,
     sync::{
         Arc, LazyLock,
-        atomic::{AtomicU8, AtomicU16, Ordering, fence},
+        atomic::AtomicU16,
     },
 };
 
 #[cfg(target_os = "linux")]
 use syscall_handler::SyscallHandler;
 
-use bincode::{borrow_decode_from_slice, error::DecodeError};
+use bincode::error::DecodeError;
 use bumpalo::Bump;
 use passfd::{FdPassingExt as _, tokio::FdPassingExt as _};
 
-use tokio::{net::UnixStream, process::Child as TokioChild};
+use tokio::{
+    io::Interest, net::UnixStream, process::Child as TokioChild, sync::watch, time::sleep,
+};
 
-use fspy_shared::ipc::{BINCODE_CONFIG, PathAccess};
-use futures_util::{FutureExt, future::try_join};
+use fspy_shared::ipc::{CwdChange, EnvRead, Event, PathAccess, Record, shm};
+#[cfg(target_os = "linux")]
+use futures_util::future::try_join;
+use futures_util::{
+    FutureExt,
+    future::{Either, FusedFuture, pending, select, select_all},
+};
 use nix::fcntl::{FcntlArg, FdFlag, OFlag, fcntl};
 
 #[cfg(target_os = "linux")]
 use nix::sys::memfd::{MFdFlags, memfd_create};
 
-use crate::{Command, TrackedChild, arena::PathAccessArena};
+use crate::{
+    Command, OwnedPathAccess,
+    arena::PathAccessArena,
+    command::{SpawnChild, Spawned},
+    process::{Process, ProcessTree},
+};
+
+use cancel::IpcSocketId;
+pub use cancel::{AbortHandle, Completion};
+#[cfg(target_os = "linux")]
+use cgroup::Cgroup;
+#[cfg(target_os = "linux")]
+pub use cgroup::{ResourceUsage, current_cgroup};
+pub(crate) use live::LiveAccesses;
+use live::ShmChunks;
 
 #[derive(Debug, Clone)]
 pub struct SpyInner {
@@ -140,40 +168,117 @@ fn unset_fd_flag(fd: BorrowedFd<'_>, flag_to_remove: FdFlag) -> io::Result<()> {
 // }
 
 pub struct PathAccessIterable {
-    arenas: Vec<PathAccessArena>,
-    shm_mmaps: Vec<Mmap>,
+    /// The root process as spawned by the parent, in case it doesn't report itself.
+    root_process: Process,
+    /// Accesses recorded in the parent while resolving the program of the root process.
+    exec_resolve_accesses: PathAccessArena,
+    /// The chunks along with where their records end.
+    /// Records are only read up to the ends, as processes still alive may keep writing after completion.
+    shm_mmaps: Vec<(Arc<Mmap>, usize)>,
+    completion: Completion,
+    survivors: Vec<u32>,
+    #[cfg(target_os = "linux")]
+    resource_usage: Option<ResourceUsage>,
 }
 
 impl PathAccessIterable {
+    fn records(&self) -> impl Iterator<Item = Record<'_>> {
+        self.shm_mmaps.iter().flat_map(|(mmap, end)| {
+            let buf = &mmap.deref().deref()[..*end];
+            let mut position = 0usize;
+            iter::from_fn(move || shm::read_record(buf, &mut position))
+        })
+    }
+
+    /// Why the accesses were completed.
+    pub fn completion(&self) -> Completion {
+        self.completion
+    }
+
+    /// The pids of the tracked processes that were still alive when the accesses were completed.
+    ///
+    /// It's only listed on Linux. It's empty if the completion is `Completion::AllExited`,
+    /// and may be incomplete for processes in the middle of exiting.
+    pub fn survivors(&self) -> &[u32] {
+        &self.survivors
+    }
+
+    /// The resources used by the tracked processes, if they were contained by `Command::cgroup`.
+    ///
+    /// It's read when the accesses complete, so it misses what survivors use afterwards.
+    #[cfg(target_os = "linux")]
+    pub fn resource_usage(&self) -> Option<&ResourceUsage> {
+        self.resource_usage.as_ref()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = PathAccess<'_>> {
+        self.iter_with_pid().map(|(_, path_access)| path_access)
+    }
+
+    /// Iterates accesses along with the pids of the processes they are from.
+    pub fn iter_with_pid(&self) -> impl Iterator<Item = (u32, PathAccess<'_>)> {
         let accesses_in_arena = self
-            .arenas
+            .exec_resolve_accesses
+            .borrow_accesses()
             .iter()
-            .flat_map(|arena| arena.borrow_accesses().iter())
-            .copied();
+            .map(|path_access| (self.root_process.pid, *path_access));
 
-        let accesses_in_shm = self.shm_mmaps.iter().flat_map(|mmap| {
-            let buf = mmap.deref();
-            let mut position = 0usize;
-            iter::from_fn(move || {
-                let (flag_buf, data_buf) = buf[position..].split_first()?;
-                let atomic_flag = unsafe { AtomicU8::from_ptr((flag_buf as *const u8).cast_mut()) };
-                let flag = atomic_flag.load(Ordering::Acquire);
-                if flag == 0 {
-                    return None;
-                };
-                fence(Ordering::Acquire);
-                let (path_access, decoded_size) =
-                    borrow_decode_from_slice::<PathAccess<'_>, _>(data_buf, BINCODE_CONFIG)
-                        .unwrap();
-
-                position += decoded_size + 1;
-
-                Some(path_access)
-            })
+        let accesses_in_shm = self.records().filter_map(|record| match record.event {
+            Event::Access(path_access) => Some((record.pid, path_access)),
+            Event::Process(_) | Event::EnvRead(_) | Event::CwdChange(_) | Event::Violation(_) => {
+                None
+            }
         });
         accesses_in_shm.chain(accesses_in_arena)
     }
+
+    /// Iterates the reads of environment variables along with the pids of the processes they are from.
+    ///
+    /// Only the first read of each variable in a process is included. Reads are intercepted in `getenv`
+    /// and `secure_getenv`, so programs that read `environ` directly or are statically linked are missed.
+    pub fn env_reads(&self) -> impl Iterator<Item = (u32, EnvRead<'_>)> {
+        self.records().filter_map(|record| match record.event {
+            Event::EnvRead(env_read) => Some((record.pid, env_read)),
+            Event::Access(_) | Event::Process(_) | Event::CwdChange(_) | Event::Violation(_) => {
+                None
+            }
+        })
+    }
+
+    /// Iterates the changes of working directories, by `chdir` and `fchdir`, along with the pids of
+    /// the processes they are from, in the order each process made them.
+    ///
+    /// Changes made under the seccomp supervisor (by statically linked programs) are recorded before
+    /// the call, with `AccessOutcome::Unknown`, and with the path passed to `chdir` rather than the one
+    /// `getcwd` would return.
+    pub fn cwd_changes(&self) -> impl Iterator<Item = (u32, CwdChange<'_>)> {
+        self.records().filter_map(|record| match record.event {
+            Event::CwdChange(cwd_change) => Some((record.pid, cwd_change)),
+            Event::Access(_) | Event::Process(_) | Event::EnvRead(_) | Event::Violation(_) => None,
+        })
+    }
+
+    /// Iterates the accesses denied by the access policy (see `Command::access_policy`) along with
+    /// the pids of the processes they are from. Denied accesses are not included in `iter`.
+    ///
+    /// The outcome of a violation is the error that the access failed with.
+    pub fn violations(&self) -> impl Iterator<Item = (u32, PathAccess<'_>)> {
+        self.records().filter_map(|record| match record.event {
+            Event::Violation(path_access) => Some((record.pid, path_access)),
+            Event::Access(_) | Event::Process(_) | Event::EnvRead(_) | Event::CwdChange(_) => None,
+        })
+    }
+
+    pub fn process_tree(&self) -> ProcessTree {
+        let mut process_tree = ProcessTree::new(self.root_process.pid);
+        process_tree.insert(self.root_process.clone());
+        for record in self.records() {
+            if let Event::Process(process_info) = record.event {
+                process_tree.insert(Process::from_info(record.pid, process_info));
+            }
+        }
+        process_tree
+    }
 }
 
 // https://github.com/nodejs/node/blob/5794e644b724c6c6cac02d306d87a4d6b78251e5/deps/uv/src/unix/core.c#L803-L808
@@ -188,21 +293,121 @@ fn duplicate_until_safe(mut fd: OwnedFd) -> io::Result<OwnedFd> {
     Ok(fd)
 }
 
-pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild> {
+/// Pairs the chunks with where their records end.
+/// If processes may still be writing, the ends are the ends of the records written so far.
+fn record_ends(mmaps: Vec<Arc<Mmap>>, may_be_writing: bool) -> Vec<(Arc<Mmap>, usize)> {
+    mmaps
+        .into_iter()
+        .map(|mmap| {
+            let end = if may_be_writing {
+                let mut position = 0usize;
+                while shm::read_record(&mmap, &mut position).is_some() {}
+                position
+            } else {
+                mmap.len()
+            };
+            (mmap, end)
+        })
+        .collect()
+}
+
+/// Finds the tracked processes still alive when the accesses complete early,
+/// after killing them unless the completion is `Completion::RootExited`.
+/// Returns `None` if they can't be listed on this platform.
+fn settle_survivors(
+    completion: Completion,
+    root_pid: u32,
+    owns_process_group: bool,
+    ipc_socket: &IpcSocketId,
+    #[cfg(target_os = "linux")] cgroup: Option<&Cgroup>,
+) -> Option<Vec<u32>> {
+    // The cgroup also has the processes that closed the ipc socket.
+    #[cfg(target_os = "linux")]
+    let cgroup_survivors = cgroup.and_then(|cgroup| {
+        if completion != Completion::RootExited && cgroup.kill().is_ok() {
+            return Some(vec![]);
+        }
+        cgroup.pids().ok()
+    });
+    let survivors = match completion {
+        Completion::RootExited => cancel::ipc_holders(ipc_socket),
+        _ => cancel::kill_tracked(root_pid, owns_process_group, ipc_socket),
+    };
+    #[cfg(target_os = "linux")]
+    let survivors = match cgroup_survivors {
+        Some(mut pids) => {
+            pids.extend(survivors.unwrap_or_default());
+            pids.sort_unstable();
+            pids.dedup();
+            Some(pids)
+        }
+        None => survivors,
+    };
+    survivors
+}
+
+/// Receives the shm fds that are already sent, without waiting for more.
+fn drain_shm_fds(shm_fd_receiver: &UnixStream, shm_chunks: &ShmChunks) -> io::Result<()> {
+    loop {
+        let received =
+            shm_fd_receiver.try_io(Interest::READABLE, || shm_fd_receiver.as_raw_fd().recv_fd());
+        let shm_fd = match received {
+            Ok(fd) => unsafe { OwnedFd::from_raw_fd(fd) },
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        shm_chunks.push(unsafe { Mmap::map(&shm_fd) }?);
+    }
+}
+
+/// Resolves the symlinks in `path` for `PathNormalization::canonicalize` of the accesses made
+/// before the root program starts.
+fn realpath(path: &[u8]) -> Option<Vec<u8>> {
+    let real_path = std::fs::canonicalize(OsStr::from_bytes(path)).ok()?;
+    Some(real_path.into_os_string().into_vec())
+}
+
+pub(crate) async fn spawn_impl<C: SpawnChild>(mut command: Command) -> io::Result<Spawned<C>> {
     let (shm_fd_sender, shm_fd_receiver) = UnixStream::pair()?;
 
     let shm_fd_sender = shm_fd_sender.into_std()?;
     shm_fd_sender.set_nonblocking(false)?;
     let shm_fd_sender = duplicate_until_safe(OwnedFd::from(shm_fd_sender))?;
 
+    let shm_chunks = Arc::new(ShmChunks::default());
+
     #[cfg(target_os = "linux")]
-    let supervisor = supervise::<SyscallHandler>()?;
+    let supervisor = supervise_with({
+        let shm_chunks = Arc::clone(&shm_chunks);
+        let path_filter = command.path_filter.clone();
+        let path_normalization = command.path_normalization;
+        let access_policy = command.access_policy.clone();
+        move || {
+            SyscallHandler::new(
+                Arc::clone(&shm_chunks),
+                path_filter.clone(),
+                path_normalization,
+                access_policy.clone(),
+            )
+        }
+    })?;
 
     #[cfg(target_os = "linux")]
     let mut supervisor_pre_exec = supervisor.pre_exec;
 
     let payload = Payload {
         ipc_fd: shm_fd_sender.as_raw_fd(),
+        path_filter: command.path_filter.clone(),
+        path_normalization: command.path_normalization,
+        access_policy: command.access_policy.clone(),
+        record_env_values: command.record_env_values,
 
         #[cfg(target_os = "macos")]
         fixtures: command.spy_inner.fixtures.clone(),
@@ -228,20 +433,62 @@ pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild>
 
     let mut exec = command.get_exec();
     let mut exec_resolve_accesses = PathAccessArena::default();
+    let mut denied = None;
     let mut pre_exec = handle_exec(
         &mut exec,
         ExecResolveConfig::search_path_enabled(None),
         &encoded_payload,
         |path_access| {
-            exec_resolve_accesses.add(path_access);
+            let payload = &encoded_payload.payload;
+            if let Some(access_policy) = &payload.access_policy
+                && let Err(errno) = access_policy.check(&path_access, realpath)
+            {
+                denied.get_or_insert(errno);
+                return;
+            }
+            payload
+                .path_normalization
+                .normalize_access(path_access, realpath, |path_access| {
+                    if payload.path_filter.matches_access(&path_access) {
+                        exec_resolve_accesses.add(path_access);
+                    }
+                });
         },
     )?;
+    // Nothing traces the exec of the root program, so it's failed here if the access policy denies it.
+    if let Some(errno) = denied {
+        return Err(io::Error::from_raw_os_error(errno));
+    }
+    let root_program = PathBuf::from(OsString::from_vec(exec.program.to_vec()));
+    let root_args = exec
+        .args
+        .iter()
+        .map(|arg| OsString::from_vec(arg.to_vec()))
+        .collect::<Vec<_>>();
     command.set_exec(exec);
 
-    let mut tokio_command = command.into_tokio_command();
+    let kill_on_drop = command.kill_on_drop;
+    let timeout = command.timeout;
+    let wait_for_descendants = command.wait_for_descendants;
+    let owns_process_group = command.process_group == Some(0);
+    let ipc_socket = IpcSocketId::of(&shm_fd_sender)?;
+    #[cfg(target_os = "linux")]
+    let cgroup = command
+        .cgroup_parent
+        .as_deref()
+        .map(Cgroup::create_in)
+        .transpose()?
+        .map(Arc::new);
+    #[cfg(target_os = "linux")]
+    let cgroup_procs = cgroup.as_deref().map(Cgroup::open_procs).transpose()?;
+    let mut std_command = command.into_std_command();
 
     unsafe {
-        tokio_command.pre_exec(move || {
+        std_command.pre_exec(move || {
+            #[cfg(target_os = "linux")]
+            if let Some(cgroup_procs) = &cgroup_procs {
+                Cgroup::join(cgroup_procs)?;
+            }
             #[cfg(target_os = "linux")]
             unset_fd_flag(preload_lib_memfd.as_fd(), FdFlag::FD_CLOEXEC)?;
             unset_fd_flag(shm_fd_sender.as_fd(), FdFlag::FD_CLOEXEC)?;
@@ -255,55 +502,181 @@ pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild>
         });
     }
 
-    let child = tokio_command.spawn()?;
-    // drop channel_sender in the parent process,
+    // Collect shm chunks in a task rather than in `accesses_future`,
+    // so that streams get accesses even if `accesses_future` is not polled yet.
+    // It's started before spawning, because the seccomp supervisor has to handle
+    // the `execve` of the child before the spawn can complete.
+    // It keeps running even if accesses are completed before all the tracked processes exit,
+    // so that the ones still alive aren't affected.
+    let shm_fd_receiver = Arc::new(shm_fd_receiver);
+    let (collecting_done_sender, collecting_done) = watch::channel(false);
+    let collecting = {
+        let shm_chunks = Arc::clone(&shm_chunks);
+        let shm_fd_receiver = Arc::clone(&shm_fd_receiver);
+        // The cgroup is removed when it's dropped, which is best done after the tracked processes exit.
+        #[cfg(target_os = "linux")]
+        let cgroup = cgroup.clone();
+        async move {
+            let shm_future = async {
+                loop {
+                    let shm_fd = match shm_fd_receiver.recv_fd().await {
+                        Ok(fd) => unsafe { OwnedFd::from_raw_fd(fd) },
+                        Err(err) => {
+                            if err.kind() == io::ErrorKind::UnexpectedEof {
+                                break;
+                            } else {
+                                return Err(err);
+                            }
+                        }
+                    };
+                    shm_chunks.push(unsafe { Mmap::map(&shm_fd) }?);
+                }
+                io::Result::Ok(())
+            };
+            #[cfg(target_os = "linux")]
+            let result = try_join(supervisor.handling_loop, shm_future)
+                .await
+                .map(|_| ());
+            #[cfg(not(target_os = "linux"))]
+            let result = shm_future.await;
+
+            shm_chunks.finish();
+            let _ = collecting_done_sender.send(true);
+            #[cfg(target_os = "linux")]
+            drop(cgroup);
+            result
+        }
+    };
+    let mut collecting = tokio::spawn(collecting).fuse();
+
+    // The spawn blocks until the child execs, so it runs on a blocking thread
+    // to let the supervisor run even on a current-thread runtime.
+    let child = tokio::task::spawn_blocking(move || {
+        C::spawn_with(std_command, kill_on_drop, std::process::Command::spawn)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    // `std_command` is dropped in the blocking task, which drops channel_sender in the parent process,
     // so that channel_receiver reaches eof as soon as the last descendant process exits.
-    drop(tokio_command);
 
-    // #[cfg(target_os = "linux")]
-    let arenas_future = async move {
-        let arenas = std::iter::once(exec_resolve_accesses);
-        #[cfg(target_os = "linux")]
-        let arenas = arenas.chain(
-            supervisor
-                .handling_loop
-                .await?
-                .into_iter()
-                .map(|handler| handler.arena),
-        );
-        io::Result::Ok(arenas.collect::<Vec<_>>())
+    let root_process = Process {
+        pid: child
+            .id()
+            .ok_or_else(|| io::Error::other("the spawned child has no pid"))?,
+        ppid: std::process::id(),
+        program: root_program,
+        args: root_args,
+    };
+
+    let live_accesses = LiveAccesses {
+        shm_chunks: Arc::clone(&shm_chunks),
+        exec_resolve_accesses: exec_resolve_accesses
+            .borrow_accesses()
+            .iter()
+            .map(|path_access| OwnedPathAccess::new(root_process.pid, *path_access))
+            .collect(),
     };
 
-    let shm_future = async move {
-        let mut shm_fds = Vec::<OwnedFd>::new();
-        loop {
-            let shm_fd = match shm_fd_receiver.recv_fd().await {
-                Ok(fd) => unsafe { OwnedFd::from_raw_fd(fd) },
-                Err(err) => {
-                    if err.kind() == io::ErrorKind::UnexpectedEof {
-                        break;
+    let abort_handle = AbortHandle::default();
+    let accesses_future = {
+        let abort_handle = abort_handle.clone();
+        let root_pid = root_process.pid;
+        async move {
+            let all_exited = async {
+                (&mut collecting).await.map_err(io::Error::other)??;
+                // Processes that closed the ipc socket can only be waited for with the cgroup.
+                #[cfg(target_os = "linux")]
+                if let Some(cgroup) = &cgroup {
+                    cgroup.emptied().await;
+                }
+                io::Result::Ok(())
+            };
+            let early_completion = async {
+                let timed_out = async {
+                    match timeout {
+                        Some(timeout) => sleep(timeout).await,
+                        None => pending().await,
+                    }
+                };
+                let root_exited = async {
+                    if wait_for_descendants {
+                        pending().await
                     } else {
-                        return Err(err);
+                        cancel::root_exited(root_pid).await
+                    }
+                };
+                select_all([
+                    abort_handle.aborted().map(|_| Completion::Aborted).boxed(),
+                    timed_out.map(|_| Completion::TimedOut).boxed(),
+                    root_exited.map(|_| Completion::RootExited).boxed(),
+                ])
+                .await
+                .0
+            };
+            let completed_early = match select(pin!(all_exited), pin!(early_completion)).await {
+                Either::Left((result, _)) => {
+                    result?;
+                    None
+                }
+                Either::Right((completion, _)) => Some(completion),
+            };
+            let (completion, survivors, collected_all) = match completed_early {
+                None => (Completion::AllExited, vec![], true),
+                Some(completion) => {
+                    let survivors = settle_survivors(
+                        completion,
+                        root_pid,
+                        owns_process_group,
+                        &ipc_socket,
+                        #[cfg(target_os = "linux")]
+                        cgroup.as_deref(),
+                    );
+                    match survivors {
+                        // Without survivors the collecting is about to complete, if it's not already,
+                        // and waiting for it makes sure no chunk in transit is missed.
+                        Some(survivors) if survivors.is_empty() => {
+                            if !collecting.is_terminated() {
+                                collecting.await.map_err(io::Error::other)??;
+                            }
+                            // Killed processes that closed the ipc socket may not have exited yet.
+                            #[cfg(target_os = "linux")]
+                            if let Some(cgroup) = &cgroup {
+                                cgroup.emptied().await;
+                            }
+                            (completion, survivors, true)
+                        }
+                        survivors => {
+                            drain_shm_fds(&shm_fd_receiver, &shm_chunks)?;
+                            shm_chunks.finish();
+                            (completion, survivors.unwrap_or_default(), false)
+                        }
                     }
                 }
             };
-            shm_fds.push(shm_fd);
+            #[cfg(target_os = "linux")]
+            let resource_usage = cgroup.as_deref().map(Cgroup::resource_usage).transpose()?;
+            Ok(PathAccessIterable {
+                root_process,
+                exec_resolve_accesses,
+                shm_mmaps: record_ends(shm_chunks.mmaps(), !collected_all),
+                completion,
+                survivors,
+                #[cfg(target_os = "linux")]
+                resource_usage,
+            })
         }
-        io::Result::Ok(shm_fds)
-    };
-
-    let accesses_future = async move {
-        let (arenas, shm_fds) = try_join(arenas_future, shm_future).await?;
-        let shm_mmaps = shm_fds
-            .into_iter()
-            .map(|fd| unsafe { Mmap::map(&fd) })
-            .collect::<io::Result<Vec<Mmap>>>()?;
-        Ok(PathAccessIterable { arenas, shm_mmaps })
     }
     .boxed();
 
-    Ok(TrackedChild {
-        tokio_child: child,
+    Ok(Spawned {
+        child,
         accesses_future,
+        live_accesses,
+        abort_handle,
+        collecting_done: async move {
+            let mut collecting_done = collecting_done;
+            let _ = collecting_done.wait_for(|done| *done).await;
+        }
+        .boxed(),
     })
 }
@@ -1,42 +1,843 @@
-use std::{io, os::unix::ffi::OsStrExt};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+    sync::Arc,
+};
 
+use super::live::ShmChunks;
 use crate::arena::PathAccessArena;
-use fspy_shared::ipc::{AccessMode, NativeStr, PathAccess};
+use bstr::BString;
+use fspy_shared::ipc::{
+    AccessMode, AccessOutcome, CwdChange, NativeStr, PathAccess, ProcessInfo, Record, shm,
+};
+use fspy_shared_unix::{
+    access_policy::AccessPolicy,
+    exec::{Exec, ExecResolveConfig},
+    path::{PathNormalization, is_absolute, resolve_at},
+    path_filter::PathFilter,
+};
+use memmap2::MmapMut;
 use seccomp_unotify::{
     impl_handler,
-    supervisor::handler::arg::{CStrPtr, Fd, Ignored},
+    supervisor::{
+        NotifyResponse, StaleNotification,
+        handler::arg::{CStrPtr, CStrPtrArray, Caller, Fd, Ignored},
+    },
 };
 
 const PATH_MAX: usize = libc::PATH_MAX as usize;
 
-#[derive(Default, Debug)]
+/// Reads `path` from the target process, and resolves it against `dir` if it's relative.
+fn with_abs_path<R>(
+    dir: &Fd,
+    path: &CStrPtr,
+    f: impl FnOnce(&[u8]) -> io::Result<R>,
+) -> io::Result<R> {
+    path.read_with_buf::<PATH_MAX, _, _>(|path| {
+        if is_absolute(path) {
+            return f(path);
+        }
+        let dir = dir.get_path()?.into_vec();
+        f(&resolve_at(&dir, path))
+    })
+}
+
+/// Resolves the symlinks in paths for `PathNormalization::canonicalize` and the access policy, as seen by `caller`.
+/// `/proc/self` and `/proc/thread-self` are resolved to the ones of the caller rather than of the supervisor.
+struct CallerRealpath {
+    caller: Caller,
+    /// Why what's been read from `/proc/<pid>` of the caller can't be used, see `finish`.
+    error: Cell<Option<io::Error>>,
+}
+
+impl CallerRealpath {
+    fn new(caller: Caller) -> Self {
+        Self {
+            caller,
+            error: Cell::new(None),
+        }
+    }
+
+    fn realpath(&self, path: &[u8]) -> Option<Vec<u8>> {
+        let tid = self.caller.pid();
+        let path = Path::new(OsStr::from_bytes(path));
+        let proc_path = if let Ok(rest) = path.strip_prefix("/proc/self") {
+            Path::new(&format!("/proc/{}", tid)).join(rest)
+        } else if let Ok(rest) = path.strip_prefix("/proc/thread-self") {
+            Path::new(&format!("/proc/{}/task/{}", tid, tid)).join(rest)
+        } else {
+            let real_path = fs::canonicalize(path).ok()?;
+            return Some(real_path.into_os_string().into_vec());
+        };
+        let real_path = fs::canonicalize(proc_path);
+        // The pid may be of another process by now.
+        if let Err(err) = self.caller.check_valid() {
+            self.error.set(Some(err));
+            return None;
+        }
+        Some(real_path.ok()?.into_os_string().into_vec())
+    }
+
+    /// Fails with `StaleNotification` if the caller was gone after a path in its `/proc/<pid>` was resolved,
+    /// or with the error of checking that.
+    fn finish(&self) -> io::Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+/// Reads the pid (tgid) and the parent pid of the process that thread `tid` belongs to.
+fn read_pid_and_ppid(tid: u32) -> io::Result<(u32, u32)> {
+    let status = fs::read_to_string(format!("/proc/{}/status", tid))?;
+    let field = |name: &str| -> io::Result<u32> {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} not found in /proc/{}/status", name, tid),
+                )
+            })
+    };
+    Ok((field("Tgid:")?, field("PPid:")?))
+}
+
+/// Reads the start time of thread `tid`, which tells it apart from a later thread that reuses the id.
+fn read_start_time(tid: u32) -> io::Result<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", tid))?;
+    // The fields after the command name, which is in parentheses and may contain spaces, start at `state`.
+    stat.rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_ascii_whitespace().nth(19))
+        .and_then(|start_time| start_time.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("starttime not found in /proc/{}/stat", tid),
+            )
+        })
+}
+
+/// A change of the working directory by a thread whose syscall hasn't completed yet, see `begin_cwd_change`.
+#[derive(Debug)]
+struct PendingCwdChange {
+    start_time: u64,
+    pid: u32,
+    /// The directory passed to the syscall, resolved against the working directory before it.
+    dir: Vec<u8>,
+}
+
+/// Infers the outcome of changing the working directory to `dir`, from the working directory `after` the syscall.
+///
+/// The change succeeded if the working directory is `dir`. Otherwise `dir` is checked for the errors that the
+/// syscall fails with. If it has none, another thread may have changed the working directory since, and the
+/// outcome is unknown.
+fn cwd_change_outcome(dir: &[u8], after: &[u8]) -> AccessOutcome {
+    let real_dir = match fs::canonicalize(OsStr::from_bytes(dir)) {
+        Ok(real_dir) => real_dir,
+        Err(err) => {
+            return err
+                .raw_os_error()
+                .map_or(AccessOutcome::Unknown, AccessOutcome::Failed);
+        }
+    };
+    if real_dir.as_os_str().as_bytes() == after {
+        return AccessOutcome::Succeeded;
+    }
+    if !real_dir.is_dir() {
+        return AccessOutcome::Failed(libc::ENOTDIR);
+    }
+    match nix::unistd::access(&real_dir, nix::unistd::AccessFlags::X_OK) {
+        Ok(()) => AccessOutcome::Unknown,
+        Err(errno) => AccessOutcome::Failed(errno as i32),
+    }
+}
+
+#[derive(Debug)]
 pub struct SyscallHandler {
-    pub(crate) arena: PathAccessArena,
+    shm_chunks: Arc<ShmChunks>,
+    /// The chunk being written, and the position to write the next record.
+    shm_cursor: Option<(MmapMut, usize)>,
+    /// Maps the thread ids in notifications to pids. Threads and processes are identified along
+    /// with their start times (see `read_start_time`), so that reused ids are not mistaken for old ones.
+    thread_pids: HashMap<(u32, u64), (u32, u64)>,
+    reported_pids: HashSet<(u32, u64)>,
+    path_filter: PathFilter,
+    path_normalization: PathNormalization,
+    access_policy: Option<AccessPolicy>,
+    /// The changes of the working directory by thread id, recorded once the syscalls complete.
+    pending_cwd_changes: HashMap<u32, PendingCwdChange>,
 }
 
 impl SyscallHandler {
-    fn openat(&mut self, (_, path): (Ignored, CStrPtr)) -> io::Result<()> {
-        path.read_with_buf::<PATH_MAX, _, _>(|path| {
-            self.arena.add(PathAccess {
-                mode: AccessMode::Read,
-                path: NativeStr::from_bytes(path),
-            });
-            Ok(())
-        })?;
+    pub fn new(
+        shm_chunks: Arc<ShmChunks>,
+        path_filter: PathFilter,
+        path_normalization: PathNormalization,
+        access_policy: Option<AccessPolicy>,
+    ) -> Self {
+        Self {
+            shm_chunks,
+            shm_cursor: None,
+            thread_pids: HashMap::new(),
+            reported_pids: HashSet::new(),
+            path_filter,
+            path_normalization,
+            access_policy,
+            pending_cwd_changes: HashMap::new(),
+        }
+    }
+
+    /// Returns the pid of the process that the calling thread belongs to, and reports the process if it's not reported yet.
+    ///
+    /// The notification is checked after each read of `/proc`, which fails with `StaleNotification` if the
+    /// caller is gone, as its tid may have been reused.
+    fn pid_of(&mut self, caller: Caller) -> io::Result<u32> {
+        let tid = caller.pid();
+        let start_time = caller.validate(read_start_time(tid))?;
+        if let Some((pid, _)) = self.thread_pids.get(&(tid, start_time)) {
+            return Ok(*pid);
+        }
+        let (pid, ppid) = caller.validate(read_pid_and_ppid(tid))?;
+        let pid_start_time = if pid == tid {
+            start_time
+        } else {
+            caller.validate(read_start_time(pid))?
+        };
+        // An entry of an earlier thread with the same id is stale.
+        self.thread_pids
+            .retain(|(thread_id, _), _| *thread_id != tid);
+        self.thread_pids
+            .insert((tid, start_time), (pid, pid_start_time));
+        if self.reported_pids.insert((pid, pid_start_time)) {
+            let program = caller.validate(fs::read_link(format!("/proc/{}/exe", pid)))?;
+            let args = caller.validate(fs::read(format!("/proc/{}/cmdline", pid)))?;
+            self.write_record(Record::process(
+                pid,
+                ProcessInfo {
+                    ppid,
+                    program: program.as_path().into(),
+                    args: NativeStr::from_bytes(&args),
+                },
+            ))?;
+        }
+        Ok(pid)
+    }
+
+    /// Returns the error code to fail `path_access` with, if the access policy denies it.
+    fn check(&self, caller: Caller, path_access: &PathAccess<'_>) -> io::Result<Option<i32>> {
+        let Some(access_policy) = &self.access_policy else {
+            return Ok(None);
+        };
+        let realpath = CallerRealpath::new(caller);
+        let denied = access_policy
+            .check(path_access, |path| realpath.realpath(path))
+            .err();
+        realpath.finish()?;
+        Ok(denied)
+    }
+
+    /// Records `path_access` made by `caller`, or the violation if the access policy denies it.
+    fn add(&mut self, caller: Caller, path_access: PathAccess<'_>) -> io::Result<NotifyResponse> {
+        self.finish_cwd_change(caller)?;
+        if let Some(errno) = self.check(caller, &path_access)? {
+            let pid = self.pid_of(caller)?;
+            self.add_violation(caller, pid, path_access, errno)?;
+            return Ok(NotifyResponse::Error(errno));
+        }
+        self.add_unchecked(caller, path_access)?;
+        Ok(NotifyResponse::Continue)
+    }
+
+    /// Records `path_access` without checking it against the access policy,
+    /// for calls on file descriptors.
+    fn add_unchecked(&mut self, caller: Caller, path_access: PathAccess<'_>) -> io::Result<()> {
+        self.finish_cwd_change(caller)?;
+        let path_normalization = self.path_normalization;
+        let realpath = CallerRealpath::new(caller);
+        path_normalization.normalize_access(
+            path_access,
+            |path| realpath.realpath(path),
+            |path_access| {
+                realpath.finish()?;
+                if !self.path_filter.matches_access(&path_access) {
+                    return Ok(());
+                }
+                let pid = self.pid_of(caller)?;
+                self.write_record(Record::access(pid, path_access))
+            },
+        )
+    }
+
+    /// Records that `path_access`, made by `caller` of process `pid`, is denied with `errno`.
+    /// Violations are recorded regardless of the path filter.
+    fn add_violation(
+        &mut self,
+        caller: Caller,
+        pid: u32,
+        path_access: PathAccess<'_>,
+        errno: i32,
+    ) -> io::Result<()> {
+        let path_normalization = self.path_normalization;
+        let realpath = CallerRealpath::new(caller);
+        path_normalization.normalize_access(
+            path_access,
+            |path| realpath.realpath(path),
+            |path_access| {
+                realpath.finish()?;
+                self.write_record(Record::violation(
+                    pid,
+                    PathAccess {
+                        outcome: AccessOutcome::Failed(errno),
+                        ..path_access
+                    },
+                ))
+            },
+        )
+    }
+
+    /// Starts recording that `caller` is changing the working directory of its process to `dir`.
+    ///
+    /// The syscall hasn't run yet, and the supervisor isn't notified when it completes. The change is recorded
+    /// by `finish_cwd_change` on the next notification from the same thread, which comes after the syscall
+    /// completed, with the outcome inferred from the working directory then (see `cwd_change_outcome`).
+    /// A relative `dir` is resolved against the working directory before the call.
+    fn begin_cwd_change(&mut self, caller: Caller, dir: &[u8]) -> io::Result<()> {
+        self.finish_cwd_change(caller)?;
+        let pid = self.pid_of(caller)?;
+        let start_time = caller.validate(read_start_time(caller.pid()))?;
+        self.pending_cwd_changes.insert(
+            caller.pid(),
+            PendingCwdChange {
+                start_time,
+                pid,
+                dir: dir.to_vec(),
+            },
+        );
         Ok(())
     }
+
+    /// Records the change of the working directory that `caller` started, if any, now that the syscall completed.
+    /// It's called before recording the accesses of each notification, so that the change is recorded before them.
+    fn finish_cwd_change(&mut self, caller: Caller) -> io::Result<()> {
+        let tid = caller.pid();
+        let Some(pending) = self.pending_cwd_changes.remove(&tid) else {
+            return Ok(());
+        };
+        let (path, outcome) = if caller.validate(read_start_time(tid))? == pending.start_time {
+            let after = caller.validate(fs::read_link(format!("/proc/{}/cwd", tid)))?;
+            let after = after.into_os_string().into_vec();
+            match cwd_change_outcome(&pending.dir, &after) {
+                AccessOutcome::Succeeded => (after, AccessOutcome::Succeeded),
+                outcome => (pending.dir, outcome),
+            }
+        } else {
+            // The thread that made the change is gone, and its id is reused by another.
+            (pending.dir, AccessOutcome::Unknown)
+        };
+        self.write_record(Record::cwd_change(
+            pending.pid,
+            CwdChange {
+                path: NativeStr::from_bytes(&path),
+                outcome,
+            },
+        ))
+    }
+
+    /// Writes `record` to shm chunks in the same format as the preload library,
+    /// so that records from both are read in the same way.
+    fn write_record(&mut self, record: Record<'_>) -> io::Result<()> {
+        let record_size = shm::record_size(&record).map_err(io::Error::other)?;
+        if record_size > shm::CHUNK_SIZE {
+            return Err(io::Error::other(format!(
+                "The record size ({}) is greater than the shm chunk size ({})",
+                record_size,
+                shm::CHUNK_SIZE
+            )));
+        }
+        let has_room = matches!(
+            &self.shm_cursor,
+            Some((mmap_mut, position)) if *position + record_size <= mmap_mut.len()
+        );
+        if !has_room {
+            self.shm_cursor = Some((self.shm_chunks.new_chunk()?, 0));
+        }
+        let (mmap_mut, position) = self.shm_cursor.as_mut().unwrap();
+        shm::write_record(&mut mmap_mut[*position..*position + record_size], &record)
+            .map_err(io::Error::other)?;
+        *position += record_size;
+        Ok(())
+    }
+
+    /// Fails the syscall of `caller` with the error code of the access policy if handling it failed with
+    /// `result`, so that an access that couldn't be checked isn't let through. The failure is recorded as a
+    /// violation of `path` and `dest` as passed to the syscall, or as empty paths if they can't be read either.
+    fn fail_closed(
+        &mut self,
+        caller: Caller,
+        mode: AccessMode,
+        (path, dest): (&CStrPtr, Option<&CStrPtr>),
+        result: io::Result<NotifyResponse>,
+    ) -> io::Result<NotifyResponse> {
+        let errno = match (&self.access_policy, result) {
+            (Some(access_policy), Err(err)) if !StaleNotification::is(&err) => {
+                access_policy.errno()
+            }
+            (_, result) => return result,
+        };
+        let read = |path: &CStrPtr| {
+            path.read_with_buf::<PATH_MAX, _, _>(|path| Ok(path.to_vec()))
+                .unwrap_or_default()
+        };
+        let path = read(path);
+        let dest = dest.map(read);
+        // The syscall is failed even if the violation can't be recorded.
+        if let Ok(pid) = self.pid_of(caller) {
+            let _ = self.write_record(Record::violation(
+                pid,
+                PathAccess {
+                    mode,
+                    path: NativeStr::from_bytes(&path),
+                    dest: dest.as_deref().map(NativeStr::from_bytes),
+                    outcome: AccessOutcome::Failed(errno),
+                    follow_symlinks: true,
+                    original_path: None,
+                    original_dest: None,
+                },
+            ));
+        }
+        Ok(NotifyResponse::Error(errno))
+    }
+
+    fn add_at(
+        &mut self,
+        dir: &Fd,
+        path: &CStrPtr,
+        mode: AccessMode,
+        follow_symlinks: bool,
+    ) -> io::Result<NotifyResponse> {
+        let caller = dir.caller();
+        let result = with_abs_path(dir, path, |path| {
+            self.add(
+                caller,
+                PathAccess {
+                    mode,
+                    path: NativeStr::from_bytes(path),
+                    dest: None,
+                    outcome: AccessOutcome::Unknown,
+                    follow_symlinks,
+                    original_path: None,
+                    original_dest: None,
+                },
+            )
+        });
+        self.fail_closed(caller, mode, (path, None), result)
+    }
+
+    fn add_at_with_dest(
+        &mut self,
+        (dir, path): (&Fd, &CStrPtr),
+        (dest_dir, dest): (&Fd, &CStrPtr),
+        mode: AccessMode,
+        follow_symlinks: bool,
+    ) -> io::Result<NotifyResponse> {
+        let caller = dir.caller();
+        let result = with_abs_path(dir, path, |path| {
+            with_abs_path(dest_dir, dest, |dest| {
+                self.add(
+                    caller,
+                    PathAccess::with_dest(
+                        mode,
+                        NativeStr::from_bytes(path),
+                        NativeStr::from_bytes(dest),
+                    )
+                    .with_follow_symlinks(follow_symlinks),
+                )
+            })
+        });
+        self.fail_closed(caller, mode, (path, Some(dest)), result)
+    }
+
+    /// Records the accesses of resolving the program like the kernel does,
+    /// and reports the process with the new program if the exec is going to succeed.
+    /// The exec is denied if the access policy denies any of the accesses.
+    fn add_exec(
+        &mut self,
+        dir: &Fd,
+        path: &CStrPtr,
+        argv: &CStrPtrArray,
+    ) -> io::Result<NotifyResponse> {
+        let result = self.try_add_exec(dir, path, argv);
+        self.fail_closed(dir.caller(), AccessMode::Read, (path, None), result)
+    }
+
+    fn try_add_exec(
+        &mut self,
+        dir: &Fd,
+        path: &CStrPtr,
+        argv: &CStrPtrArray,
+    ) -> io::Result<NotifyResponse> {
+        let caller = dir.caller();
+        self.finish_cwd_change(caller)?;
+        let args = argv.read()?;
+        with_abs_path(dir, path, |program| {
+            let mut exec = Exec {
+                program: program.into(),
+                args: args.into_iter().map(BString::from).collect(),
+                envs: vec![],
+            };
+            let mut resolve_accesses = PathAccessArena::default();
+            let resolve_result = exec.resolve(
+                |path_access| resolve_accesses.add(path_access),
+                ExecResolveConfig::search_path_disabled(),
+            );
+            let denied = resolve_accesses
+                .borrow_accesses()
+                .iter()
+                .map(|path_access| self.check(caller, path_access))
+                .find_map(Result::transpose)
+                .transpose()?;
+            let pid = if resolve_result.is_ok() && denied.is_none() {
+                // The supervisor can't see the result of the exec, so it's reported once the program is found executable.
+                // The exec can still fail after that (with `E2BIG` for example), but rarely does.
+                let (pid, ppid) = caller.validate(read_pid_and_ppid(caller.pid()))?;
+                // The thread calling exec takes over the start time of the process along with the pid.
+                let pid_start_time = caller.validate(read_start_time(pid))?;
+                // Exec ends the other threads, and the thread calling it takes over the pid.
+                self.thread_pids
+                    .retain(|_, (thread_pid, _)| *thread_pid != pid);
+                self.reported_pids.insert((pid, pid_start_time));
+                let mut args = Vec::<u8>::new();
+                for arg in &exec.args {
+                    args.extend_from_slice(arg);
+                    args.push(b'\0');
+                }
+                self.write_record(Record::process(
+                    pid,
+                    ProcessInfo {
+                        ppid,
+                        program: NativeStr::from_bytes(&exec.program),
+                        args: NativeStr::from_bytes(&args),
+                    },
+                ))?;
+                pid
+            } else {
+                self.pid_of(caller)?
+            };
+            let path_normalization = self.path_normalization;
+            for path_access in resolve_accesses.borrow_accesses() {
+                if let Some(errno) = self.check(caller, path_access)? {
+                    self.add_violation(caller, pid, *path_access, errno)?;
+                    continue;
+                }
+                let realpath = CallerRealpath::new(caller);
+                path_normalization.normalize_access(
+                    *path_access,
+                    |path| realpath.realpath(path),
+                    |path_access| {
+                        realpath.finish()?;
+                        if self.path_filter.matches_access(&path_access) {
+                            self.write_record(Record::access(pid, path_access))?;
+                        }
+                        io::Result::Ok(())
+                    },
+                )?;
+            }
+            Ok(denied.map_or(NotifyResponse::Continue, NotifyResponse::Error))
+        })
+    }
+
+    fn add_symlink(
+        &mut self,
+        target: &CStrPtr,
+        (dir, linkpath): (&Fd, &CStrPtr),
+    ) -> io::Result<NotifyResponse> {
+        let result = self.try_add_symlink(target, (dir, linkpath));
+        self.fail_closed(
+            dir.caller(),
+            AccessMode::Link,
+            (target, Some(linkpath)),
+            result,
+        )
+    }
+
+    fn try_add_symlink(
+        &mut self,
+        target: &CStrPtr,
+        (dir, linkpath): (&Fd, &CStrPtr),
+    ) -> io::Result<NotifyResponse> {
+        target.read_with_buf::<PATH_MAX, _, _>(|target| {
+            with_abs_path(dir, linkpath, |linkpath| {
+                // A relative symlink target is relative to the directory containing the link.
+                let link_dir = Path::new(OsStr::from_bytes(linkpath))
+                    .parent()
+                    .unwrap_or(Path::new("/"));
+                let abs_target = link_dir.join(OsStr::from_bytes(target));
+                self.add(
+                    dir.caller(),
+                    PathAccess::with_dest(
+                        AccessMode::Link,
+                        abs_target.as_path(),
+                        NativeStr::from_bytes(linkpath),
+                    )
+                    .with_follow_symlinks(false),
+                )
+            })
+        })
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn open(&mut self, (path, flags): (CStrPtr, libc::c_int)) -> io::Result<NotifyResponse> {
+        self.openat((Fd::cwd(path.caller()), path, flags))
+    }
+    #[cfg(target_arch = "x86_64")]
+    fn creat(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Write, true)
+    }
+    fn openat(
+        &mut self,
+        (dir, path, flags): (Fd, CStrPtr, libc::c_int),
+    ) -> io::Result<NotifyResponse> {
+        let mode = AccessMode::from_open_flags(flags);
+        self.add_at(&dir, &path, mode, flags & libc::O_NOFOLLOW == 0)
+    }
     fn getdents64(&mut self, (fd,): (Fd,)) -> io::Result<()> {
         let path = fd.get_path()?;
-        self.arena.add(PathAccess {
-            mode: AccessMode::ReadDir,
-            path: NativeStr::from_bytes(path.as_bytes()),
-        });
-        Ok(())
+        self.add_unchecked(
+            fd.caller(),
+            PathAccess {
+                mode: AccessMode::ReadDir,
+                path: NativeStr::from_bytes(path.as_bytes()),
+                dest: None,
+                outcome: AccessOutcome::Unknown,
+                follow_symlinks: true,
+                original_path: None,
+                original_dest: None,
+            },
+        )
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn stat(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Read, true)
+    }
+    #[cfg(target_arch = "x86_64")]
+    fn lstat(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Read, false)
+    }
+    fn newfstatat(
+        &mut self,
+        (dir, path, _, flags): (Fd, CStrPtr, Ignored, libc::c_int),
+    ) -> io::Result<NotifyResponse> {
+        let follow_symlinks = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
+        self.add_at(&dir, &path, AccessMode::Read, follow_symlinks)
+    }
+    // `fstat` is not handled, because the path of the fd is recorded when it's opened.
+
+    #[cfg(target_arch = "x86_64")]
+    fn readlink(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Read, false)
+    }
+    fn readlinkat(&mut self, (dir, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        self.add_at(&dir, &path, AccessMode::Read, false)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn access(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Read, true)
+    }
+    fn faccessat(&mut self, (dir, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        self.add_at(&dir, &path, AccessMode::Read, true)
+    }
+    fn faccessat2(
+        &mut self,
+        (dir, path, _, flags): (Fd, CStrPtr, Ignored, libc::c_int),
+    ) -> io::Result<NotifyResponse> {
+        let follow_symlinks = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
+        self.add_at(&dir, &path, AccessMode::Read, follow_symlinks)
+    }
+
+    fn statx(
+        &mut self,
+        (dir, path, flags): (Fd, CStrPtr, libc::c_int),
+    ) -> io::Result<NotifyResponse> {
+        // Since Linux 6.11, the path can be null with AT_EMPTY_PATH to stat `dir` itself.
+        if path.is_null() {
+            let path = dir.get_path()?;
+            self.add_unchecked(
+                dir.caller(),
+                PathAccess::read(NativeStr::from_bytes(path.as_bytes())),
+            )?;
+            return Ok(NotifyResponse::Continue);
+        }
+        let follow_symlinks = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
+        self.add_at(&dir, &path, AccessMode::Read, follow_symlinks)
+    }
+
+    fn execve(&mut self, (path, argv): (CStrPtr, CStrPtrArray)) -> io::Result<NotifyResponse> {
+        self.add_exec(&Fd::cwd(path.caller()), &path, &argv)
+    }
+    fn execveat(
+        &mut self,
+        (dir, path, argv): (Fd, CStrPtr, CStrPtrArray),
+    ) -> io::Result<NotifyResponse> {
+        // With AT_EMPTY_PATH, the path is empty and `dir` itself is executed, which `with_abs_path` handles.
+        self.add_exec(&dir, &path, &argv)
+    }
+
+    fn chdir(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        let cwd = Fd::cwd(path.caller());
+        let response = self.add_at(&cwd, &path, AccessMode::Read, true)?;
+        if matches!(response, NotifyResponse::Continue) {
+            with_abs_path(&cwd, &path, |new_cwd| {
+                self.begin_cwd_change(path.caller(), new_cwd)
+            })?;
+        }
+        Ok(response)
+    }
+    fn fchdir(&mut self, (dir,): (Fd,)) -> io::Result<()> {
+        let Ok(new_cwd) = dir.get_path() else {
+            // An invalid fd, which makes the call fail without changing the working directory.
+            return self.finish_cwd_change(dir.caller());
+        };
+        self.begin_cwd_change(dir.caller(), new_cwd.as_bytes())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn rename(&mut self, (path, dest): (CStrPtr, CStrPtr)) -> io::Result<NotifyResponse> {
+        let cwd = Fd::cwd(path.caller());
+        self.add_at_with_dest((&cwd, &path), (&cwd, &dest), AccessMode::Rename, false)
+    }
+    fn renameat(
+        &mut self,
+        (dir, path, dest_dir, dest): (Fd, CStrPtr, Fd, CStrPtr),
+    ) -> io::Result<NotifyResponse> {
+        self.add_at_with_dest((&dir, &path), (&dest_dir, &dest), AccessMode::Rename, false)
+    }
+    fn renameat2(
+        &mut self,
+        (dir, path, dest_dir, dest, flags): (Fd, CStrPtr, Fd, CStrPtr, libc::c_uint),
+    ) -> io::Result<NotifyResponse> {
+        // RENAME_EXCHANGE swaps the two paths, which is reported as a rename in each direction.
+        if flags & libc::RENAME_EXCHANGE != 0 {
+            let response = self.add_at_with_dest(
+                (&dest_dir, &dest),
+                (&dir, &path),
+                AccessMode::Rename,
+                false,
+            )?;
+            if !matches!(response, NotifyResponse::Continue) {
+                return Ok(response);
+            }
+        }
+        self.add_at_with_dest((&dir, &path), (&dest_dir, &dest), AccessMode::Rename, false)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn unlink(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Remove, false)
+    }
+    fn unlinkat(&mut self, (dir, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        self.add_at(&dir, &path, AccessMode::Remove, false)
+    }
+    #[cfg(target_arch = "x86_64")]
+    fn rmdir(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::Remove, false)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn mkdir(&mut self, (path,): (CStrPtr,)) -> io::Result<NotifyResponse> {
+        self.add_at(&Fd::cwd(path.caller()), &path, AccessMode::CreateDir, false)
+    }
+    fn mkdirat(&mut self, (dir, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        self.add_at(&dir, &path, AccessMode::CreateDir, false)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn link(&mut self, (path, dest): (CStrPtr, CStrPtr)) -> io::Result<NotifyResponse> {
+        let cwd = Fd::cwd(path.caller());
+        self.add_at_with_dest((&cwd, &path), (&cwd, &dest), AccessMode::Link, false)
+    }
+    fn linkat(
+        &mut self,
+        (dir, path, dest_dir, dest, flags): (Fd, CStrPtr, Fd, CStrPtr, libc::c_int),
+    ) -> io::Result<NotifyResponse> {
+        let follow_symlinks = flags & libc::AT_SYMLINK_FOLLOW != 0;
+        self.add_at_with_dest(
+            (&dir, &path),
+            (&dest_dir, &dest),
+            AccessMode::Link,
+            follow_symlinks,
+        )
+    }
+    #[cfg(target_arch = "x86_64")]
+    fn symlink(&mut self, (target, linkpath): (CStrPtr, CStrPtr)) -> io::Result<NotifyResponse> {
+        self.add_symlink(&target, (&Fd::cwd(linkpath.caller()), &linkpath))
+    }
+    fn symlinkat(
+        &mut self,
+        (target, dir, linkpath): (CStrPtr, Fd, CStrPtr),
+    ) -> io::Result<NotifyResponse> {
+        self.add_symlink(&target, (&dir, &linkpath))
+    }
+}
+
+impl Drop for SyscallHandler {
+    /// Records the changes of the working directory whose syscalls were the last of their threads,
+    /// with unknown outcomes, as the threads are gone.
+    fn drop(&mut self) {
+        for (_, pending) in std::mem::take(&mut self.pending_cwd_changes) {
+            let _ = self.write_record(Record::cwd_change(
+                pending.pid,
+                CwdChange {
+                    path: NativeStr::from_bytes(&pending.dir),
+                    outcome: AccessOutcome::Unknown,
+                },
+            ));
+        }
     }
 }
 
 impl_handler!(
     SyscallHandler,
+    #[cfg(target_arch = "x86_64")] open
+    #[cfg(target_arch = "x86_64")] creat
     openat
     getdents64
+    #[cfg(target_arch = "x86_64")] stat
+    #[cfg(target_arch = "x86_64")] lstat
+    newfstatat
+    #[cfg(target_arch = "x86_64")] readlink
+    readlinkat
+    #[cfg(target_arch = "x86_64")] access
+    faccessat
+    faccessat2
+    statx
+    execve
+    execveat
+    chdir
+    fchdir
+    #[cfg(target_arch = "x86_64")] rename
+    renameat
+    renameat2
+    #[cfg(target_arch = "x86_64")] unlink
+    unlinkat
+    #[cfg(target_arch = "x86_64")] rmdir
+    #[cfg(target_arch = "x86_64")] mkdir
+    mkdirat
+    #[cfg(target_arch = "x86_64")] link
+    linkat
+    #[cfg(target_arch = "x86_64")] symlink
+    symlinkat;
+    // i386 binaries, which x86_64 runs too. Their stat syscalls of the native names take older structures,
+    // which doesn't matter as only the paths are read.
+    #[cfg(target_arch = "x86_64")]
+    X86:
+    stat64 => stat
+    lstat64 => lstat
+    fstatat64 => newfstatat
 );
@@ -1,21 +1,30 @@
 use std::{
+    collections::HashMap,
     convert::Infallible,
     env::temp_dir,
     ffi::{CStr, c_char, c_void},
     fs::{File, OpenOptions, create_dir},
     io, mem,
-    os::windows::{ffi::OsStrExt, io::AsRawHandle, process::ChildExt as _},
+    os::windows::{
+        ffi::OsStrExt,
+        io::AsRawHandle,
+        process::{ChildExt as _, CommandExt as _},
+    },
     path::Path,
     ptr::{null, null_mut},
     str::from_utf8,
     sync::Arc,
 };
 
-use crate::{TrackedChild, arena::PathAccessArena, command::Command};
+use crate::{
+    arena::PathAccessArena,
+    command::{Command, SpawnChild, Spawned},
+    process::{Process, ProcessTree},
+};
 use bincode::borrow_decode_from_slice;
 use const_format::formatcp;
 use fspy_shared::{
-    ipc::{BINCODE_CONFIG, PathAccess},
+    ipc::{BINCODE_CONFIG, Event, PathAccess, Record},
     windows::{PAYLOAD_ID, Payload},
 };
 use futures_util::{
@@ -78,7 +87,10 @@ fn named_pipe_server_stream(
 }
 
 pub struct PathAccessIterable {
-    arena: PathAccessArena,
+    root_pid: u32,
+    /// Accesses grouped by the pids of the processes they are from.
+    arenas: HashMap<u32, PathAccessArena>,
+    processes: Vec<Process>,
     // pipe_receiver: NamedPipeServer,
 }
 
@@ -86,7 +98,25 @@ const MESSAGE_MAX_LEN: usize = 4096;
 
 impl PathAccessIterable {
     pub fn iter(&self) -> impl Iterator<Item = PathAccess<'_>> {
-        self.arena.borrow_accesses().iter().copied()
+        self.iter_with_pid().map(|(_, path_access)| path_access)
+    }
+
+    /// Iterates accesses along with the pids of the processes they are from.
+    pub fn iter_with_pid(&self) -> impl Iterator<Item = (u32, PathAccess<'_>)> {
+        self.arenas.iter().flat_map(|(pid, arena)| {
+            arena
+                .borrow_accesses()
+                .iter()
+                .map(|path_access| (*pid, *path_access))
+        })
+    }
+
+    pub fn process_tree(&self) -> ProcessTree {
+        let mut process_tree = ProcessTree::new(self.root_pid);
+        for process in &self.processes {
+            process_tree.insert(process.clone());
+        }
+        process_tree
     }
     //     pub async fn next<'a>(&mut self, buf: &'a mut Vec<u8>) -> io::Result<Option<PathAccess<'a>>> {
     //         buf.resize(MESSAGE_MAX_LEN, 0);
@@ -131,9 +161,10 @@ impl SpyInner {
     }
 }
 
-pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild> {
+pub(crate) async fn spawn_impl<C: SpawnChild>(mut command: Command) -> io::Result<Spawned<C>> {
     let asni_dll_path_with_nul = Arc::clone(&command.spy_inner.asni_dll_path_with_nul);
-    let mut command = command.into_tokio_command();
+    let kill_on_drop = command.kill_on_drop;
+    let mut command = command.into_std_command();
 
     command.creation_flags(CREATE_SUSPENDED);
 
@@ -153,28 +184,9 @@ pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild>
 
     connect_fut.await?;
 
-    let accesses_future = async move {
-        let mut arena = PathAccessArena::default();
-
-        let mut buf = [0u8; MESSAGE_MAX_LEN];
-        loop {
-            let n = pipe_receiver.read(&mut buf).await?;
-            if n == 0 {
-                break;
-            }
-            let msg = &buf[..n];
-            let (path_access, decoded_len) =
-                borrow_decode_from_slice::<'_, PathAccess, _>(msg, BINCODE_CONFIG).unwrap();
-            assert_eq!(decoded_len, msg.len());
-            arena.add(path_access);
-        }
-        io::Result::Ok(PathAccessIterable { arena })
-    }
-    .boxed();
-
     // let path_access_stream = PathAccessIterable { pipe_receiver };
 
-    let child = command.spawn_with(|std_command| {
+    let child = C::spawn_with(command, kill_on_drop, |std_command| {
         let std_child = std_command.spawn()?;
 
         let mut dll_paths = asni_dll_path_with_nul.as_ptr().cast::<c_char>();
@@ -202,6 +214,7 @@ pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild>
 
         let payload = Payload {
             pipe_handle: handle_in_child.addr(),
+            parent_pid: std::process::id(),
             asni_dll_path_with_nul: asni_dll_path_with_nul.to_bytes(),
         };
         let payload_bytes = bincode::encode_to_vec(payload, BINCODE_CONFIG).unwrap();
@@ -229,8 +242,46 @@ pub(crate) async fn spawn_impl(mut command: Command) -> io::Result<TrackedChild>
     })?;
 
     drop(pipe_sender);
-    Ok(TrackedChild {
-        tokio_child: child,
+
+    let root_pid = child
+        .id()
+        .ok_or_else(|| io::Error::other("the spawned child has no pid"))?;
+    let accesses_future = async move {
+        let mut arenas = HashMap::<u32, PathAccessArena>::new();
+        let mut processes = Vec::<Process>::new();
+
+        let mut buf = [0u8; MESSAGE_MAX_LEN];
+        loop {
+            let n = pipe_receiver.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            let msg = &buf[..n];
+            let (record, decoded_len) =
+                borrow_decode_from_slice::<'_, Record, _>(msg, BINCODE_CONFIG).unwrap();
+            assert_eq!(decoded_len, msg.len());
+            match record.event {
+                Event::Access(path_access) => {
+                    arenas.entry(record.pid).or_default().add(path_access);
+                }
+                Event::Process(process_info) => {
+                    processes.push(Process::from_info(record.pid, process_info));
+                }
+                // Reads of environment variables, changes of working directories and access policies
+                // are only supported on Unix.
+                Event::EnvRead(_) | Event::CwdChange(_) | Event::Violation(_) => {}
+            }
+        }
+        io::Result::Ok(PathAccessIterable {
+            root_pid,
+            arenas,
+            processes,
+        })
+    }
+    .boxed();
+
+    Ok(Spawned {
+        child,
         accesses_future,
     })
 }
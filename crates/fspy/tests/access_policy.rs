@@ -0,0 +1,170 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{
+    env::current_exe,
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use fspy::{AccessMode, AccessOutcome, AccessPolicy, PathAccessIterable};
+use test_utils::{assert_contains, child_id, command_with_id};
+
+/// Returns the modes and the outcomes of the violations at `path`.
+fn violations_at(accesses: &PathAccessIterable, path: &Path) -> Vec<(AccessMode, AccessOutcome)> {
+    accesses
+        .violations()
+        .filter(|(_, access)| Path::new(&access.path.to_cow_os_str()) == path)
+        .map(|(_, access)| (access.mode, access.outcome))
+        .collect()
+}
+
+async fn accesses_with(
+    dir: &Path,
+    access_policy: AccessPolicy,
+    id: &str,
+) -> io::Result<PathAccessIterable> {
+    let mut command = command_with_id(id)?;
+    command.current_dir(dir).access_policy(access_policy);
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.accesses)
+}
+
+#[tokio::test]
+async fn write_outside_of_roots() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("out"))?;
+    fs::write(dir.join("src"), "src")?;
+
+    let accesses = accesses_with(
+        &dir,
+        AccessPolicy::default()
+            .allow_read("/")
+            .allow_write(dir.join("out").as_os_str().as_bytes()),
+        child_id!({
+            fs::write("out/a", "a").unwrap();
+            assert_eq!(fs::read("src").unwrap(), b"src");
+            let error = fs::write("b", "b").unwrap_err();
+            assert_eq!(error.raw_os_error(), Some(libc::EACCES));
+            let error = fs::remove_file("src").unwrap_err();
+            assert_eq!(error.raw_os_error(), Some(libc::EACCES));
+        }),
+    )
+    .await?;
+
+    // The denied calls are not made.
+    assert!(!dir.join("b").exists());
+    assert!(dir.join("src").exists());
+
+    assert_contains(&accesses, &dir.join("out/a"), AccessMode::Write);
+    assert_contains(&accesses, &dir.join("src"), AccessMode::Read);
+    let failed = AccessOutcome::Failed(libc::EACCES);
+    assert_eq!(
+        violations_at(&accesses, &dir.join("b")),
+        [(AccessMode::Write, failed)]
+    );
+    assert_eq!(
+        violations_at(&accesses, &dir.join("src")),
+        [(AccessMode::Remove, failed)]
+    );
+    // Violations are recorded instead of accesses.
+    assert!(
+        accesses
+            .iter()
+            .all(|access| Path::new(&access.path.to_cow_os_str()) != dir.join("b"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_outside_of_roots() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("inputs"))?;
+    fs::write(dir.join("inputs/a"), "a")?;
+    fs::write(dir.join("secret"), "secret")?;
+    std::os::unix::fs::symlink("../secret", dir.join("inputs/link"))?;
+
+    // What the test binary needs to run, and the inputs.
+    let test_binary_dir = current_exe()?.canonicalize()?.parent().unwrap().to_owned();
+    let mut access_policy = AccessPolicy::default().deny_with(libc::ENOENT);
+    for root in ["/usr", "/lib", "/lib64", "/etc", "/proc", "/sys", "/dev"]
+        .into_iter()
+        .map(PathBuf::from)
+        .chain([test_binary_dir, dir.join("inputs")])
+    {
+        access_policy = access_policy.allow_read(root.as_os_str().as_bytes());
+    }
+
+    let accesses = accesses_with(
+        &dir,
+        access_policy,
+        child_id!({
+            assert_eq!(fs::read("inputs/a").unwrap(), b"a");
+            let error = fs::read("secret").unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+            // The symlink is resolved, and leads out of the roots.
+            let error = fs::read("inputs/link").unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+            let error = fs::write("inputs/b", "b").unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        }),
+    )
+    .await?;
+
+    assert_contains(&accesses, &dir.join("inputs/a"), AccessMode::Read);
+    let failed = AccessOutcome::Failed(libc::ENOENT);
+    assert_eq!(
+        violations_at(&accesses, &dir.join("secret")),
+        [(AccessMode::Read, failed)]
+    );
+    assert_eq!(
+        violations_at(&accesses, &dir.join("inputs/link")),
+        [(AccessMode::Read, failed)]
+    );
+    assert_eq!(
+        violations_at(&accesses, &dir.join("inputs/b")),
+        [(AccessMode::Write, failed)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_roots_read() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::write(dir.join("input"), "input")?;
+
+    // Only what the test binary reads, without `/proc`, `/sys` and `/dev`, which the preload library
+    // reads itself.
+    let test_binary_dir = current_exe()?.canonicalize()?.parent().unwrap().to_owned();
+    let mut access_policy = AccessPolicy::default();
+    for root in ["/usr", "/lib", "/lib64", "/etc"]
+        .into_iter()
+        .map(PathBuf::from)
+        .chain([test_binary_dir, dir.clone()])
+    {
+        access_policy = access_policy.allow_read(root.as_os_str().as_bytes());
+    }
+
+    let accesses = accesses_with(
+        &dir,
+        access_policy,
+        child_id!({
+            assert_eq!(fs::read("input").unwrap(), b"input");
+        }),
+    )
+    .await?;
+
+    assert_contains(&accesses, &dir.join("input"), AccessMode::Read);
+    assert_eq!(accesses.violations().count(), 0);
+    Ok(())
+}
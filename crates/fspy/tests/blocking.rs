@@ -0,0 +1,74 @@
+mod test_utils;
+
+use std::{env::current_dir, fs::File, io};
+
+use fspy::AccessMode;
+use test_utils::{assert_contains, child_id, command_with_id};
+
+fn spawn_blocking_with_id(id: &str) -> io::Result<fspy::PathAccessIterable> {
+    let fspy::blocking::TrackedChild {
+        mut std_child,
+        accesses,
+        ..
+    } = command_with_id(id)?.spawn_blocking()?;
+    let status = std_child.wait()?;
+    assert!(status.success());
+    accesses.wait()
+}
+
+#[test]
+fn without_runtime() -> io::Result<()> {
+    let accesses = spawn_blocking_with_id(child_id!({
+        let _ = File::open("hello_blocking");
+    }))?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello_blocking").as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn inside_runtime() -> io::Result<()> {
+    let accesses = spawn_blocking_with_id(child_id!({
+        let _ = File::open("hello_blocking_in_runtime");
+    }))?;
+    assert_contains(
+        &accesses,
+        current_dir()
+            .unwrap()
+            .join("hello_blocking_in_runtime")
+            .as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
+
+#[test]
+fn spawn_error() {
+    let command = fspy::Spy::global()
+        .unwrap()
+        .new_command("/nonexistent/program");
+    assert!(command.spawn_blocking().is_err());
+}
+
+#[test]
+fn output_with_accesses() -> io::Result<()> {
+    let output = command_with_id(child_id!({
+        let _ = File::open("output_with_accesses_blocking");
+        print!("{}", "x".repeat(1024 * 1024));
+    }))?
+    .output_with_accesses_blocking()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 1024 * 1024);
+    assert_contains(
+        &output.accesses,
+        current_dir()
+            .unwrap()
+            .join("output_with_accesses_blocking")
+            .as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
@@ -0,0 +1,125 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{
+    env::current_dir,
+    fs::File,
+    io,
+    process::{Command as StdCommand, Stdio},
+    time::Duration,
+};
+
+use fspy::{AccessMode, Completion};
+use test_utils::{assert_contains, child_id, command_with_id};
+use tokio::time::timeout;
+
+/// Opens `name`, and leaves behind a process that keeps the ipc socket open.
+macro_rules! daemonizing_child {
+    ($name: literal) => {
+        child_id!({
+            let _ = File::open($name);
+            // Left running on purpose, to be reparented when this process exits.
+            #[allow(clippy::zombie_processes)]
+            let _sleep = StdCommand::new("sleep")
+                .arg("100")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+        })
+    };
+}
+
+fn is_alive(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[tokio::test]
+async fn abort() -> io::Result<()> {
+    let mut tracked_child = command_with_id(daemonizing_child!("abort_hello"))?
+        .spawn()
+        .await?;
+    assert!(tracked_child.tokio_child.wait().await?.success());
+
+    tracked_child.abort_handle().abort();
+    let accesses = timeout(Duration::from_secs(10), tracked_child.accesses_future)
+        .await
+        .expect("accesses should complete after abort")?;
+    assert_eq!(accesses.completion(), Completion::Aborted);
+    assert_eq!(accesses.survivors(), &[] as &[u32]);
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("abort_hello").as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn timeout_kills_descendants() -> io::Result<()> {
+    let mut command = command_with_id(daemonizing_child!("timeout_hello"))?;
+    command.timeout(Duration::from_millis(200));
+    let tracked_child = command.spawn().await?;
+
+    let accesses = timeout(Duration::from_secs(10), tracked_child.accesses_future)
+        .await
+        .expect("accesses should complete after the timeout")?;
+    assert_eq!(accesses.completion(), Completion::TimedOut);
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("timeout_hello").as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn complete_on_root_exit() -> io::Result<()> {
+    let mut command = command_with_id(daemonizing_child!("root_exit_hello"))?;
+    command.wait_for_descendants(false);
+    let mut tracked_child = command.spawn().await?;
+    let root_pid = tracked_child.tokio_child.id().unwrap();
+
+    let accesses = timeout(Duration::from_secs(10), tracked_child.accesses_future)
+        .await
+        .expect("accesses should complete when the root exits")?;
+    assert!(tracked_child.tokio_child.wait().await?.success());
+    assert_eq!(accesses.completion(), Completion::RootExited);
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("root_exit_hello").as_path(),
+        AccessMode::Read,
+    );
+
+    // The sleep is left running, and reported.
+    let survivors = accesses.survivors();
+    assert_eq!(survivors.len(), 1, "{:?}", survivors);
+    assert_ne!(survivors[0], root_pid);
+    assert!(is_alive(survivors[0]));
+    unsafe { libc::kill(survivors[0] as libc::pid_t, libc::SIGKILL) };
+    Ok(())
+}
+
+#[tokio::test]
+async fn complete_on_root_exit_without_survivors() -> io::Result<()> {
+    let mut command = command_with_id(child_id!({
+        let _ = File::open("root_exit_alone_hello");
+    }))?;
+    command.wait_for_descendants(false);
+    let mut tracked_child = command.spawn().await?;
+    assert!(tracked_child.tokio_child.wait().await?.success());
+
+    let accesses = tracked_child.accesses_future.await?;
+    assert_eq!(accesses.survivors(), &[] as &[u32]);
+    assert_contains(
+        &accesses,
+        current_dir()
+            .unwrap()
+            .join("root_exit_alone_hello")
+            .as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
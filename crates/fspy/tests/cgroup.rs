@@ -0,0 +1,137 @@
+#![cfg(target_os = "linux")]
+
+mod test_utils;
+
+use std::{
+    env::{current_dir, temp_dir},
+    fs::{self, File},
+    io,
+    path::PathBuf,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use fspy::{AccessMode, Completion};
+use nix::unistd::{AccessFlags, access};
+use test_utils::{assert_contains, child_id, command_with_id};
+use tokio::time::timeout;
+
+/// The cgroup to create the cgroups of the tests in, or `None` if it's not delegated to the current user.
+fn cgroup_parent() -> Option<PathBuf> {
+    let parent = fspy::current_cgroup().ok()?;
+    if access(&parent, AccessFlags::W_OK).is_err() {
+        eprintln!("skipped: {} is not writable", parent.display());
+        return None;
+    }
+    Some(parent)
+}
+
+/// Forks a process that closes all its fds, including the ipc socket, and exits after `duration`.
+fn spawn_orphan_without_fds(duration: Duration) -> u32 {
+    match unsafe { libc::fork() } {
+        0 => {
+            unsafe { libc::syscall(libc::SYS_close_range, 0, u32::MAX, 0) };
+            std::thread::sleep(duration);
+            unsafe { libc::_exit(0) }
+        }
+        pid => pid as u32,
+    }
+}
+
+/// Whether `pid` is alive and not a zombie.
+fn is_running(pid: u32) -> bool {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    // The state follows the parenthesized program name.
+    let state = stat.rsplit_once(") ").map(|(_, rest)| rest.chars().next());
+    state != Some(Some('Z'))
+}
+
+#[tokio::test]
+async fn waits_for_processes_that_closed_the_ipc_socket() -> io::Result<()> {
+    let Some(parent) = cgroup_parent() else {
+        return Ok(());
+    };
+    let mut command = command_with_id(child_id!({
+        let _ = File::open("cgroup_hello");
+        spawn_orphan_without_fds(Duration::from_millis(500));
+    }))?;
+    command.cgroup(parent);
+
+    let start = Instant::now();
+    let output = command.output_with_accesses().await?;
+    assert!(output.status.success());
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert_eq!(output.accesses.completion(), Completion::AllExited);
+    assert!(output.accesses.resource_usage().is_some());
+    assert_contains(
+        &output.accesses,
+        current_dir().unwrap().join("cgroup_hello").as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn abort_kills_the_whole_cgroup() -> io::Result<()> {
+    let Some(parent) = cgroup_parent() else {
+        return Ok(());
+    };
+    let mut command = command_with_id(child_id!({
+        println!("{}", spawn_orphan_without_fds(Duration::from_secs(100)));
+    }))?;
+    command.cgroup(parent).stdout(Stdio::piped());
+    let tracked_child = command.spawn().await?;
+    let abort_handle = tracked_child.abort_handle();
+
+    let output = tracked_child.tokio_child.wait_with_output().await?;
+    let orphan_pid = String::from_utf8(output.stdout)
+        .unwrap()
+        .trim()
+        .parse::<u32>()
+        .unwrap();
+    assert!(is_running(orphan_pid));
+
+    abort_handle.abort();
+    let accesses = timeout(Duration::from_secs(10), tracked_child.accesses_future)
+        .await
+        .expect("accesses should complete after abort")?;
+    assert_eq!(accesses.completion(), Completion::Aborted);
+    assert_eq!(accesses.survivors(), &[] as &[u32]);
+    assert!(!is_running(orphan_pid));
+    Ok(())
+}
+
+#[tokio::test]
+async fn resource_usage() -> io::Result<()> {
+    let Some(parent) = cgroup_parent() else {
+        return Ok(());
+    };
+    let mut command = command_with_id(child_id!({
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(200) {
+            std::hint::black_box(start);
+        }
+    }))?;
+    command.cgroup(parent);
+
+    let output = command.output_with_accesses().await?;
+    assert!(output.status.success());
+    let resource_usage = output.accesses.resource_usage().unwrap();
+    assert!(
+        resource_usage.user_time + resource_usage.system_time >= Duration::from_millis(100),
+        "{:?}",
+        resource_usage
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn parent_outside_cgroup_v2() -> io::Result<()> {
+    let mut command = command_with_id(child_id!({}))?;
+    command.cgroup(temp_dir());
+    let err = command.spawn().await.err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
@@ -0,0 +1,157 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{
+    env::set_current_dir,
+    fs::{self, File},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use fspy::{AccessMode, AccessOutcome, PathAccessIterable};
+use test_utils::{assert_contains, child_id, command_with_id};
+
+fn cwd_changes_of(accesses: &PathAccessIterable) -> Vec<(PathBuf, AccessOutcome)> {
+    accesses
+        .cwd_changes()
+        .map(|(_, cwd_change)| {
+            let path = PathBuf::from(cwd_change.path.to_cow_os_str().into_owned());
+            (path, cwd_change.outcome)
+        })
+        .collect()
+}
+
+/// Counts the accesses at `path` with `outcome`.
+fn count_accesses(accesses: &PathAccessIterable, path: &Path, outcome: AccessOutcome) -> usize {
+    accesses
+        .iter()
+        .filter(|access| {
+            Path::new(&access.path.to_cow_os_str()) == path && access.outcome == outcome
+        })
+        .count()
+}
+
+#[tokio::test]
+async fn chdir_and_fchdir() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("sub"))?;
+
+    let mut command = command_with_id(child_id!({
+        set_current_dir("sub").unwrap();
+        let _ = File::open("hello");
+        let parent = File::open("..").unwrap();
+        assert_eq!(unsafe { libc::fchdir(parent.as_raw_fd()) }, 0);
+        let _ = File::open("hello2");
+        let _ = set_current_dir("missing");
+    }))?;
+    command.current_dir(&dir);
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        cwd_changes_of(&output.accesses),
+        vec![
+            (dir.join("sub"), AccessOutcome::Succeeded),
+            (dir.clone(), AccessOutcome::Succeeded),
+            (dir.join("missing"), AccessOutcome::Failed(libc::ENOENT)),
+        ]
+    );
+    assert_contains(&output.accesses, &dir.join("sub"), AccessMode::Read);
+    assert_contains(&output.accesses, &dir.join("sub/hello"), AccessMode::Read);
+    assert_contains(&output.accesses, &dir.join("hello2"), AccessMode::Read);
+    // Without concurrent changes, relative paths are resolved against the only working directory it can be.
+    let failed = AccessOutcome::Failed(libc::ENOENT);
+    assert_eq!(
+        count_accesses(&output.accesses, &dir.join("hello"), failed),
+        0
+    );
+    assert_eq!(
+        count_accesses(&output.accesses, &dir.join("sub/hello2"), failed),
+        0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn relative_paths_during_concurrent_chdir() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("a"))?;
+    fs::create_dir(dir.join("b"))?;
+    File::create(dir.join("a/file"))?;
+
+    let mut command = command_with_id(child_id!({
+        const ROUNDS: usize = 1000;
+        let dir = std::env::current_dir().unwrap();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        set_current_dir(&a).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flipping = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    set_current_dir(&b).unwrap();
+                    set_current_dir(&a).unwrap();
+                }
+            }
+        });
+        let (mut succeeded, mut failed) = (0, 0);
+        for _ in 0..ROUNDS {
+            match File::open("file") {
+                Ok(_) => succeeded += 1,
+                Err(_) => failed += 1,
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        flipping.join().unwrap();
+        println!("{} {}", succeeded, failed);
+    }))?;
+    command.current_dir(&dir);
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (succeeded, failed) = stdout.trim().split_once(' ').unwrap();
+    let (succeeded, failed) = (
+        succeeded.parse::<usize>().unwrap(),
+        failed.parse::<usize>().unwrap(),
+    );
+
+    // Each open is recorded once, against the working directory that the call used:
+    // only a/file exists, so the opens that succeeded were in a, and the ones that failed in b.
+    let enoent = AccessOutcome::Failed(libc::ENOENT);
+    let (a_file, b_file) = (dir.join("a/file"), dir.join("b/file"));
+    assert_eq!(
+        count_accesses(&output.accesses, &a_file, AccessOutcome::Succeeded),
+        succeeded,
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        count_accesses(&output.accesses, &b_file, enoent),
+        failed,
+        "{}",
+        stdout
+    );
+    assert_eq!(count_accesses(&output.accesses, &a_file, enoent), 0);
+    assert_eq!(
+        count_accesses(&output.accesses, &b_file, AccessOutcome::Succeeded),
+        0
+    );
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{ffi::OsStr, io};
+
+use fspy::{PathAccessIterable, ReportEnvRead};
+use test_utils::{child_id, command_with_id};
+
+fn env_reads_of(accesses: &PathAccessIterable, name: &str) -> Vec<ReportEnvRead> {
+    accesses
+        .env_reads()
+        .map(|(pid, env_read)| ReportEnvRead::new(pid, env_read))
+        .filter(|env_read| env_read.name == name)
+        .collect()
+}
+
+#[tokio::test]
+async fn names() -> io::Result<()> {
+    let mut command = command_with_id(child_id!({
+        let _ = std::env::var_os("FSPY_TEST_SET");
+        let _ = std::env::var_os("FSPY_TEST_SET");
+        let _ = std::env::var_os("FSPY_TEST_UNSET");
+    }))?;
+    command
+        .env("FSPY_TEST_SET", "secret")
+        .env_remove("FSPY_TEST_UNSET");
+    let output = command.output_with_accesses().await?;
+    assert!(output.status.success());
+
+    // Only the first read is reported.
+    let set = env_reads_of(&output.accesses, "FSPY_TEST_SET");
+    assert_eq!(set.len(), 1, "{:?}", set);
+    assert!(set[0].is_set);
+    assert_eq!(set[0].value, None);
+
+    let unset = env_reads_of(&output.accesses, "FSPY_TEST_UNSET");
+    assert_eq!(unset.len(), 1, "{:?}", unset);
+    assert!(!unset[0].is_set);
+    Ok(())
+}
+
+#[tokio::test]
+async fn values() -> io::Result<()> {
+    let mut command = command_with_id(child_id!({
+        let _ = std::env::var_os("FSPY_TEST_VALUE");
+    }))?;
+    command
+        .env("FSPY_TEST_VALUE", "recorded")
+        .record_env_values(true);
+    let output = command.output_with_accesses().await?;
+    assert!(output.status.success());
+
+    let env_reads = env_reads_of(&output.accesses, "FSPY_TEST_VALUE");
+    assert_eq!(env_reads.len(), 1, "{:?}", env_reads);
+    assert_eq!(env_reads[0].value.as_deref(), Some(OsStr::new("recorded")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn forked_child() -> io::Result<()> {
+    let output = command_with_id(child_id!({
+        let _ = std::env::var_os("FSPY_TEST_FORK");
+        match unsafe { libc::fork() } {
+            0 => {
+                let _ = std::env::var_os("FSPY_TEST_FORK");
+                unsafe { libc::_exit(0) };
+            }
+            pid => {
+                unsafe { libc::waitpid(pid, std::ptr::null_mut(), 0) };
+            }
+        }
+    }))?
+    .output_with_accesses()
+    .await?;
+    assert!(output.status.success());
+
+    // The forked child starts over, so its read is reported too.
+    let pids = env_reads_of(&output.accesses, "FSPY_TEST_FORK")
+        .into_iter()
+        .map(|env_read| env_read.pid)
+        .collect::<Vec<_>>();
+    assert_eq!(pids.len(), 2, "{:?}", pids);
+    assert_ne!(pids[0], pids[1]);
+    Ok(())
+}
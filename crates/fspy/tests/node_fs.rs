@@ -18,6 +18,7 @@ async fn track_node_script(script: &str) -> io::Result<PathAccessIterable> {
     let TrackedChild {
         mut tokio_child,
         accesses_future,
+        ..
     } = command.spawn().await?;
 
     let acceses = accesses_future.await?;
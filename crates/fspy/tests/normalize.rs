@@ -0,0 +1,145 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::fs::symlink,
+    path::Path,
+};
+
+use fspy::{OwnedPathAccess, PathAccessIterable, PathNormalization};
+use test_utils::{child_id, command_with_id};
+
+/// Whether `path` is spelled exactly like `expected`.
+/// `Path`s are compared by components, which ignores `.` and duplicated slashes.
+fn is_spelled(path: Option<&Path>, expected: &Path) -> bool {
+    path.map(Path::as_os_str) == Some(expected.as_os_str())
+}
+
+/// Returns the accesses whose path, or original path if it's kept, is spelled like `path`.
+fn accesses_at(accesses: &PathAccessIterable, path: &Path) -> Vec<OwnedPathAccess> {
+    accesses
+        .iter_with_pid()
+        .map(|(pid, path_access)| OwnedPathAccess::new(pid, path_access))
+        .filter(|access| {
+            is_spelled(Some(&access.path), path)
+                || is_spelled(access.original_path.as_deref(), path)
+        })
+        .collect()
+}
+
+async fn accesses_with(
+    dir: &Path,
+    path_normalization: PathNormalization,
+    id: &str,
+) -> io::Result<PathAccessIterable> {
+    let mut command = command_with_id(id)?;
+    command
+        .current_dir(dir)
+        .path_normalization(path_normalization);
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.accesses)
+}
+
+#[tokio::test]
+async fn not_normalized_by_default() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("a"))?;
+
+    let accesses = accesses_with(
+        &dir,
+        PathNormalization::default(),
+        child_id!({
+            let _ = File::open("a/../b//file");
+        }),
+    )
+    .await?;
+    let spelled = dir.join("a/../b//file");
+    let accesses = accesses_at(&accesses, &spelled);
+    assert!(!accesses.is_empty());
+    assert!(
+        accesses.iter().all(|access| access.original_path.is_none()),
+        "{:?}",
+        accesses
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn lexical() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("a"))?;
+
+    let accesses = accesses_with(
+        &dir,
+        PathNormalization {
+            lexical: true,
+            keep_original: true,
+            ..Default::default()
+        },
+        child_id!({
+            let _ = File::open("a/../b//file");
+            let _ = File::open("./a");
+        }),
+    )
+    .await?;
+
+    let accesses_of_file = accesses_at(&accesses, &dir.join("b/file"));
+    assert!(!accesses_of_file.is_empty());
+    for access in &accesses_of_file {
+        assert!(is_spelled(Some(&access.path), &dir.join("b/file")));
+        assert!(
+            is_spelled(access.original_path.as_deref(), &dir.join("a/../b//file")),
+            "{:?}",
+            access
+        );
+    }
+    let accesses_of_a = accesses_at(&accesses, &dir.join("./a"));
+    assert!(!accesses_of_a.is_empty());
+    for access in &accesses_of_a {
+        assert!(is_spelled(Some(&access.path), &dir.join("a")));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn canonical() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("target"))?;
+    File::create(dir.join("target/file"))?;
+    symlink("target", dir.join("link"))?;
+
+    let accesses = accesses_with(
+        &dir,
+        PathNormalization {
+            canonicalize: true,
+            ..Default::default()
+        },
+        child_id!({
+            File::open("link/file").unwrap();
+            File::create("link/new").unwrap();
+            fs::symlink_metadata("link").unwrap();
+            // The symlinks are resolved when the paths are accessed, not when the accesses are collected.
+            fs::remove_file("link").unwrap();
+        }),
+    )
+    .await?;
+
+    assert!(!accesses_at(&accesses, &dir.join("target/file")).is_empty());
+    assert!(!accesses_at(&accesses, &dir.join("target/new")).is_empty());
+    // The symlink itself is accessed.
+    assert!(!accesses_at(&accesses, &dir.join("link")).is_empty());
+    assert!(accesses_at(&accesses, &dir.join("link/file")).is_empty());
+    assert!(accesses_at(&accesses, &dir.join("link/new")).is_empty());
+    Ok(())
+}
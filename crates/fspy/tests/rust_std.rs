@@ -3,12 +3,15 @@ mod test_utils;
 use fspy::{AccessMode, Command};
 use std::{
     env::current_dir,
+    ffi::OsStr,
     fs::{File, OpenOptions},
     io::{self, Stdin},
     path::Path,
     process::Stdio,
 };
-use test_utils::{assert_contains, track_child};
+use test_utils::{
+    assert_contains, assert_contains_with_dest, child_id, command_with_id, track_child,
+};
 
 #[tokio::test]
 async fn open_read() -> io::Result<()> {
@@ -91,3 +94,555 @@ async fn subprocess() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn process_tree() -> io::Result<()> {
+    use std::{collections::HashSet, ffi::OsString};
+
+    let accesses = track_child!({
+        let path = format!("{}/process_tree_exec", env!("CARGO_TARGET_TMPDIR"));
+        std::process::Command::new("cat")
+            .arg(path)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+
+        match unsafe { libc::fork() } {
+            0 => {
+                let path = format!("{}/process_tree_fork", env!("CARGO_TARGET_TMPDIR"));
+                let _ = File::open(path);
+                unsafe { libc::_exit(0) };
+            }
+            -1 => panic!("fork failed: {}", io::Error::last_os_error()),
+            pid => {
+                let mut status = 0;
+                unsafe { libc::waitpid(pid, &mut status, 0) };
+            }
+        }
+    })
+    .await?;
+    let process_tree = accesses.process_tree();
+    let root = process_tree.root().unwrap();
+    assert_eq!(root.ppid, std::process::id());
+
+    let pid_of_access = |name: &str| {
+        let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        accesses
+            .iter_with_pid()
+            .find(|(_, access)| Path::new(&access.path.to_cow_os_str()) == path)
+            .unwrap()
+            .0
+    };
+
+    let cat = process_tree.get(pid_of_access("process_tree_exec")).unwrap();
+    assert_eq!(cat.ppid, root.pid);
+    assert_eq!(
+        cat.args,
+        [
+            OsString::from("cat"),
+            Path::new(env!("CARGO_TARGET_TMPDIR"))
+                .join("process_tree_exec")
+                .into()
+        ]
+    );
+
+    let forked = process_tree.get(pid_of_access("process_tree_fork")).unwrap();
+    assert_ne!(forked.pid, root.pid);
+    assert_eq!(forked.ppid, root.pid);
+    assert_eq!(forked.program, root.program);
+
+    let children = process_tree.children(root.pid).map(|process| process.pid);
+    assert_eq!(
+        children.collect::<HashSet<u32>>(),
+        HashSet::from([cat.pid, forked.pid])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename() -> io::Result<()> {
+    let accesses = track_child!({
+        let from = format!("{}/rename_from", env!("CARGO_TARGET_TMPDIR"));
+        let to = format!("{}/rename_to", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::rename(from, to);
+    })
+    .await?;
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    assert_contains_with_dest(
+        &accesses,
+        tmp_dir.join("rename_from").as_path(),
+        tmp_dir.join("rename_to").as_path(),
+        AccessMode::Rename,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn remove_file() -> io::Result<()> {
+    let accesses = track_child!({
+        let path = format!("{}/hello", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::remove_file(path);
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("hello")
+            .as_path(),
+        AccessMode::Remove,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_dir() -> io::Result<()> {
+    let accesses = track_child!({
+        let path = format!("{}/hello", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::create_dir(path);
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("hello")
+            .as_path(),
+        AccessMode::CreateDir,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn remove_dir() -> io::Result<()> {
+    let accesses = track_child!({
+        let path = format!("{}/hello", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::remove_dir(path);
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("hello")
+            .as_path(),
+        AccessMode::Remove,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hard_link() -> io::Result<()> {
+    let accesses = track_child!({
+        let original = format!("{}/link_original", env!("CARGO_TARGET_TMPDIR"));
+        let link = format!("{}/link_hard", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::hard_link(original, link);
+    })
+    .await?;
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    assert_contains_with_dest(
+        &accesses,
+        tmp_dir.join("link_original").as_path(),
+        tmp_dir.join("link_hard").as_path(),
+        AccessMode::Link,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlink() -> io::Result<()> {
+    let accesses = track_child!({
+        let link = format!("{}/link_sym", env!("CARGO_TARGET_TMPDIR"));
+        std::os::unix::fs::symlink("link_original", link);
+    })
+    .await?;
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    assert_contains_with_dest(
+        &accesses,
+        tmp_dir.join("link_original").as_path(),
+        tmp_dir.join("link_sym").as_path(),
+        AccessMode::Link,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn failed_lookup() -> io::Result<()> {
+    use fspy::AccessOutcome;
+
+    let accesses = track_child!({
+        let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+        std::fs::write(tmp_dir.join("lookup_found"), "").unwrap();
+        File::open(tmp_dir.join("lookup_found")).unwrap();
+        // The error must still be seen by the tracked process after the access is recorded.
+        let error = File::open(tmp_dir.join("lookup_missing")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let error = File::open(tmp_dir.join("lookup_found/child")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotADirectory);
+    })
+    .await?;
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let outcome_of = |path: &Path, mode: AccessMode| {
+        accesses
+            .iter()
+            .find(|access| Path::new(&access.path.to_cow_os_str()) == path && access.mode == mode)
+            .unwrap()
+            .outcome
+    };
+    assert_eq!(
+        outcome_of(&tmp_dir.join("lookup_found"), AccessMode::Read),
+        AccessOutcome::Succeeded
+    );
+    let missing = outcome_of(&tmp_dir.join("lookup_missing"), AccessMode::Read);
+    assert_eq!(missing, AccessOutcome::Failed(libc::ENOENT));
+    assert!(missing.is_not_found());
+    assert!(outcome_of(&tmp_dir.join("lookup_found/child"), AccessMode::Read).is_not_found());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn no_follow() -> io::Result<()> {
+    let accesses = track_child!({
+        use std::os::unix::{ffi::OsStrExt, fs::OpenOptionsExt};
+
+        let link = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_follow_link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink("no_follow_target", &link).unwrap();
+        std::fs::read_link(&link).unwrap();
+        let link_cstr = std::ffi::CString::new(link.as_os_str().as_bytes()).unwrap();
+        let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+        unsafe { libc::lstat(link_cstr.as_ptr(), &mut stat) };
+        OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&link);
+        File::open(&link);
+    })
+    .await?;
+    let link = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_follow_link");
+    let follows = |mode: AccessMode| {
+        accesses
+            .iter()
+            .filter(|access| Path::new(&access.path.to_cow_os_str()) == link && access.mode == mode)
+            .map(|access| access.follow_symlinks)
+            .collect::<Vec<_>>()
+    };
+    // `read_link` and `lstat`, followed by `File::open`
+    assert_eq!(follows(AccessMode::Read), [false, false, true]);
+    // Opening with O_NOFOLLOW
+    assert_eq!(follows(AccessMode::Write), [false]);
+    // Removing acts on the link itself.
+    assert!(follows(AccessMode::Remove).iter().all(|follow| !follow));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn read_link() -> io::Result<()> {
+    let accesses = track_child!({
+        std::fs::read_link("hello");
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello").as_path(),
+        AccessMode::Read,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn access() -> io::Result<()> {
+    let accesses = track_child!({
+        unsafe { libc::access(c"hello_access".as_ptr(), libc::F_OK) };
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello_access").as_path(),
+        AccessMode::Read,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn faccessat() -> io::Result<()> {
+    let accesses = track_child!({
+        unsafe { libc::faccessat(libc::AT_FDCWD, c"hello_faccessat".as_ptr(), libc::R_OK, 0) };
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello_faccessat").as_path(),
+        AccessMode::Read,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn realpath() -> io::Result<()> {
+    let accesses = track_child!({
+        std::fs::canonicalize("hello_realpath");
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello_realpath").as_path(),
+        AccessMode::Read,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn metadata() -> io::Result<()> {
+    // On Linux, `std::fs::metadata` uses `statx`.
+    let accesses = track_child!({
+        std::fs::metadata("hello_metadata");
+    })
+    .await?;
+    assert_contains(
+        &accesses,
+        current_dir().unwrap().join("hello_metadata").as_path(),
+        AccessMode::Read,
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn path_filter() -> io::Result<()> {
+    use fspy::PathFilter;
+
+    let id = child_id!({
+        let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+        let _ = File::open(tmp_dir.join("filter_included"));
+        let _ = File::open(tmp_dir.join("filter_excluded/a"));
+        let _ = File::open("/dev/null");
+    });
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let mut command = command_with_id(id)?;
+    command.path_filter(
+        PathFilter::empty()
+            .include(tmp_dir.as_os_str().as_encoded_bytes())
+            .exclude(tmp_dir.join("*_excluded").as_os_str().as_encoded_bytes()),
+    );
+    let mut tracked_child = command.spawn().await?;
+    let accesses = tracked_child.accesses_future.await?;
+    assert!(tracked_child.tokio_child.wait().await?.success());
+
+    assert_contains(
+        &accesses,
+        &tmp_dir.join("filter_included"),
+        AccessMode::Read,
+    );
+    for access in accesses.iter() {
+        let path = access.path.to_cow_os_str();
+        assert!(
+            Path::new(&path).starts_with(tmp_dir),
+            "{:?} is not included",
+            path
+        );
+        assert!(!Path::new(&path).starts_with(tmp_dir.join("filter_excluded")));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn empty_path_filter() -> io::Result<()> {
+    use fspy::PathFilter;
+
+    let id = child_id!({
+        let _ = File::open("/dev/null");
+        let _ = File::open("empty_path_filter");
+    });
+    let mut command = command_with_id(id)?;
+    command.path_filter(PathFilter::empty());
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_contains(&output.accesses, Path::new("/dev/null"), AccessMode::Read);
+    assert_contains(
+        &output.accesses,
+        current_dir().unwrap().join("empty_path_filter").as_path(),
+        AccessMode::Read,
+    );
+    // The calls that the preload library makes itself are not recorded.
+    for access in output.accesses.iter() {
+        let path = access.path.to_cow_os_str();
+        assert!(
+            !Path::new(&path).starts_with("/dev/shm") && Path::new(&path) != Path::new("/proc/self/cwd"),
+            "{:?} is recorded",
+            path
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn report() -> io::Result<()> {
+    use fspy::{Report, ReportExitStatus};
+
+    let id = child_id!({
+        let _ = File::open("report_hello");
+    });
+    let mut tracked_child = command_with_id(id)?.spawn().await?;
+    let accesses = tracked_child.accesses_future.await?;
+    let status = tracked_child.tokio_child.wait().await?;
+
+    let mut report = Report::new(&accesses);
+    report.exit_status = Some(status.into());
+    assert_eq!(report.exit_status, Some(ReportExitStatus::Code(0)));
+    assert!(report.accesses.iter().any(|access| {
+        access.path == current_dir().unwrap().join("report_hello")
+            && access.mode == AccessMode::Read
+    }));
+    assert!(
+        report
+            .processes
+            .iter()
+            .any(|process| process.pid == report.root_pid)
+    );
+
+    let json = report.to_json()?;
+    assert_eq!(Report::decode(json.as_bytes())?, report);
+    let binary = report.to_binary()?;
+    assert_eq!(Report::decode(&binary)?, report);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn env() -> io::Result<()> {
+    let id = child_id!({
+        let name = match (std::env::var_os("PATH"), std::env::var_os("FSPY_TEST_ENV")) {
+            (Some(_), Some(_)) => "env_inherited_and_set",
+            (None, Some(_)) => "env_cleared_and_set",
+            (Some(_), None) => "env_inherited",
+            (None, None) => "env_cleared",
+        };
+        let _ = File::open(name);
+    });
+    async fn assert_env(
+        id: &str,
+        configure: impl FnOnce(&mut Command),
+        expected_file: &str,
+    ) -> io::Result<()> {
+        let mut command = command_with_id(id)?;
+        configure(&mut command);
+        let mut tracked_child = command.spawn().await?;
+        let accesses = tracked_child.accesses_future.await?;
+        assert!(tracked_child.tokio_child.wait().await?.success());
+        assert_contains(
+            &accesses,
+            current_dir().unwrap().join(expected_file).as_path(),
+            AccessMode::Read,
+        );
+        Ok(())
+    }
+    assert_env(id, |_| {}, "env_inherited").await?;
+    assert_env(
+        id,
+        |command| {
+            command.env("FSPY_TEST_ENV", "1");
+        },
+        "env_inherited_and_set",
+    )
+    .await?;
+    assert_env(
+        id,
+        |command| {
+            command.env_remove("PATH");
+        },
+        "env_cleared",
+    )
+    .await?;
+    assert_env(
+        id,
+        |command| {
+            command.env_clear().env("FSPY_TEST_ENV", "1");
+        },
+        "env_cleared_and_set",
+    )
+    .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn output() -> io::Result<()> {
+    let id = child_id!({
+        println!("hello from the child");
+    });
+    let mut command = command_with_id(id)?;
+    command.env("FSPY_TEST_ENV", "1");
+    assert_eq!(command.get_args().collect::<Vec<_>>(), [id]);
+    assert_eq!(
+        command.get_envs().collect::<Vec<_>>(),
+        [(OsStr::new("FSPY_TEST_ENV"), Some(OsStr::new("1")))]
+    );
+
+    let output = command.output().await?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello from the child\n");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn pre_exec() -> io::Result<()> {
+    let mut command = command_with_id(child_id!({}))?;
+    unsafe {
+        command.pre_exec(|| Err(io::Error::from_raw_os_error(libc::EPERM)));
+    }
+    let err = command.spawn().await.err().unwrap();
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    Ok(())
+}
+
+#[tokio::test]
+async fn output_with_accesses() -> io::Result<()> {
+    use std::io::Write as _;
+
+    let id = child_id!({
+        let _ = File::open("output_with_accesses");
+        // More than pipe buffers hold, so the child only exits if both pipes are drained.
+        let chunk = [b'x'; 1024];
+        for _ in 0..1024 {
+            io::stdout().write_all(&chunk).unwrap();
+            io::stderr().write_all(&chunk).unwrap();
+        }
+    });
+    let output = command_with_id(id)?.output_with_accesses().await?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 1024 * 1024);
+    assert_eq!(output.stderr.len(), 1024 * 1024);
+    assert_contains(
+        &output.accesses,
+        current_dir().unwrap().join("output_with_accesses").as_path(),
+        AccessMode::Read,
+    );
+    Ok(())
+}
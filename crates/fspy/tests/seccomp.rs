@@ -0,0 +1,240 @@
+#![cfg(target_os = "linux")]
+
+mod test_utils;
+
+use std::{
+    env::{current_exe, set_current_dir},
+    fs::{self, File},
+    io,
+    os::{fd::AsRawFd, unix::fs::symlink},
+    path::Path,
+};
+
+use fspy::{AccessMode, AccessOutcome, AccessPolicy, PathAccessIterable};
+use test_utils::{assert_contains, assert_contains_with_dest, child_id};
+
+/// The dynamic loader, which is a statically linked executable itself.
+#[cfg(target_arch = "x86_64")]
+const LD_SO: &str = "/lib64/ld-linux-x86-64.so.2";
+#[cfg(target_arch = "aarch64")]
+const LD_SO: &str = "/lib/ld-linux-aarch64.so.1";
+
+/// Runs the child with id `id` in `dir` through the dynamic loader. The loader is statically linked,
+/// so the child is traced by the seccomp supervisor instead of the preload library.
+async fn spawn_static_with_id(dir: &Path, id: &str) -> io::Result<PathAccessIterable> {
+    let mut command = fspy::Spy::global()?.new_command(LD_SO);
+    command.arg(current_exe()?).arg(id).current_dir(dir);
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.accesses)
+}
+
+#[tokio::test]
+async fn rename_and_remove() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::write(dir.join("from"), "")?;
+    fs::write(dir.join("removed"), "")?;
+    fs::create_dir(dir.join("removed_dir"))?;
+
+    let accesses = spawn_static_with_id(
+        &dir,
+        child_id!({
+            fs::rename("from", "to").unwrap();
+            fs::remove_file("removed").unwrap();
+            fs::remove_dir("removed_dir").unwrap();
+        }),
+    )
+    .await?;
+
+    assert_contains_with_dest(
+        &accesses,
+        &dir.join("from"),
+        &dir.join("to"),
+        AccessMode::Rename,
+    );
+    assert_contains(&accesses, &dir.join("removed"), AccessMode::Remove);
+    assert_contains(&accesses, &dir.join("removed_dir"), AccessMode::Remove);
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_dir_and_links() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::write(dir.join("original"), "")?;
+
+    let accesses = spawn_static_with_id(
+        &dir,
+        child_id!({
+            fs::create_dir("created_dir").unwrap();
+            fs::hard_link("original", "hard").unwrap();
+            symlink("original", "sym").unwrap();
+        }),
+    )
+    .await?;
+
+    assert_contains(&accesses, &dir.join("created_dir"), AccessMode::CreateDir);
+    assert_contains_with_dest(
+        &accesses,
+        &dir.join("original"),
+        &dir.join("hard"),
+        AccessMode::Link,
+    );
+    // A relative symlink target is relative to the directory containing the link.
+    assert_contains_with_dest(
+        &accesses,
+        &dir.join("original"),
+        &dir.join("sym"),
+        AccessMode::Link,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn at_variants() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("sub"))?;
+    fs::write(dir.join("sub/from"), "")?;
+    fs::write(dir.join("sub/original"), "")?;
+
+    let accesses = spawn_static_with_id(
+        &dir,
+        child_id!({
+            // Relative to `sub`, not to the working directory.
+            let sub = File::open("sub").unwrap();
+            let sub = sub.as_raw_fd();
+            unsafe {
+                assert_eq!(
+                    libc::renameat(sub, c"from".as_ptr(), sub, c"to".as_ptr()),
+                    0
+                );
+                assert_eq!(libc::mkdirat(sub, c"created_dir".as_ptr(), 0o755), 0);
+                assert_eq!(
+                    libc::linkat(sub, c"original".as_ptr(), sub, c"hard".as_ptr(), 0),
+                    0
+                );
+                assert_eq!(
+                    libc::symlinkat(c"original".as_ptr(), sub, c"sym".as_ptr()),
+                    0
+                );
+                assert_eq!(libc::unlinkat(sub, c"hard".as_ptr(), 0), 0);
+                assert_eq!(
+                    libc::unlinkat(sub, c"created_dir".as_ptr(), libc::AT_REMOVEDIR),
+                    0
+                );
+            }
+        }),
+    )
+    .await?;
+
+    let sub = dir.join("sub");
+    assert_contains_with_dest(
+        &accesses,
+        &sub.join("from"),
+        &sub.join("to"),
+        AccessMode::Rename,
+    );
+    assert_contains(&accesses, &sub.join("created_dir"), AccessMode::CreateDir);
+    assert_contains_with_dest(
+        &accesses,
+        &sub.join("original"),
+        &sub.join("hard"),
+        AccessMode::Link,
+    );
+    assert_contains_with_dest(
+        &accesses,
+        &sub.join("original"),
+        &sub.join("sym"),
+        AccessMode::Link,
+    );
+    assert_contains(&accesses, &sub.join("hard"), AccessMode::Remove);
+    assert_contains(&accesses, &sub.join("created_dir"), AccessMode::Remove);
+    Ok(())
+}
+
+#[tokio::test]
+async fn chdir_and_fchdir() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+    fs::create_dir(dir.join("sub"))?;
+
+    let accesses = spawn_static_with_id(
+        &dir,
+        child_id!({
+            set_current_dir("sub").unwrap();
+            let _ = File::open("hello");
+            let parent = File::open("..").unwrap();
+            assert_eq!(unsafe { libc::fchdir(parent.as_raw_fd()) }, 0);
+            let _ = File::open("hello2");
+            assert!(set_current_dir("missing").is_err());
+            let _ = File::open("hello3");
+        }),
+    )
+    .await?;
+
+    // The changes are recorded once the syscalls complete, with their outcomes.
+    let cwd_changes: Vec<_> = accesses
+        .cwd_changes()
+        .map(|(_, cwd_change)| {
+            let path = cwd_change.path.to_cow_os_str().into_owned();
+            (path, cwd_change.outcome)
+        })
+        .collect();
+    assert_eq!(
+        cwd_changes,
+        [
+            (dir.join("sub").into_os_string(), AccessOutcome::Succeeded),
+            (dir.clone().into_os_string(), AccessOutcome::Succeeded),
+            (
+                dir.join("missing").into_os_string(),
+                AccessOutcome::Failed(libc::ENOENT)
+            ),
+        ]
+    );
+    assert_contains(&accesses, &dir.join("sub"), AccessMode::Read);
+    assert_contains(&accesses, &dir.join("sub/hello"), AccessMode::Read);
+    assert_contains(&accesses, &dir.join("hello2"), AccessMode::Read);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unchecked_access_fails_closed() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path().canonicalize()?;
+
+    let mut command = fspy::Spy::global()?.new_command(LD_SO);
+    command
+        .arg(current_exe()?)
+        .arg(child_id!({
+            // Too long for the supervisor to read, so the access can't be checked.
+            let long_path = std::ffi::CString::new("a".repeat(40000)).unwrap();
+            let fd = unsafe { libc::open(long_path.as_ptr(), libc::O_RDONLY) };
+            assert_eq!(fd, -1);
+            assert_eq!(
+                io::Error::last_os_error().raw_os_error(),
+                Some(libc::EACCES)
+            );
+        }))
+        .current_dir(&dir)
+        .access_policy(AccessPolicy::default().allow_write("/"));
+    let output = command.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let violations: Vec<_> = output
+        .accesses
+        .violations()
+        .map(|(_, access)| (access.path.to_cow_os_str().is_empty(), access.outcome))
+        .collect();
+    assert_eq!(violations, [(true, AccessOutcome::Failed(libc::EACCES))]);
+    Ok(())
+}
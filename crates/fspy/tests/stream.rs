@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+mod test_utils;
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read as _},
+    path::Path,
+    process::Stdio,
+    time::Duration,
+};
+
+use fspy::{AccessMode, OwnedPathAccess};
+use futures_util::StreamExt as _;
+use test_utils::{child_id, command_with_id};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn stream_while_running() -> io::Result<()> {
+    let id = child_id!({
+        let path = format!("{}/stream_while_running", env!("CARGO_TARGET_TMPDIR"));
+        let _ = File::open(path);
+        // Keep running until the parent closes stdin
+        let _ = io::stdin().read(&mut [0u8]);
+    });
+    let mut command = command_with_id(id)?;
+    command.stdin(Stdio::piped());
+    let mut tracked_child = command.spawn().await?;
+    let mut stream = tracked_child.access_stream();
+
+    let expected_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("stream_while_running");
+    timeout(Duration::from_secs(10), async {
+        while let Some(access) = stream.next().await {
+            if access.path == expected_path && access.mode == AccessMode::Read {
+                return;
+            }
+        }
+        panic!("stream ended without the expected access");
+    })
+    .await
+    .expect("timed out waiting for the access");
+    assert!(tracked_child.tokio_child.try_wait()?.is_none());
+
+    drop(tracked_child.tokio_child.stdin.take());
+    let status = tracked_child.tokio_child.wait().await?;
+    assert!(status.success());
+    // The stream ends after all the tracked processes exit
+    timeout(Duration::from_secs(10), stream.count())
+        .await
+        .expect("timed out waiting for the stream to end");
+    Ok(())
+}
+
+#[tokio::test]
+async fn stream_matches_batch() -> io::Result<()> {
+    let id = child_id!({
+        for i in 0..100 {
+            let path = format!("{}/stream_matches_batch_{}", env!("CARGO_TARGET_TMPDIR"), i);
+            let _ = File::open(path);
+        }
+    });
+    let mut tracked_child = command_with_id(id)?.spawn().await?;
+    let streamed = tracked_child
+        .access_stream()
+        .collect::<HashSet<OwnedPathAccess>>()
+        .await;
+    let accesses = tracked_child.accesses_future.await?;
+    assert!(tracked_child.tokio_child.wait().await?.success());
+
+    let batch = accesses
+        .iter_with_pid()
+        .map(|(pid, path_access)| OwnedPathAccess::new(pid, path_access))
+        .collect::<HashSet<OwnedPathAccess>>();
+    assert_eq!(streamed, batch);
+    assert!(batch.iter().any(|access| {
+        access.path == Path::new(env!("CARGO_TARGET_TMPDIR")).join("stream_matches_batch_99")
+    }));
+    Ok(())
+}
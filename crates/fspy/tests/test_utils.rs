@@ -1,6 +1,9 @@
+// Shared by several test crates, each of which uses only some of the helpers.
+#![allow(dead_code, unused_macros, unused_imports)]
+
 use std::{ffi::OsStr, io, path::Path};
 
-use fspy::{AccessMode, PathAccessIterable, TrackedChild};
+use fspy::{AccessMode, Command, PathAccessIterable};
 
 #[track_caller]
 pub fn assert_contains(
@@ -28,7 +31,28 @@ pub fn assert_contains(
         .unwrap();
 }
 
-macro_rules! track_child {
+#[track_caller]
+pub fn assert_contains_with_dest(
+    accesses: &PathAccessIterable,
+    expected_path: &Path,
+    expected_dest: &Path,
+    expected_mode: AccessMode,
+) {
+    accesses
+        .iter()
+        .find(|access| {
+            let Some(dest) = access.dest else {
+                return false;
+            };
+            Path::new(&access.path.to_cow_os_str()) == expected_path
+                && Path::new(&dest.to_cow_os_str()) == expected_dest
+                && access.mode == expected_mode
+        })
+        .unwrap();
+}
+
+/// Registers `$body` to run in a child process, and evaluates to the id to pass to the child.
+macro_rules! child_id {
     ($body: block) => {{
         const ID: &str = ::core::concat!(
             ::core::file!(),
@@ -51,24 +75,31 @@ macro_rules! track_child {
                 ::std::process::exit(0);
             }
         }
-        $crate::test_utils::spawn_with_id(ID)
+        ID
     }};
 }
 
-pub async fn spawn_with_id(id: &str) -> io::Result<PathAccessIterable> {
+macro_rules! track_child {
+    ($body: block) => {
+        $crate::test_utils::spawn_with_id($crate::test_utils::child_id!($body))
+    };
+}
+
+pub fn command_with_id(id: &str) -> io::Result<Command> {
     let mut command = fspy::Spy::global()?.new_command(::std::env::current_exe()?);
     command.arg(id);
-    let TrackedChild {
-        mut tokio_child,
-        accesses_future,
-    } = command.spawn().await?;
+    Ok(command)
+}
 
-    let acceses = accesses_future.await?;
-    let status = tokio_child.wait().await?;
-    assert!(status.success());
-    Ok(acceses)
+pub async fn spawn_with_id(id: &str) -> io::Result<PathAccessIterable> {
+    let output = command_with_id(id)?.output_with_accesses().await?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.accesses)
 }
 
+pub(crate) use child_id;
 pub(crate) use track_child;
-
-
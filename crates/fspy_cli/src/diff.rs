@@ -0,0 +1,62 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Args;
+use fspy::{AccessSummary, Report};
+
+use crate::read_report;
+
+/// Compares the accessed paths of two traces.
+///
+/// Prints `-` for paths only accessed in OLD, `+` for paths only accessed in NEW,
+/// and `~` for paths accessed differently. Exits with 1 if the traces differ, like `diff`.
+#[derive(Args)]
+pub struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+
+    /// Only compares paths under the directory, and prints them relative to it.
+    #[arg(long, value_name = "DIR")]
+    relative_to: Option<PathBuf>,
+}
+
+fn summarize(report: &Report, root: Option<&Path>) -> AccessSummary {
+    let mut summary = match root {
+        Some(root) => AccessSummary::relative_to(root),
+        None => AccessSummary::new(),
+    };
+    summary.extend(&report.accesses);
+    summary
+}
+
+pub fn run(args: DiffArgs) -> io::Result<ExitCode> {
+    let old = summarize(&read_report(&args.old)?, args.relative_to.as_deref());
+    let new = summarize(&read_report(&args.new)?, args.relative_to.as_deref());
+
+    let paths = old
+        .iter()
+        .chain(new.iter())
+        .map(|(path, _)| path)
+        .collect::<BTreeSet<_>>();
+    let mut differs = false;
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for path in paths {
+        let path_display = path.display();
+        match (old.get(path), new.get(path)) {
+            (Some(old), Some(new)) if old == new => continue,
+            (Some(old), Some(new)) => {
+                writeln!(stdout, "~ {}: {:?} -> {:?}", path_display, old, new)?
+            }
+            (Some(old), None) => writeln!(stdout, "- {}: {:?}", path_display, old)?,
+            (None, Some(new)) => writeln!(stdout, "+ {}: {:?}", path_display, new)?,
+            (None, None) => unreachable!("the path is from one of the summaries"),
+        }
+        differs = true;
+    }
+    stdout.flush()?;
+    Ok(ExitCode::from(u8::from(differs)))
+}
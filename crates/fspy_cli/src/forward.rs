@@ -0,0 +1,95 @@
+//! Makes `fspy trace` behave like the traced command to its parent: signals sent to fspy are passed on
+//! to the command, and fspy exits with the status of the command.
+
+use std::process::{ExitCode, ExitStatus};
+
+#[cfg(unix)]
+use tokio::{sync::watch, task::JoinSet};
+
+/// Keeps the signals meant for the traced command from terminating fspy, and sends the ones that
+/// only fspy receives to the command, once it is known with [`SignalForwarding::forward_to`].
+/// Signals are handled until the returned value is dropped.
+///
+/// `SIGINT` and `SIGQUIT` from the terminal are sent to the whole foreground process group,
+/// so the command already receives them. They are only kept from terminating fspy before the command exits.
+#[cfg(unix)]
+pub fn forward_signals() -> std::io::Result<SignalForwarding> {
+    use nix::{
+        sys::signal::{Signal, kill},
+        unistd::Pid,
+    };
+    use tokio::signal::unix::{SignalKind, signal};
+
+    const FORWARDED: [Signal; 4] = [
+        Signal::SIGTERM,
+        Signal::SIGHUP,
+        Signal::SIGUSR1,
+        Signal::SIGUSR2,
+    ];
+    const IGNORED: [Signal; 2] = [Signal::SIGINT, Signal::SIGQUIT];
+
+    let (pid_sender, pid_receiver) = watch::channel(None::<u32>);
+    let mut tasks = JoinSet::new();
+    for sig in FORWARDED {
+        let mut stream = signal(SignalKind::from_raw(sig as i32))?;
+        let mut pid_receiver = pid_receiver.clone();
+        tasks.spawn(async move {
+            while stream.recv().await.is_some() {
+                // Signals received while the command is being spawned are sent once it is.
+                let Ok(pid) = pid_receiver.wait_for(Option::is_some).await else {
+                    return;
+                };
+                if let Some(pid) = *pid {
+                    let _ = kill(Pid::from_raw(pid as i32), sig);
+                }
+            }
+        });
+    }
+    for sig in IGNORED {
+        let mut stream = signal(SignalKind::from_raw(sig as i32))?;
+        tasks.spawn(async move { while stream.recv().await.is_some() {} });
+    }
+    Ok(SignalForwarding {
+        _tasks: tasks,
+        pid_sender,
+    })
+}
+
+/// Returned by [`forward_signals`].
+#[cfg(unix)]
+pub struct SignalForwarding {
+    _tasks: JoinSet<()>,
+    pid_sender: watch::Sender<Option<u32>>,
+}
+
+#[cfg(unix)]
+impl SignalForwarding {
+    /// Sends the forwarded signals to the command with `pid`.
+    pub fn forward_to(&self, pid: u32) {
+        self.pid_sender.send_replace(Some(pid));
+    }
+}
+
+/// Converts the exit status of the traced command to the exit code of fspy.
+///
+/// If the command was killed by a signal, fspy kills itself with the same signal, so that the
+/// parent sees the same status. It's only returned from if the signal doesn't terminate fspy,
+/// and then the exit code is 128 plus the signal number, like shells report it.
+pub fn exit_code_of(status: ExitStatus) -> ExitCode {
+    #[cfg(unix)]
+    if let Some(sig) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        use nix::sys::signal::{SigHandler, Signal, raise, signal};
+        if let Ok(sig) = Signal::try_from(sig) {
+            // Restore the default action, which may have been replaced by `forward_signals`.
+            let _ = unsafe { signal(sig, SigHandler::SigDfl) };
+            let _ = raise(sig);
+        }
+        return ExitCode::from(128u8.wrapping_add(sig as u8));
+    }
+    let code = status.code().unwrap_or(1);
+    match u8::try_from(code) {
+        Ok(code) => ExitCode::from(code),
+        // Exit codes on Windows don't fit in `ExitCode::from`.
+        Err(_) => std::process::exit(code),
+    }
+}
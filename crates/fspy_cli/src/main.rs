@@ -0,0 +1,53 @@
+mod diff;
+mod forward;
+mod summarize;
+mod trace;
+
+use std::{fs, io, path::Path, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use fspy::Report;
+
+/// Traces the file system accesses of commands.
+#[derive(Parser)]
+#[command(name = "fspy", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    Trace(trace::TraceArgs),
+    Diff(diff::DiffArgs),
+    Summarize(summarize::SummarizeArgs),
+}
+
+/// Loads a trace saved by `fspy trace` in the json or binary format.
+fn read_report(path: &Path) -> io::Result<Report> {
+    let data = fs::read(path)?;
+    Report::decode(&data).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("failed to read the trace {}: {}", path.display(), err),
+        )
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        CliCommand::Trace(args) => trace::run(args).await,
+        CliCommand::Diff(args) => diff::run(args),
+        CliCommand::Summarize(args) => summarize::run(args),
+    };
+    match result {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("fspy: {}", err);
+            // Same as `diff` and `cmp`, to be told apart from the results of the subcommands.
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Args;
+use fspy::{AccessMode, ReportExitStatus};
+
+use crate::read_report;
+
+/// Prints an overview of a trace: counts by mode, the directories with the most accesses,
+/// the environment variables read, and the accesses of each process.
+#[derive(Args)]
+pub struct SummarizeArgs {
+    trace: PathBuf,
+
+    /// How many directories to list.
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top: usize,
+}
+
+pub fn run(args: SummarizeArgs) -> io::Result<ExitCode> {
+    let report = read_report(&args.trace)?;
+    let mut stdout = BufWriter::new(io::stdout().lock());
+
+    let unique_paths = report
+        .accesses
+        .iter()
+        .map(|access| access.path.as_path())
+        .collect::<HashSet<_>>();
+    writeln!(
+        stdout,
+        "{} accesses to {} paths by {} processes",
+        report.accesses.len(),
+        unique_paths.len(),
+        report.processes.len()
+    )?;
+    match report.exit_status {
+        Some(ReportExitStatus::Code(code)) => writeln!(stdout, "Exited with code {}", code)?,
+        Some(ReportExitStatus::Signal(signal)) => writeln!(stdout, "Killed by signal {}", signal)?,
+        None => {}
+    }
+    if let Some(timing) = report.timing {
+        writeln!(stdout, "Took {:.3}s", timing.duration.as_secs_f64())?;
+    }
+
+    let mut mode_counts = BTreeMap::<AccessMode, usize>::new();
+    for access in &report.accesses {
+        *mode_counts.entry(access.mode).or_default() += 1;
+    }
+    writeln!(stdout, "\nBy mode:")?;
+    for (mode, count) in mode_counts {
+        writeln!(stdout, "{:>10}  {:?}", count, mode)?;
+    }
+
+    let mut dir_counts = HashMap::<&Path, usize>::new();
+    for access in &report.accesses {
+        let dir = access.path.parent().unwrap_or(&access.path);
+        *dir_counts.entry(dir).or_default() += 1;
+    }
+    let mut dir_counts = dir_counts.into_iter().collect::<Vec<_>>();
+    // The most accessed first, and in path order among equal counts.
+    dir_counts.sort_by(|(dir_a, count_a), (dir_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| dir_a.cmp(dir_b))
+    });
+    writeln!(stdout, "\nTop directories:")?;
+    for (dir, count) in dir_counts.into_iter().take(args.top) {
+        writeln!(stdout, "{:>10}  {}", count, dir.display())?;
+    }
+
+    let mut process_counts = HashMap::<u32, (usize, HashSet<&Path>)>::new();
+    for access in &report.accesses {
+        let (count, paths) = process_counts.entry(access.pid).or_default();
+        *count += 1;
+        paths.insert(&access.path);
+    }
+    let mut env_reads = BTreeMap::<&OsStr, (usize, bool)>::new();
+    for env_read in &report.env_reads {
+        let (process_count, is_set) = env_reads.entry(&env_read.name).or_default();
+        *process_count += 1;
+        *is_set |= env_read.is_set;
+    }
+    writeln!(stdout, "\nEnvironment variables:")?;
+    for (name, (process_count, is_set)) in env_reads {
+        let unset = if is_set { "" } else { " (unset)" };
+        writeln!(stdout, "{:>10}  {}{}", process_count, name.display(), unset)?;
+    }
+
+    writeln!(stdout, "\nBy process:")?;
+    writeln!(
+        stdout,
+        "{:>10}  {:>10}  {:>10}  {:>10}  PROGRAM",
+        "PID", "PPID", "ACCESSES", "PATHS"
+    )?;
+    for process in &report.processes {
+        let (count, paths) = process_counts
+            .get(&process.pid)
+            .map(|(count, paths)| (*count, paths.len()))
+            .unwrap_or_default();
+        writeln!(
+            stdout,
+            "{:>10}  {:>10}  {:>10}  {:>10}  {}",
+            process.pid,
+            process.ppid,
+            count,
+            paths,
+            process.program.display()
+        )?;
+    }
+
+    stdout.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
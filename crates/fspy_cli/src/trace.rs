@@ -0,0 +1,290 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Instant, SystemTime},
+};
+
+use clap::{Args, ValueEnum};
+use fspy::{Report, ReportAccess, ReportTiming};
+
+use crate::forward;
+
+/// Runs a command and records the files it and its descendants access.
+///
+/// fspy exits with the exit status of the command.
+#[derive(Args)]
+pub struct TraceArgs {
+    /// Writes the trace to the file instead of stdout. Without it, the output of the command goes to stderr.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Only json and binary traces can be read by `diff` and `summarize`.
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Only records paths under the directory, and writes them relative to it.
+    #[arg(long, value_name = "DIR")]
+    relative_to: Option<PathBuf>,
+
+    /// Only records paths matching the pattern. `*` matches within a path component and `**` across components.
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Doesn't record paths matching the pattern.
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Also records paths under /dev, /proc and /sys, which are excluded by default.
+    #[arg(long)]
+    no_default_excludes: bool,
+
+    /// Records the values of the environment variables read, not only their names.
+    #[arg(long)]
+    env_values: bool,
+
+    /// Normalizes the recorded paths, which are otherwise only made absolute.
+    #[arg(long, value_enum, value_name = "HOW")]
+    normalize: Option<Normalize>,
+
+    /// Also records the paths as the command spelled them, when normalization changed them.
+    #[arg(long, requires = "normalize")]
+    keep_original: bool,
+
+    /// Allows reading files under the directory. With any --allow-read or --allow-write, accesses
+    /// outside of the allowed directories fail, and are recorded as violations.
+    #[arg(long, value_name = "DIR")]
+    allow_read: Vec<PathBuf>,
+
+    /// Allows reading, writing, creating and removing files under the directory.
+    #[arg(long, value_name = "DIR")]
+    allow_write: Vec<PathBuf>,
+
+    /// The command to run, and its arguments.
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<OsString>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// The versioned report with the accesses, the processes, the exit status and the timing.
+    Json,
+    /// The same report as json, in a compact binary encoding.
+    Binary,
+    /// One access per line as a JSON object.
+    Ndjson,
+    /// One access per row.
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Normalize {
+    /// Removes `.`, `..` and duplicated slashes, without looking at the filesystem.
+    Lexical,
+    /// Also resolves symlinks, when the paths are accessed.
+    Canonical,
+}
+
+pub async fn run(args: TraceArgs) -> io::Result<ExitCode> {
+    let relative_to = args
+        .relative_to
+        .as_deref()
+        .map(std::path::absolute)
+        .transpose()?;
+
+    let spy = fspy::Spy::global()?;
+    let mut command = spy.new_command(&args.command[0]);
+    command.args(&args.command[1..]);
+
+    #[cfg(unix)]
+    {
+        let mut path_filter = if args.no_default_excludes {
+            fspy::PathFilter::empty()
+        } else {
+            fspy::PathFilter::default()
+        };
+        if let Some(root) = &relative_to {
+            path_filter = path_filter.include_literal(root.as_os_str().as_encoded_bytes());
+        }
+        for pattern in &args.include {
+            path_filter = path_filter.include(pattern);
+        }
+        for pattern in &args.exclude {
+            path_filter = path_filter.exclude(pattern);
+        }
+        command.path_filter(path_filter);
+        command.record_env_values(args.env_values);
+        command.path_normalization(fspy::PathNormalization {
+            lexical: matches!(args.normalize, Some(Normalize::Lexical)),
+            canonicalize: matches!(args.normalize, Some(Normalize::Canonical)),
+            keep_original: args.keep_original,
+        });
+        if !args.allow_read.is_empty() || !args.allow_write.is_empty() {
+            let mut access_policy = fspy::AccessPolicy::default();
+            for dir in &args.allow_read {
+                access_policy = access_policy
+                    .allow_read(std::path::absolute(dir)?.as_os_str().as_encoded_bytes());
+            }
+            for dir in &args.allow_write {
+                access_policy = access_policy
+                    .allow_write(std::path::absolute(dir)?.as_os_str().as_encoded_bytes());
+            }
+            command.access_policy(access_policy);
+        }
+    }
+    #[cfg(not(unix))]
+    if !args.include.is_empty() || !args.exclude.is_empty() || args.no_default_excludes {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "path filters are not supported on this platform",
+        ));
+    }
+    #[cfg(not(unix))]
+    if args.env_values {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "environment variables are not recorded on this platform",
+        ));
+    }
+    #[cfg(not(unix))]
+    if args.normalize.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "paths are not normalized on this platform",
+        ));
+    }
+    #[cfg(not(unix))]
+    if !args.allow_read.is_empty() || !args.allow_write.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "access policies are not supported on this platform",
+        ));
+    }
+
+    // Keeps the output of the command out of the report.
+    if args.output.is_none() {
+        command.stdout(io::stderr());
+    }
+
+    // Registered before spawning, so that no signal sent right after the command starts terminates fspy.
+    #[cfg(unix)]
+    let signal_forwarding = forward::forward_signals()?;
+
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    let mut tracked_child = command.spawn().await?;
+
+    #[cfg(unix)]
+    if let Some(pid) = tracked_child.tokio_child.id() {
+        signal_forwarding.forward_to(pid);
+    }
+    let status = tracked_child.tokio_child.wait().await?;
+    let accesses = tracked_child.accesses_future.await?;
+    let duration = start.elapsed();
+    #[cfg(unix)]
+    drop(signal_forwarding);
+
+    let mut report = Report::new(&accesses);
+    report.exit_status = Some(status.into());
+    report.timing = Some(ReportTiming {
+        started_at,
+        duration,
+    });
+    if let Some(root) = &relative_to {
+        make_relative(&mut report.accesses, root);
+        // Violations are kept wherever they are, as they are recorded regardless of the filter.
+        for violation in &mut report.violations {
+            make_access_relative(violation, root);
+        }
+    }
+
+    match &args.output {
+        Some(path) => write_report(&report, args.format, File::create(path)?)?,
+        None => write_report(&report, args.format, io::stdout().lock())?,
+    }
+    Ok(forward::exit_code_of(status))
+}
+
+/// Removes accesses outside of `root`, and makes the paths under it relative.
+/// Renames and links are kept if either of their paths is under `root`.
+fn make_relative(accesses: &mut Vec<ReportAccess>, root: &Path) {
+    accesses.retain_mut(|access| make_access_relative(access, root));
+}
+
+/// Makes the paths of `access` under `root` relative, and returns whether any is.
+fn make_access_relative(access: &mut ReportAccess, root: &Path) -> bool {
+    let mut is_under_root = false;
+    for path in [Some(&mut access.path), access.dest.as_mut()]
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(relative_path) = path.strip_prefix(root) {
+            *path = relative_path.to_owned();
+            is_under_root = true;
+        }
+    }
+    for original in [access.original_path.as_mut(), access.original_dest.as_mut()]
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(relative_original) = original.strip_prefix(root) {
+            *original = relative_original.to_owned();
+        }
+    }
+    is_under_root
+}
+
+fn write_report(report: &Report, format: Format, writer: impl Write) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    match format {
+        Format::Json => writeln!(writer, "{}", report.to_json()?)?,
+        Format::Binary => writer.write_all(&report.to_binary()?)?,
+        Format::Ndjson => {
+            for access in &report.accesses {
+                serde_json::to_writer(&mut writer, access)?;
+                writeln!(writer)?;
+            }
+        }
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut writer);
+            csv_writer.write_record([
+                "pid",
+                "mode",
+                "path",
+                "dest",
+                "outcome",
+                "follow_symlinks",
+                "original_path",
+                "original_dest",
+            ])?;
+            for access in &report.accesses {
+                csv_writer.write_record([
+                    access.pid.to_string(),
+                    format!("{:?}", access.mode),
+                    access.path.to_string_lossy().into_owned(),
+                    access
+                        .dest
+                        .as_ref()
+                        .map(|dest| dest.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    format!("{:?}", access.outcome),
+                    access.follow_symlinks.to_string(),
+                    access
+                        .original_path
+                        .as_ref()
+                        .map(|original_path| original_path.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    access
+                        .original_dest
+                        .as_ref()
+                        .map(|original_dest| original_dest.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                ])?;
+            }
+            csv_writer.flush()?;
+        }
+    }
+    writer.flush()
+}
@@ -0,0 +1,131 @@
+#![cfg(unix)]
+
+use std::{fs, path::Path, process::Command};
+
+fn fspy() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_fspy"))
+}
+
+fn trace(dir: &Path, output: &Path, script: &str) -> std::process::ExitStatus {
+    fspy()
+        .arg("trace")
+        .arg("--relative-to")
+        .arg(dir)
+        .arg("-o")
+        .arg(output)
+        .args(["/bin/sh", "-c", script])
+        .current_dir(dir)
+        .status()
+        .unwrap()
+}
+
+#[test]
+fn trace_diff_summarize() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path().canonicalize().unwrap();
+    fs::write(dir.join("a"), "").unwrap();
+    fs::write(dir.join("b"), "").unwrap();
+
+    let old_trace = dir.join("old.json");
+    let status = trace(&dir, &old_trace, "cat a; exit 3");
+    assert_eq!(status.code(), Some(3));
+    let report = fs::read_to_string(&old_trace).unwrap();
+    let version = format!(r#""version":{}"#, fspy::REPORT_VERSION);
+    assert!(report.contains(&version), "{}", report);
+    assert!(report.contains(r#""path":"a""#), "{}", report);
+
+    let new_trace = dir.join("new.json");
+    let status = trace(&dir, &new_trace, "cat a b");
+    assert!(status.success());
+
+    let same = fspy()
+        .arg("diff")
+        .args([&old_trace, &old_trace])
+        .output()
+        .unwrap();
+    assert_eq!(same.status.code(), Some(0));
+    assert!(same.stdout.is_empty());
+
+    let changed = fspy()
+        .arg("diff")
+        .args([&old_trace, &new_trace])
+        .output()
+        .unwrap();
+    assert_eq!(changed.status.code(), Some(1));
+    assert_eq!(String::from_utf8(changed.stdout).unwrap(), "+ b: Read\n");
+
+    let summary = fspy().arg("summarize").arg(&new_trace).output().unwrap();
+    assert!(summary.status.success());
+    let summary = String::from_utf8(summary.stdout).unwrap();
+    assert!(summary.contains("Exited with code 0"), "{}", summary);
+    assert!(summary.contains("Read"), "{}", summary);
+}
+
+#[test]
+fn forwards_signal_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let status = trace(dir.path(), &dir.path().join("trace.json"), "kill -TERM $$");
+    assert_eq!(
+        std::os::unix::process::ExitStatusExt::signal(&status),
+        Some(nix::sys::signal::Signal::SIGTERM as i32)
+    );
+}
+
+#[test]
+fn report_on_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = fspy()
+        .args(["trace", "/bin/sh", "-c", "echo hello"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    // Only the report, on a single line.
+    assert!(
+        report.starts_with('{') && report.ends_with("}\n"),
+        "{}",
+        report
+    );
+    assert_eq!(report.lines().count(), 1, "{}", report);
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "hello\n");
+}
+
+#[test]
+fn no_default_excludes() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("trace.json");
+    let status = fspy()
+        .args(["trace", "--no-default-excludes", "-o"])
+        .arg(&output)
+        .args(["/bin/sh", "-c", "echo hello > /dev/null; exit 3"])
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+    let report = fs::read_to_string(&output).unwrap();
+    assert!(report.contains(r#""path":"/dev/null""#), "{}", report);
+}
+
+#[test]
+fn allow_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path().canonicalize().unwrap();
+    fs::write(dir.join("a"), "").unwrap();
+    // Only what the shell and cat read, without `/proc`, `/sys` and `/dev`.
+    let mut command = fspy();
+    command.arg("trace");
+    for root in ["/bin", "/usr", "/lib", "/lib64", "/etc"] {
+        command.args(["--allow-read", root]);
+    }
+    let status = command
+        .arg("--allow-read")
+        .arg(&dir)
+        .arg("-o")
+        .arg(dir.join("trace.json"))
+        .args(["/bin/sh", "-c", "cat a"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
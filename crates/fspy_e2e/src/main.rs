@@ -1,14 +1,13 @@
 use std::{
-    collections::{btree_map::Entry, BTreeMap, HashMap},
+    collections::HashMap,
     env::{self, args},
     fs::{read, File},
     io::{stderr, BufWriter, Write as _},
     path::PathBuf,
-    process::{self, Stdio},
+    process,
 };
 
-use fspy::{AccessMode, PathAccess};
-use futures_util::future::try_join;
+use fspy::{AccessSet, AccessSummary};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -22,46 +21,15 @@ struct Case {
     cmd: Vec<String>,
 }
 
-struct AccessCollector {
-    dir: PathBuf,
-    accesses: BTreeMap<String, AccessMode>,
-}
-
-impl AccessCollector {
-    pub fn new(dir: PathBuf) -> Self {
-        Self {
-            dir,
-            accesses: BTreeMap::new(),
-        }
-    }
-    pub fn iter(&self) -> impl Iterator<Item = (&str, AccessMode)> {
-        self.accesses.iter().map(|(k, v)| (k.as_str(), *v))
-    }
-    pub fn add(&mut self, access: PathAccess) {
-        let path = PathBuf::from(access.path.to_cow_os_str().to_os_string());
-        if let Ok(relative_path) = path.strip_prefix(&self.dir) {
-            let relative_path = relative_path
-                .to_str()
-                .expect("relative path should be valid UTF-8")
-                .to_owned();
-            match self.accesses.entry(relative_path) {
-                Entry::Vacant(vacant) => {
-                    vacant.insert(access.mode);
-                }
-                Entry::Occupied(mut occupied) => {
-                    let occupied_mode = occupied.get_mut();
-                    match (*occupied_mode, access.mode) {
-                        (_, AccessMode::ReadWrite) => {
-                            *occupied_mode = AccessMode::ReadWrite;
-                        }
-                        (AccessMode::Read, AccessMode::ReadDir) => {
-                            *occupied_mode = AccessMode::ReadDir;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        }
+/// Formats `accesses` in the format of the snapshots, which have a single mode per path,
+/// with `ReadWrite` for reads and writes and `ReadDir` for reads of a directory.
+fn snapshot_mode(accesses: AccessSet) -> String {
+    if accesses == AccessSet::READ | AccessSet::WRITE {
+        "ReadWrite".to_owned()
+    } else if accesses == AccessSet::READ | AccessSet::READ_DIR {
+        "ReadDir".to_owned()
+    } else {
+        format!("{:?}", accesses)
     }
 }
 
@@ -85,19 +53,9 @@ async fn main() {
         let dir = manifest_dir.join(&case.dir);
         cmd.args(&case.cmd[1..])
             .envs(env::vars_os())
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
             .current_dir(&dir);
 
-        let tracked_child = cmd.spawn().await.unwrap();
-
-        let (accesses, output) = try_join(
-            tracked_child.accesses_future,
-            tracked_child.tokio_child.wait_with_output(),
-        )
-        .await
-        .unwrap();
+        let output = cmd.output_with_accesses().await.unwrap();
         if !output.status.success() {
             eprintln!("----- stdout begin -----");
             stderr().write_all(&output.stdout).unwrap();
@@ -110,14 +68,13 @@ async fn main() {
             process::exit(1);
         }
 
-        let mut collector = AccessCollector::new(dir);
-        for access in accesses.iter() {
-            collector.add(access);
-        }
+        let mut summary = AccessSummary::relative_to(dir);
+        summary.extend(output.accesses.iter());
         let snap_file = File::create(manifest_dir.join(format!("snaps/{}.txt", name))).unwrap();
         let mut snap_writer = BufWriter::new(snap_file);
-        for (path, mode) in collector.iter() {
-            writeln!(snap_writer, "{}: {:?}", path, mode).unwrap();
+        for (path, accesses) in summary.iter() {
+            let path = path.to_str().expect("relative path should be valid UTF-8");
+            writeln!(snap_writer, "{}: {}", path, snapshot_mode(accesses)).unwrap();
         }
     }
 }
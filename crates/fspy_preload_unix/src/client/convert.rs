@@ -1,6 +1,6 @@
 use std::{
     env::current_dir,
-    ffi::{CStr, OsStr},
+    ffi::CStr,
     os::{fd::BorrowedFd, unix::ffi::OsStrExt as _},
     path::PathBuf,
 };
@@ -9,20 +9,19 @@ use std::{
 use bstr::BString;
 use bstr::{BStr, ByteSlice};
 use fspy_shared::ipc::{AccessMode, NativeStr};
+use fspy_shared_unix::path;
 use libc::{c_char, c_int};
 use nix::fcntl::FcntlArg;
-use nix::unistd::getcwd;
 use std::{
     borrow::Cow,
     ffi::{CString, OsString},
     os::{fd::RawFd, unix::ffi::OsStringExt as _},
 };
 
+use super::cwd;
+
 #[cfg(target_os = "linux")]
 fn get_fd_path(fd: RawFd) -> nix::Result<Option<PathBuf>> {
-    if fd == libc::AT_FDCWD {
-        return Ok(Some(getcwd()?));
-    };
     match nix::fcntl::readlink(
         CString::new(format!("/proc/self/fd/{}", fd))
             .unwrap()
@@ -36,9 +35,6 @@ fn get_fd_path(fd: RawFd) -> nix::Result<Option<PathBuf>> {
 
 #[cfg(target_os = "macos")]
 fn get_fd_path(fd: RawFd) -> nix::Result<Option<PathBuf>> {
-    if fd == libc::AT_FDCWD {
-        return Ok(Some(getcwd()?));
-    };
     let mut path = std::path::PathBuf::new();
     match nix::fcntl::fcntl(
         unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) },
@@ -50,77 +46,83 @@ fn get_fd_path(fd: RawFd) -> nix::Result<Option<PathBuf>> {
     }
 }
 
+/// Calls `f` with `pathname` resolved against `dirfd`, or with `None` if `dirfd` has no path.
+/// With `AT_FDCWD`, a relative `pathname` is resolved against the current working directory, see `cwd::current`.
+fn resolve_at<E: From<nix::Error>>(
+    dirfd: RawFd,
+    pathname: &[u8],
+    f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+) -> Result<(), E> {
+    if path::is_absolute(pathname) {
+        return f(Some(pathname.as_bstr()));
+    }
+    let dir = if dirfd == libc::AT_FDCWD {
+        cwd::current()?
+    } else {
+        let Some(dir) = get_fd_path(dirfd)? else {
+            return f(None);
+        };
+        dir.into_os_string().into_vec()
+    };
+    f(Some(path::resolve_at(&dir, pathname).as_bstr()))
+}
+
 pub trait ToAbsolutePath {
-    unsafe fn to_absolute_path<R, F: FnOnce(Option<&BStr>) -> nix::Result<R>>(self, f: F)
-    -> nix::Result<R>;
+    /// Calls `f` with the absolute path, or with `None` if it can't be resolved.
+    unsafe fn to_absolute_path<E: From<nix::Error>>(
+        self,
+        f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+    ) -> Result<(), E>;
 }
 
 pub struct Fd(pub c_int);
 impl ToAbsolutePath for Fd {
-    unsafe fn to_absolute_path<R, F: FnOnce(Option<&BStr>) -> nix::Result<R>>(
+    unsafe fn to_absolute_path<E: From<nix::Error>>(
         self,
-        f: F,
-    ) -> nix::Result<R> {
-        let path = get_fd_path(self.0)?;
-        f(path.as_ref().map(|p| p.as_os_str().as_bytes().as_bstr()))
+        f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        resolve_at(self.0, b"", f)
     }
 }
 
 pub struct MaybeRelative<'a>(pub NativeStr<'a>);
 impl ToAbsolutePath for MaybeRelative<'_> {
-    unsafe fn to_absolute_path<R, F: FnOnce(Option<&BStr>) -> nix::Result<R>>(
+    unsafe fn to_absolute_path<E: From<nix::Error>>(
         self,
-        f: F,
-    ) -> nix::Result<R> {
-        let pathname = self.0.as_os_str().as_bytes();
-        if pathname.first().copied() == Some(b'/') {
-            f(Some(pathname.as_bstr()))
-        } else {
-            let Some(mut abs_path) = get_fd_path(libc::AT_FDCWD)? else {
-                return f(None);
-            };
-            if !pathname.is_empty() {
-                abs_path.push(OsStr::from_bytes(pathname));
-            }
-            f(Some(abs_path.as_os_str().as_bytes().as_bstr()))
-        }
+        f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        resolve_at(libc::AT_FDCWD, self.0.as_os_str().as_bytes(), f)
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct PathAt(pub c_int, pub *const c_char);
 
 impl ToAbsolutePath for PathAt {
-    unsafe fn to_absolute_path<R, F: FnOnce(Option<&BStr>) -> nix::Result<R>>(
+    unsafe fn to_absolute_path<E: From<nix::Error>>(
         self,
-        f: F,
-    ) -> nix::Result<R> {
-        let pathname = unsafe { CStr::from_ptr(self.1) }.to_bytes().as_bstr();
-
-        if pathname.first().copied() == Some(b'/') {
-            f(pathname.into())
-        } else {
-            let Some(mut abs_path) = get_fd_path(self.0)? else {
-                return f(None);
-            };
-            if !pathname.is_empty() {
-                abs_path.push(OsStr::from_bytes(pathname));
-            }
-            f(Some(abs_path.as_os_str().as_bytes().as_bstr()))
-        }
+        f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let pathname = unsafe { CStr::from_ptr(self.1) }.to_bytes();
+        resolve_at(self.0, pathname, f)
     }
 }
 
 impl ToAbsolutePath for *const c_char {
-    unsafe fn to_absolute_path<R, F: FnOnce(Option<&BStr>) -> nix::Result<R>>(
+    unsafe fn to_absolute_path<E: From<nix::Error>>(
         self,
-        f: F,
-    ) -> nix::Result<R> {
+        f: impl FnOnce(Option<&BStr>) -> Result<(), E>,
+    ) -> Result<(), E> {
         unsafe { PathAt(libc::AT_FDCWD, self).to_absolute_path(f) }
     }
 }
 
 pub trait ToAccessMode {
     unsafe fn to_access_mode(self) -> AccessMode;
+    /// Whether the call follows a symlink at the path.
+    fn follow_symlinks(&self) -> bool {
+        true
+    }
 }
 
 impl ToAccessMode for AccessMode {
@@ -129,14 +131,25 @@ impl ToAccessMode for AccessMode {
     }
 }
 
+/// An access by a call on the symlink itself, like `lstat` or `unlink`.
+#[derive(Clone, Copy)]
+pub struct NoFollow(pub AccessMode);
+impl ToAccessMode for NoFollow {
+    unsafe fn to_access_mode(self) -> AccessMode {
+        self.0
+    }
+    fn follow_symlinks(&self) -> bool {
+        false
+    }
+}
+
 pub struct OpenFlags(pub c_int);
 impl ToAccessMode for OpenFlags {
     unsafe fn to_access_mode(self) -> AccessMode {
-        match self.0 & libc::O_ACCMODE {
-            libc::O_RDWR => AccessMode::ReadWrite,
-            libc::O_WRONLY => AccessMode::Write,
-            _ => AccessMode::Read,
-        }
+        AccessMode::from_open_flags(self.0)
+    }
+    fn follow_symlinks(&self) -> bool {
+        self.0 & libc::O_NOFOLLOW == 0
     }
 }
 
@@ -153,3 +166,37 @@ impl ToAccessMode for ModeStr {
         }
     }
 }
+
+/// Return values of intercepted functions, which indicate whether the call failed.
+pub trait ReturnValue {
+    fn is_failure(&self) -> bool;
+    /// The value that indicates a failure, returned by calls failed without calling the original function.
+    fn failure() -> Self;
+}
+
+impl ReturnValue for c_int {
+    fn is_failure(&self) -> bool {
+        *self == -1
+    }
+    fn failure() -> Self {
+        -1
+    }
+}
+
+impl ReturnValue for libc::ssize_t {
+    fn is_failure(&self) -> bool {
+        *self == -1
+    }
+    fn failure() -> Self {
+        -1
+    }
+}
+
+impl<T> ReturnValue for *mut T {
+    fn is_failure(&self) -> bool {
+        self.is_null()
+    }
+    fn failure() -> Self {
+        std::ptr::null_mut()
+    }
+}
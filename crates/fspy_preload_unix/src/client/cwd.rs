@@ -0,0 +1,161 @@
+//! The working directory that relative paths of intercepted calls are resolved against.
+//!
+//! Intercepted calls on paths hold a lock from before the call is made until its accesses are recorded
+//! (`begin_call` and `end_call`), and calls that change the working directory hold it exclusively
+//! (`changing`). The working directory is read from the kernel once per call, under the lock, so relative
+//! paths are resolved against the working directory that the call used, even if another thread changes it.
+//!
+//! The lock is only waited for up to `LOCK_TIMEOUT`, so that a call that blocks, like opening a FIFO,
+//! doesn't block `chdir` in other threads indefinitely. After that, the call goes on without the lock,
+//! and paths may be resolved against a working directory that changed during the call. Changes that
+//! aren't intercepted, like the `chdir` syscall made directly, aren't serialized either.
+
+use std::{
+    cell::{Cell, RefCell},
+    os::unix::ffi::OsStringExt as _,
+    sync::atomic::{AtomicU32, Ordering},
+    thread::yield_now,
+    time::{Duration, Instant},
+};
+
+use libc::c_int;
+use nix::errno::Errno;
+
+use super::convert::ReturnValue as _;
+
+/// How long `begin_call` and `changing` wait for the lock before going on without it.
+const LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The number of calls holding the lock, or `WRITER` if the working directory is being changed.
+static LOCK: AtomicU32 = AtomicU32::new(0);
+const WRITER: u32 = u32::MAX;
+
+/// The number of threads waiting to change the working directory. Calls wait for them to finish
+/// before taking the lock, so that a thread making calls continuously doesn't hold off `chdir`.
+static WRITERS_WAITING: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Held {
+    None,
+    Call,
+    Change,
+}
+
+thread_local! {
+    static HELD: Cell<Held> = const { Cell::new(Held::None) };
+    /// The working directory read during the call that holds the lock.
+    static SNAPSHOT: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Calls `try_lock` until it succeeds or `LOCK_TIMEOUT` passes, and returns whether it succeeded.
+fn wait_for(mut try_lock: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    loop {
+        if try_lock() {
+            return true;
+        }
+        if start.elapsed() >= LOCK_TIMEOUT {
+            return false;
+        }
+        yield_now();
+    }
+}
+
+/// Takes the lock for an intercepted call on paths, before its accesses are resolved and the call is made.
+/// Nothing happens if the thread already holds the lock.
+pub fn begin_call() {
+    if HELD.get() != Held::None {
+        return;
+    }
+    let locked = wait_for(|| {
+        if WRITERS_WAITING.load(Ordering::Acquire) != 0 {
+            return false;
+        }
+        let calls = LOCK.load(Ordering::Relaxed);
+        calls < WRITER - 1
+            && LOCK
+                .compare_exchange_weak(calls, calls + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+    });
+    if locked {
+        HELD.set(Held::Call);
+    }
+}
+
+/// Releases the lock taken by `begin_call`, after the accesses of the call are recorded.
+/// Nothing happens if the thread doesn't hold it.
+pub fn end_call() {
+    if HELD.get() != Held::Call {
+        return;
+    }
+    SNAPSHOT.take();
+    LOCK.fetch_sub(1, Ordering::Release);
+    HELD.set(Held::None);
+}
+
+/// Runs `f`, which changes the working directory, holding the lock exclusively.
+pub fn changing<R>(f: impl FnOnce() -> R) -> R {
+    // A signal handler may change the working directory during a call of the same thread.
+    end_call();
+    if HELD.get() == Held::Change {
+        return f();
+    }
+    WRITERS_WAITING.fetch_add(1, Ordering::Relaxed);
+    let locked = wait_for(|| {
+        LOCK.compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    });
+    WRITERS_WAITING.fetch_sub(1, Ordering::Release);
+    if !locked {
+        return f();
+    }
+    HELD.set(Held::Change);
+    let result = f();
+    HELD.set(Held::None);
+    LOCK.store(0, Ordering::Release);
+    result
+}
+
+/// Resets the lock in the child of `fork`, where the threads holding it don't exist.
+#[cfg(not(test))]
+pub fn reset_atfork() {
+    LOCK.store(0, Ordering::Relaxed);
+    WRITERS_WAITING.store(0, Ordering::Relaxed);
+    HELD.set(Held::None);
+    SNAPSHOT.take();
+}
+
+/// Returns the working directory of the process, as the kernel has it.
+/// During a call holding the lock, it's read once and the same is returned for the rest of the call.
+pub fn current() -> nix::Result<Vec<u8>> {
+    if HELD.get() != Held::Call {
+        return read();
+    }
+    if let Some(snapshot) = SNAPSHOT.with_borrow(Clone::clone) {
+        return Ok(snapshot);
+    }
+    let cwd = read()?;
+    SNAPSHOT.set(Some(cwd.clone()));
+    Ok(cwd)
+}
+
+#[cfg(target_os = "linux")]
+fn read() -> nix::Result<Vec<u8>> {
+    Ok(nix::fcntl::readlink(c"/proc/self/cwd")?.into_vec())
+}
+
+#[cfg(target_os = "macos")]
+fn read() -> nix::Result<Vec<u8>> {
+    Ok(nix::unistd::getcwd()?.into_os_string().into_vec())
+}
+
+/// Runs `chdir`, a call that changes the working directory, and returns what it returned,
+/// along with the new working directory if it succeeded. `errno` is preserved.
+/// It's called in `changing`.
+pub fn change(chdir: impl FnOnce() -> c_int) -> (c_int, Option<Vec<u8>>) {
+    let ret = chdir();
+    let errno = Errno::last_raw();
+    let new_cwd = if ret.is_failure() { None } else { read().ok() };
+    Errno::set_raw(errno);
+    (ret, new_cwd)
+}
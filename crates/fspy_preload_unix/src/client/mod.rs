@@ -1,50 +1,56 @@
 pub mod convert;
+pub mod cwd;
+mod process;
 pub mod raw_exec;
 
 use core::panic;
 use std::{
     borrow::Cow,
-    cell::{Ref, RefCell},
-    ffi::CStr,
+    cell::{Cell, Ref, RefCell},
+    collections::HashSet,
+    ffi::{CStr, CString, OsStr},
     fmt::Debug,
     io,
+    mem::transmute,
     ops::DerefMut as _,
     os::{
         fd::{AsRawFd, RawFd},
         unix::ffi::OsStrExt,
     },
+    path::Path,
     ptr::null,
     sync::{
-        LazyLock, OnceLock,
-        atomic::{AtomicU8, AtomicU16, AtomicUsize, Ordering, fence},
+        LazyLock, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicUsize, Ordering},
     },
     thread::panicking,
     time::{Instant, SystemTime},
 };
 
 use anyhow::Context;
-use bincode::{
-    enc::write::SizeWriter, encode_into_slice, encode_into_std_write, encode_into_writer,
+use bstr::{BStr, ByteSlice as _};
+use fspy_shared::ipc::{
+    AccessMode, AccessOutcome, CwdChange, EnvRead, NativeStr, NativeString, PathAccess,
+    ProcessInfo, Record, shm,
 };
-use bstr::BStr;
-use fspy_shared::ipc::{AccessMode, BINCODE_CONFIG, NativeStr, NativeString, PathAccess};
 use fspy_shared_unix::{
     exec::ExecResolveConfig,
     payload::{EncodedPayload, decode_payload_from_env},
     spawn::{PreExec, handle_exec},
 };
 
-use convert::{ToAbsolutePath, ToAccessMode};
-use libc::{off_t, pthread_atfork};
+use convert::{ReturnValue, ToAbsolutePath, ToAccessMode};
+use libc::{c_int, off_t, pthread_atfork};
 use memmap2::{Mmap, MmapMut};
 use nix::{
+    errno::Errno,
     fcntl::OFlag,
     sys::{
         mman::{shm_open, shm_unlink},
         stat::Mode,
     },
     time::{ClockId, clock_gettime},
-    unistd::{Pid, ftruncate, getpid},
+    unistd::{Pid, ftruncate, getpid, getppid},
 };
 use passfd::FdPassingExt;
 use raw_exec::RawExec;
@@ -71,11 +77,24 @@ pub struct Client {
     encoded_payload: EncodedPayload,
     shm_id: AtomicUsize,
     tls_shm_cursor: ThreadLocal<RefCell<ShmCursor>>,
+    // Cached because they only change in forked children, where they are updated in the atfork handler.
+    pid: AtomicU32,
+    ppid: AtomicU32,
+    process_reported: AtomicBool,
+    env_names_read: Mutex<EnvNamesRead>,
 
     #[cfg(target_os = "macos")]
     posix_spawn_file_actions: OnceLock<libc::posix_spawn_file_actions_t>,
 }
 
+/// The environment variables already reported as read by the process `pid`.
+/// The pid tells whether it's inherited from the parent of a forked child, which starts over.
+#[derive(Default)]
+struct EnvNamesRead {
+    pid: u32,
+    names: HashSet<Vec<u8>>,
+}
+
 #[cfg(target_os = "macos")]
 unsafe impl Sync for Client {}
 #[cfg(target_os = "macos")]
@@ -87,7 +106,7 @@ impl Debug for Client {
     }
 }
 
-const SHM_CHUNK_SIZE: off_t = 256 * 1024;
+const SHM_CHUNK_SIZE: off_t = shm::CHUNK_SIZE as off_t;
 
 impl Client {
     fn from_env() -> Self {
@@ -96,6 +115,10 @@ impl Client {
             shm_id: AtomicUsize::new(0),
             encoded_payload,
             tls_shm_cursor: ThreadLocal::new(),
+            pid: AtomicU32::new(getpid().as_raw() as u32),
+            ppid: AtomicU32::new(getppid().as_raw() as u32),
+            process_reported: AtomicBool::new(false),
+            env_names_read: Mutex::default(),
             #[cfg(target_os = "macos")]
             posix_spawn_file_actions: OnceLock::new(),
         }
@@ -112,11 +135,12 @@ impl Client {
             Mode::empty(),
         )?;
         shm_unlink(shm_name.as_str())?;
+        // Truncate before sending so that the receiver can map the chunk as soon as it gets the fd.
+        ftruncate(&shm_fd, SHM_CHUNK_SIZE)?;
         self.encoded_payload
             .payload
             .ipc_fd
             .send_fd(shm_fd.as_raw_fd())?;
-        ftruncate(&shm_fd, SHM_CHUNK_SIZE)?;
         let mmap_mut = unsafe { MmapMut::map_mut(&shm_fd) }?;
         Ok(ShmCursor {
             mmap_mut,
@@ -148,29 +172,147 @@ impl Client {
         }
     }
 
+    fn send_record(&self, record: Record<'_>) -> anyhow::Result<()> {
+        let record_size = shm::record_size(&record)?;
+        self.with_shm_buf(record_size, |buf| Ok(shm::write_record(buf, &record)?))
+    }
+
+    /// Reports the current process if it's not reported since it started or was forked.
+    fn ensure_process_reported(&self) -> anyhow::Result<()> {
+        if self.process_reported.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let result = (|| {
+            let (program, args) = process::current_program_and_args()?;
+            self.send_record(Record::process(
+                self.pid.load(Ordering::Relaxed),
+                ProcessInfo {
+                    ppid: self.ppid.load(Ordering::Relaxed),
+                    program: program.as_path().into(),
+                    args: NativeStr::from_bytes(&args),
+                },
+            ))
+        })();
+        if result.is_err() {
+            // Retried on the next record.
+            self.process_reported.store(false, Ordering::Relaxed);
+        }
+        result
+    }
+
     fn send(&self, path_access: PathAccess<'_>) -> anyhow::Result<()> {
-        let path = path_access.path.as_bstr();
-        if path.starts_with(b"/dev/")
-            || (cfg!(target_os = "linux")
-                && (path.starts_with(b"/proc/") || path.starts_with(b"/sys/")))
-        {
+        let payload = &self.encoded_payload.payload;
+        payload
+            .path_normalization
+            .normalize_access(path_access, realpath, |path_access| {
+                if !payload.path_filter.matches_access(&path_access) {
+                    return Ok(());
+                };
+                self.ensure_process_reported()?;
+                self.send_record(Record::access(
+                    self.pid.load(Ordering::Relaxed),
+                    path_access,
+                ))
+            })
+    }
+
+    /// Checks `path_access` against the access policy, and records it as a violation if it's denied.
+    /// Returns the error code that the call should fail with.
+    fn check(&self, path_access: PathAccess<'_>) -> anyhow::Result<Option<i32>> {
+        let payload = &self.encoded_payload.payload;
+        let Some(access_policy) = &payload.access_policy else {
+            return Ok(None);
+        };
+        let Err(errno) = access_policy.check(&path_access, realpath) else {
+            return Ok(None);
+        };
+        let path_access = PathAccess {
+            outcome: AccessOutcome::Failed(errno),
+            ..path_access
+        };
+        // Violations are recorded regardless of the path filter.
+        payload
+            .path_normalization
+            .normalize_access(path_access, realpath, |path_access| {
+                self.ensure_process_reported()?;
+                self.send_record(Record::violation(
+                    self.pid.load(Ordering::Relaxed),
+                    path_access,
+                ))
+            })?;
+        Ok(Some(errno))
+    }
+
+    /// Whether `name` is read for the first time in the current process.
+    fn is_first_env_read(&self, name: &[u8]) -> bool {
+        // The lock may be held forever in a forked child, if another thread of the parent held it at the fork.
+        let Ok(mut env_names_read) = self.env_names_read.try_lock() else {
+            return true;
+        };
+        let pid = self.pid.load(Ordering::Relaxed);
+        if env_names_read.pid != pid {
+            *env_names_read = EnvNamesRead {
+                pid,
+                names: HashSet::new(),
+            };
+        }
+        env_names_read.names.insert(name.to_vec())
+    }
+
+    pub unsafe fn try_handle_env_read(
+        &self,
+        name: *const libc::c_char,
+        value: *const libc::c_char,
+    ) -> anyhow::Result<()> {
+        if name.is_null() {
+            return Ok(());
+        }
+        let name = unsafe { CStr::from_ptr(name) }.to_bytes();
+        if !self.is_first_env_read(name) {
             return Ok(());
+        }
+        let recorded_value = if self.encoded_payload.payload.record_env_values && !value.is_null() {
+            Some(NativeStr::from_bytes(
+                unsafe { CStr::from_ptr(value) }.to_bytes(),
+            ))
+        } else {
+            None
         };
-        let mut size_writer = SizeWriter::default();
-        encode_into_writer(&path_access, &mut size_writer, BINCODE_CONFIG)?;
-
-        self.with_shm_buf(1 + size_writer.bytes_written, |buf| {
-            let data_buf = &mut buf[1..];
-            let written_size = encode_into_slice(&path_access, data_buf, BINCODE_CONFIG)?;
-            debug_assert_eq!(written_size, size_writer.bytes_written);
-
-            let flag_ptr = buf.as_mut_ptr().cast::<u8>();
-            fence(Ordering::Release);
-            unsafe { AtomicU8::from_ptr(flag_ptr) }.store(1, Ordering::Release);
-            Ok(())
-        })?;
+        self.ensure_process_reported()?;
+        self.send_record(Record::env_read(
+            self.pid.load(Ordering::Relaxed),
+            EnvRead {
+                name: NativeStr::from_bytes(name),
+                is_set: !value.is_null(),
+                value: recorded_value,
+            },
+        ))
+    }
 
-        Ok(())
+    /// Records a change of the working directory to `new_cwd`, or to `abs_dir` if the call failed.
+    /// `abs_dir` is what was passed to the call resolved like other paths, and is recorded as read
+    /// if `reads_dir` is set.
+    fn try_handle_cwd_change(
+        &self,
+        abs_dir: Option<&[u8]>,
+        new_cwd: Option<&[u8]>,
+        reads_dir: bool,
+        outcome: AccessOutcome,
+    ) -> anyhow::Result<()> {
+        if reads_dir && let Some(abs_dir) = abs_dir {
+            self.send(PathAccess::read(abs_dir.as_bstr()).with_outcome(outcome))?;
+        }
+        let Some(path) = new_cwd.or(abs_dir) else {
+            return Ok(());
+        };
+        self.ensure_process_reported()?;
+        self.send_record(Record::cwd_change(
+            self.pid.load(Ordering::Relaxed),
+            CwdChange {
+                path: NativeStr::from_bytes(path),
+                outcome,
+            },
+        ))
     }
 
     pub unsafe fn handle_exec<R>(
@@ -178,33 +320,154 @@ impl Client {
         config: ExecResolveConfig,
         raw_exec: RawExec,
         f: impl FnOnce(RawExec, Option<PreExec>) -> nix::Result<R>,
+    ) -> nix::Result<R> {
+        // The program is resolved and executed under the lock of `cwd`, like other calls on paths.
+        cwd::begin_call();
+        let result = unsafe { self.handle_exec_locked(config, raw_exec, f) };
+        cwd::end_call();
+        result
+    }
+
+    unsafe fn handle_exec_locked<R>(
+        &self,
+        config: ExecResolveConfig,
+        raw_exec: RawExec,
+        f: impl FnOnce(RawExec, Option<PreExec>) -> nix::Result<R>,
     ) -> nix::Result<R> {
         let mut exec = unsafe { raw_exec.to_exec() };
+        let mut denied = None;
         let pre_exec = handle_exec(&mut exec, config, &self.encoded_payload, |path_access| {
-            self.send(path_access).unwrap();
+            with_client(|client| {
+                if let Some(errno) = client.check(path_access).unwrap() {
+                    denied.get_or_insert(errno);
+                } else {
+                    client.send(path_access).unwrap();
+                }
+            });
         })?;
+        if let Some(errno) = denied {
+            return Err(Errno::from_raw(errno));
+        }
         RawExec::from_exec(exec, |raw_command| f(raw_command, pre_exec))
     }
 
-    pub unsafe fn try_handle_open(
-        &self,
+    /// Calls `f` with the access of a call on `path`.
+    unsafe fn for_each_open_access(
         path: impl ToAbsolutePath,
         mode: impl ToAccessMode,
+        outcome: AccessOutcome,
+        mut f: impl FnMut(PathAccess<'_>) -> anyhow::Result<()>,
     ) -> anyhow::Result<()> {
+        let follow_symlinks = mode.follow_symlinks();
         let mode = unsafe { mode.to_access_mode() };
-        let () = unsafe {
+        unsafe {
             path.to_absolute_path(|abs_path| {
                 let Some(abs_path) = abs_path else {
-                    return Ok(Ok(()));
+                    return Ok(());
                 };
-                Ok(self.send(PathAccess {
+                f(PathAccess {
                     mode,
                     path: abs_path.into(),
-                }))
+                    dest: None,
+                    outcome,
+                    follow_symlinks,
+                    original_path: None,
+                    original_dest: None,
+                })
             })
-        }??;
+        }
+    }
 
-        Ok(())
+    /// Calls `f` with the access of a call on `path` and `dest`.
+    unsafe fn for_each_paths_access(
+        path: impl ToAbsolutePath,
+        dest: impl ToAbsolutePath,
+        mode: impl ToAccessMode,
+        outcome: AccessOutcome,
+        mut f: impl FnMut(PathAccess<'_>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let follow_symlinks = mode.follow_symlinks();
+        let mode = unsafe { mode.to_access_mode() };
+        unsafe {
+            path.to_absolute_path(|abs_path| {
+                let Some(abs_path) = abs_path else {
+                    return Ok(());
+                };
+                dest.to_absolute_path(|abs_dest| {
+                    let Some(abs_dest) = abs_dest else {
+                        return Ok(());
+                    };
+                    f(PathAccess::with_dest(mode, abs_path, abs_dest)
+                        .with_outcome(outcome)
+                        .with_follow_symlinks(follow_symlinks))
+                })
+            })
+        }
+    }
+
+    /// Calls `f` with the access of creating a symlink at `linkpath` to `target`.
+    /// Unlike other relative paths, a relative symlink target is resolved against the directory containing the link.
+    unsafe fn for_each_symlink_access(
+        target: *const libc::c_char,
+        linkpath: impl ToAbsolutePath,
+        outcome: AccessOutcome,
+        mut f: impl FnMut(PathAccess<'_>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let target = unsafe { CStr::from_ptr(target) }.to_bytes();
+        unsafe {
+            linkpath.to_absolute_path(|abs_linkpath| {
+                let Some(abs_linkpath) = abs_linkpath else {
+                    return Ok(());
+                };
+                let link_dir = Path::new(OsStr::from_bytes(abs_linkpath))
+                    .parent()
+                    .unwrap_or(Path::new("/"));
+                let abs_target = link_dir.join(OsStr::from_bytes(target));
+                f(
+                    PathAccess::with_dest(AccessMode::Link, abs_target.as_path(), abs_linkpath)
+                        .with_outcome(outcome)
+                        .with_follow_symlinks(false),
+                )
+            })
+        }
+    }
+
+    pub unsafe fn try_handle_open(
+        &self,
+        path: impl ToAbsolutePath,
+        mode: impl ToAccessMode,
+        outcome: AccessOutcome,
+    ) -> anyhow::Result<()> {
+        unsafe {
+            Self::for_each_open_access(path, mode, outcome, |path_access| self.send(path_access))
+        }
+    }
+
+    pub unsafe fn try_handle_paths(
+        &self,
+        path: impl ToAbsolutePath,
+        dest: impl ToAbsolutePath,
+        mode: impl ToAccessMode,
+        outcome: AccessOutcome,
+    ) -> anyhow::Result<()> {
+        unsafe {
+            Self::for_each_paths_access(path, dest, mode, outcome, |path_access| {
+                self.send(path_access)
+            })
+        }
+    }
+
+    pub unsafe fn try_handle_symlink(
+        &self,
+        target: *const libc::c_char,
+        linkpath: impl ToAbsolutePath,
+        outcome: AccessOutcome,
+    ) -> anyhow::Result<()> {
+        unsafe {
+            Self::for_each_symlink_access(target, linkpath, outcome, |path_access| {
+                self.send(path_access)
+            })
+        }
     }
 
     #[cfg(not(target_os = "macos"))]
@@ -280,16 +543,242 @@ impl Client {
     }
 }
 
+/// Resolves the symlinks in `path` for `PathNormalization::canonicalize`.
+/// The original `realpath` is called directly, so that the resolution is not recorded as an access.
+fn realpath(path: &[u8]) -> Option<Vec<u8>> {
+    type Realpath =
+        unsafe extern "C" fn(*const libc::c_char, *mut libc::c_char) -> *mut libc::c_char;
+    static REALPATH: LazyLock<Realpath> =
+        LazyLock::new(|| unsafe { transmute(libc::dlsym(libc::RTLD_NEXT, c"realpath".as_ptr())) });
+    let path = CString::new(path).ok()?;
+    let mut resolved = vec![0 as libc::c_char; libc::PATH_MAX as usize];
+    if unsafe { REALPATH(path.as_ptr(), resolved.as_mut_ptr()) }.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(resolved.as_ptr()) }
+            .to_bytes()
+            .to_vec(),
+    )
+}
+
 static CLIENT: OnceLock<Client> = OnceLock::new();
 
 pub fn global_client() -> Option<&'static Client> {
     CLIENT.get()
 }
 
-pub unsafe fn handle_open(path: impl ToAbsolutePath, mode: impl ToAccessMode) {
-    if let Some(client) = global_client() {
-        unsafe { client.try_handle_open(path, mode) }.unwrap();
+thread_local! {
+    /// Whether the thread is running the client, see `with_client`.
+    static IN_CLIENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Calls `f` with the client, unless it's not initialized or the thread is already running it.
+///
+/// The client calls intercepted functions itself: `readlink` of `/proc/self/cwd` and `/proc/self/fd` to
+/// resolve paths, `shm_open` for the shared memory that records are written to, and reads of `/proc/self`
+/// to report the process. Those calls are neither recorded nor checked against the access policy, whatever
+/// the path filter and the policy are, as that would make the same calls again, or fail them.
+fn with_client<R>(f: impl FnOnce(&'static Client) -> R) -> Option<R> {
+    let client = global_client()?;
+    if IN_CLIENT.replace(true) {
+        return None;
     }
+    let result = f(client);
+    IN_CLIENT.set(false);
+    Some(result)
+}
+
+/// Records an access made by an intercepted function that returned `ret`, and returns `ret`.
+/// It must be called right after the intercepted function, before anything else changes `errno`.
+/// `errno` is restored afterwards so that the caller still sees the error of the intercepted function.
+fn handle_ret<R: ReturnValue>(ret: R, f: impl FnOnce(&Client, AccessOutcome)) -> R {
+    let errno = Errno::last_raw();
+    let outcome = AccessOutcome::from_last_os_error(ret.is_failure());
+    with_client(|client| {
+        f(client, outcome);
+        cwd::end_call();
+    });
+    Errno::set_raw(errno);
+    ret
+}
+
+/// Checks the accesses of an intercepted call against the access policy, before the call is made.
+/// If any is denied, the denials are recorded, `errno` is set, and the failure that the intercepted
+/// function should return instead of calling the original function is returned.
+///
+/// `for_each_access` calls the function it's given with each access of the call.
+/// The calls that the client makes itself are not checked, see `with_client`.
+fn enforce<R: ReturnValue>(
+    client: &Client,
+    for_each_access: impl FnOnce(
+        &mut dyn FnMut(PathAccess<'_>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>,
+) -> Option<R> {
+    client.encoded_payload.payload.access_policy.as_ref()?;
+    // The call isn't made yet, so relative paths are resolved against the current working directory.
+    let mut denied = None;
+    for_each_access(&mut |path_access| {
+        if let Some(errno) = client.check(path_access)? {
+            denied.get_or_insert(errno);
+        }
+        Ok(())
+    })
+    .unwrap();
+    let errno = denied?;
+    Errno::set_raw(errno);
+    Some(R::failure())
+}
+
+/// Starts an intercepted call on paths, before it's made: takes the lock of `cwd` that `handle_ret`
+/// releases after the call, and checks the accesses of the call like `enforce`.
+fn begin_call<R: ReturnValue>(
+    client: &Client,
+    for_each_access: impl FnOnce(
+        &mut dyn FnMut(PathAccess<'_>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>,
+) -> Option<R> {
+    cwd::begin_call();
+    let denied = enforce(client, for_each_access);
+    if denied.is_some() {
+        cwd::end_call();
+    }
+    denied
+}
+
+/// Checks the access of a call on `path` against the access policy, like `handle_open` records it.
+/// See `begin_call`.
+pub unsafe fn enforce_open<R: ReturnValue>(
+    path: impl ToAbsolutePath,
+    mode: impl ToAccessMode,
+) -> Option<R> {
+    with_client(|client| {
+        begin_call(client, |f| unsafe {
+            Client::for_each_open_access(path, mode, AccessOutcome::Unknown, f)
+        })
+    })
+    .flatten()
+}
+
+/// Checks the access of a call on `path` and `dest` against the access policy, like `handle_paths` records it.
+/// See `begin_call`.
+pub unsafe fn enforce_paths<R: ReturnValue>(
+    path: impl ToAbsolutePath,
+    dest: impl ToAbsolutePath,
+    mode: impl ToAccessMode,
+) -> Option<R> {
+    with_client(|client| {
+        begin_call(client, |f| unsafe {
+            Client::for_each_paths_access(path, dest, mode, AccessOutcome::Unknown, f)
+        })
+    })
+    .flatten()
+}
+
+/// Checks the access of creating a symlink against the access policy, like `handle_symlink` records it.
+/// See `begin_call`.
+pub unsafe fn enforce_symlink<R: ReturnValue>(
+    target: *const libc::c_char,
+    linkpath: impl ToAbsolutePath,
+) -> Option<R> {
+    with_client(|client| {
+        begin_call(client, |f| unsafe {
+            Client::for_each_symlink_access(target, linkpath, AccessOutcome::Unknown, f)
+        })
+    })
+    .flatten()
+}
+
+pub unsafe fn handle_open<R: ReturnValue>(
+    path: impl ToAbsolutePath,
+    mode: impl ToAccessMode,
+    ret: R,
+) -> R {
+    handle_ret(ret, |client, outcome| {
+        unsafe { client.try_handle_open(path, mode, outcome) }.unwrap();
+    })
+}
+
+/// Records a read of the environment variable `name`, whose value is `value`, or null if it's not set.
+pub unsafe fn handle_env_read(name: *const libc::c_char, value: *const libc::c_char) {
+    let errno = Errno::last_raw();
+    with_client(|client| unsafe { client.try_handle_env_read(name, value) }.unwrap());
+    Errno::set_raw(errno);
+}
+
+/// Runs `chdir`, an intercepted call that changes the working directory to `dir`, and records the change.
+/// If `reads_dir` is set, `dir` is a path looked up by the call, and is recorded as read.
+/// Other intercepted calls wait for the change, see `cwd::changing`.
+pub unsafe fn handle_cwd_change(
+    dir: impl ToAbsolutePath,
+    reads_dir: bool,
+    chdir: impl FnOnce() -> c_int,
+) -> c_int {
+    let mut chdir = Some(chdir);
+    let handled = with_client(|client| {
+        cwd::changing(|| {
+            // A relative `dir` is relative to the working directory before the call, so it's resolved beforehand.
+            let mut abs_dir = None;
+            let _ = unsafe {
+                dir.to_absolute_path(|dir| {
+                    abs_dir = dir.map(|dir| dir.to_vec());
+                    nix::Result::Ok(())
+                })
+            };
+            if reads_dir {
+                let denied = enforce(client, |f| match &abs_dir {
+                    Some(abs_dir) => f(PathAccess::read(abs_dir.as_bstr())),
+                    None => Ok(()),
+                });
+                if let Some(denied) = denied {
+                    return denied;
+                }
+            }
+            let (ret, new_cwd) = cwd::change(chdir.take().unwrap());
+            let errno = Errno::last_raw();
+            let outcome = AccessOutcome::from_last_os_error(ret.is_failure());
+            client
+                .try_handle_cwd_change(abs_dir.as_deref(), new_cwd.as_deref(), reads_dir, outcome)
+                .unwrap();
+            Errno::set_raw(errno);
+            ret
+        })
+    });
+    handled.unwrap_or_else(|| chdir.take().unwrap()())
+}
+
+pub unsafe fn handle_symlink<R: ReturnValue>(
+    target: *const libc::c_char,
+    linkpath: impl ToAbsolutePath,
+    ret: R,
+) -> R {
+    handle_ret(ret, |client, outcome| {
+        unsafe { client.try_handle_symlink(target, linkpath, outcome) }.unwrap();
+    })
+}
+
+pub unsafe fn handle_paths<R: ReturnValue>(
+    path: impl ToAbsolutePath,
+    dest: impl ToAbsolutePath,
+    mode: impl ToAccessMode,
+    ret: R,
+) -> R {
+    handle_ret(ret, |client, outcome| {
+        unsafe { client.try_handle_paths(path, dest, mode, outcome) }.unwrap();
+    })
+}
+
+/// Records a call that swaps `path` and `dest`, like `RENAME_EXCHANGE`, as an access in each direction.
+pub unsafe fn handle_exchange<P: ToAbsolutePath + Copy, R: ReturnValue>(
+    path: P,
+    dest: P,
+    mode: impl ToAccessMode + Copy,
+    ret: R,
+) -> R {
+    handle_ret(ret, |client, outcome| {
+        unsafe { client.try_handle_paths(dest, path, mode, outcome) }.unwrap();
+        unsafe { client.try_handle_paths(path, dest, mode, outcome) }.unwrap();
+    })
 }
 
 #[cfg(not(test))]
@@ -305,9 +794,17 @@ fn init_client() {
             let mut shm_cursor = shm_cursor.borrow_mut();
             shm_cursor.position = shm_cursor.mmap_mut.len();
         }
+        // The forked child is reported lazily, on its first record.
+        client.pid.store(getpid().as_raw() as u32, Ordering::Relaxed);
+        client.ppid.store(getppid().as_raw() as u32, Ordering::Relaxed);
+        client.process_reported.store(false, Ordering::Relaxed);
+        cwd::reset_atfork();
     }
     let ret = unsafe { pthread_atfork(None, None, Some(reset_shm_atfork)) };
     if ret != 0 {
         panic!("pthread_atfork failed: {}", ret);
     }
+    // Report eagerly so that processes without any access are still in the process tree.
+    // A failure here must not abort the program; the report is retried on the first record.
+    let _ = with_client(|client| client.ensure_process_reported());
 }
@@ -0,0 +1,41 @@
+use std::{io, path::PathBuf};
+
+/// Returns the executable path and the NUL-separated arguments of the current process.
+#[cfg(target_os = "linux")]
+pub fn current_program_and_args() -> io::Result<(PathBuf, Vec<u8>)> {
+    let program = std::fs::read_link("/proc/self/exe")?;
+    let args = std::fs::read("/proc/self/cmdline")?;
+    Ok((program, args))
+}
+
+/// Returns the executable path and the NUL-separated arguments of the current process.
+#[cfg(target_os = "macos")]
+pub fn current_program_and_args() -> io::Result<(PathBuf, Vec<u8>)> {
+    use std::{
+        ffi::{CStr, OsStr},
+        os::unix::ffi::OsStrExt,
+    };
+
+    unsafe extern "C" {
+        unsafe fn _NSGetExecutablePath(buf: *mut libc::c_char, bufsize: *mut u32) -> libc::c_int;
+        unsafe fn _NSGetArgc() -> *mut libc::c_int;
+        unsafe fn _NSGetArgv() -> *mut *mut *mut libc::c_char;
+    }
+
+    let mut program_buf = vec![0u8; libc::PATH_MAX as usize];
+    let mut program_buf_size = program_buf.len() as u32;
+    if unsafe { _NSGetExecutablePath(program_buf.as_mut_ptr().cast(), &mut program_buf_size) } != 0
+    {
+        return Err(io::Error::other("_NSGetExecutablePath: buffer too small"));
+    }
+    let program = unsafe { CStr::from_ptr(program_buf.as_ptr().cast()) };
+    let program = PathBuf::from(OsStr::from_bytes(program.to_bytes()));
+
+    let mut args = Vec::<u8>::new();
+    let (argc, argv) = unsafe { (*_NSGetArgc(), *_NSGetArgv()) };
+    for i in 0..usize::try_from(argc).unwrap_or(0) {
+        let arg = unsafe { CStr::from_ptr(*argv.add(i)) };
+        args.extend_from_slice(arg.to_bytes_with_nul());
+    }
+    Ok((program, args))
+}
@@ -0,0 +1,42 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_open, handle_open,
+    },
+    macros::intercept,
+};
+
+intercept!(access: unsafe extern "C" fn(path: *const c_char, mode: c_int) -> c_int);
+unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
+    if let Some(denied) = unsafe { enforce_open(path, AccessMode::Read) } {
+        return denied;
+    }
+    let ret = unsafe { access::original()(path, mode) };
+    unsafe { handle_open(path, AccessMode::Read, ret) }
+}
+
+intercept!(faccessat: unsafe extern "C" fn(dirfd: c_int, path: *const c_char, mode: c_int, flags: c_int) -> c_int);
+unsafe extern "C" fn faccessat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: c_int,
+    flags: c_int,
+) -> c_int {
+    let denied = if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+        unsafe { enforce_open(PathAt(dirfd, path), NoFollow(AccessMode::Read)) }
+    } else {
+        unsafe { enforce_open(PathAt(dirfd, path), AccessMode::Read) }
+    };
+    if let Some(denied) = denied {
+        return denied;
+    }
+    let ret = unsafe { faccessat::original()(dirfd, path, mode, flags) };
+    if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+        unsafe { handle_open(PathAt(dirfd, path), NoFollow(AccessMode::Read), ret) }
+    } else {
+        unsafe { handle_open(PathAt(dirfd, path), AccessMode::Read, ret) }
+    }
+}
@@ -0,0 +1,16 @@
+use libc::{c_char, c_int};
+
+use crate::{
+    client::{convert::Fd, handle_cwd_change},
+    macros::intercept,
+};
+
+intercept!(chdir: unsafe extern "C" fn(path: *const c_char) -> c_int);
+unsafe extern "C" fn chdir(path: *const c_char) -> c_int {
+    unsafe { handle_cwd_change(path, true, || chdir::original()(path)) }
+}
+
+intercept!(fchdir: unsafe extern "C" fn(fd: c_int) -> c_int);
+unsafe extern "C" fn fchdir(fd: c_int) -> c_int {
+    unsafe { handle_cwd_change(Fd(fd), false, || fchdir::original()(fd)) }
+}
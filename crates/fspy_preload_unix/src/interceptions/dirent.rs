@@ -2,7 +2,7 @@ use fspy_shared::ipc::AccessMode;
 use libc::{DIR, c_char, c_int, c_long, c_void};
 
 use crate::{
-    client::{convert::Fd, handle_open},
+    client::{convert::Fd, enforce_open, handle_open},
     macros::intercept,
 };
 
@@ -18,8 +18,11 @@ unsafe extern "C" fn scandir(
     select: *const c_void,
     compar: *const c_void,
 ) -> c_int {
-    unsafe { handle_open(dirname, AccessMode::ReadDir) }
-    unsafe { scandir::original()(dirname, namelist, select, compar) }
+    if let Some(denied) = unsafe { enforce_open(dirname, AccessMode::ReadDir) } {
+        return denied;
+    }
+    let ret = unsafe { scandir::original()(dirname, namelist, select, compar) };
+    unsafe { handle_open(dirname, AccessMode::ReadDir, ret) }
 }
 
 #[cfg(target_os = "macos")]
@@ -37,8 +40,11 @@ mod macos_only {
         select: *const c_void,
         compar: *const c_void,
     ) -> c_int {
-        unsafe { handle_open(dirname, AccessMode::ReadDir) };
-        unsafe { scandir_b::original()(dirname, namelist, select, compar) }
+        if let Some(denied) = unsafe { enforce_open(dirname, AccessMode::ReadDir) } {
+            return denied;
+        }
+        let ret = unsafe { scandir_b::original()(dirname, namelist, select, compar) };
+        unsafe { handle_open(dirname, AccessMode::ReadDir, ret) }
     }
 }
 
@@ -49,18 +55,21 @@ unsafe extern "C" fn getdirentries(
     nbytes: c_int,
     basep: *mut c_long,
 ) -> c_int {
-    unsafe { handle_open(Fd(fd), AccessMode::ReadDir) };
-    unsafe { getdirentries::original()(fd, buf, nbytes, basep) }
+    let ret = unsafe { getdirentries::original()(fd, buf, nbytes, basep) };
+    unsafe { handle_open(Fd(fd), AccessMode::ReadDir, ret) }
 }
 
 intercept!(fdopendir(64): unsafe extern "C" fn (fd: c_int) -> *mut DIR);
 unsafe extern "C" fn fdopendir(fd: c_int) -> *mut DIR {
-    unsafe { handle_open(Fd(fd), AccessMode::ReadDir) };
-    unsafe { fdopendir::original()(fd) }
+    let ret = unsafe { fdopendir::original()(fd) };
+    unsafe { handle_open(Fd(fd), AccessMode::ReadDir, ret) }
 }
 
 intercept!(opendir(64): unsafe extern "C" fn (*const c_char) -> *mut DIR);
 unsafe extern "C" fn opendir(dir_name: *const c_char) -> *mut DIR {
-    unsafe { handle_open(dir_name, AccessMode::ReadDir) };
-    unsafe { opendir::original()(dir_name) }
+    if let Some(denied) = unsafe { enforce_open(dir_name, AccessMode::ReadDir) } {
+        return denied;
+    }
+    let ret = unsafe { opendir::original()(dir_name) };
+    unsafe { handle_open(dir_name, AccessMode::ReadDir, ret) }
 }
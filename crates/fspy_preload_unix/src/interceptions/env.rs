@@ -0,0 +1,19 @@
+use libc::c_char;
+
+use crate::{client::handle_env_read, macros::intercept};
+
+intercept!(getenv: unsafe extern "C" fn(name: *const c_char) -> *mut c_char);
+unsafe extern "C" fn getenv(name: *const c_char) -> *mut c_char {
+    let value = unsafe { getenv::original()(name) };
+    unsafe { handle_env_read(name, value) };
+    value
+}
+
+#[cfg(target_os = "linux")]
+intercept!(secure_getenv: unsafe extern "C" fn(name: *const c_char) -> *mut c_char);
+#[cfg(target_os = "linux")]
+unsafe extern "C" fn secure_getenv(name: *const c_char) -> *mut c_char {
+    let value = unsafe { secure_getenv::original()(name) };
+    unsafe { handle_env_read(name, value) };
+    value
+}
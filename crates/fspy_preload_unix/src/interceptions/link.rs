@@ -0,0 +1,78 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_paths, enforce_symlink, handle_paths, handle_symlink,
+    },
+    macros::intercept,
+};
+
+intercept!(link: unsafe extern "C" fn(oldpath: *const c_char, newpath: *const c_char) -> c_int);
+unsafe extern "C" fn link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    if let Some(denied) = unsafe { enforce_paths(oldpath, newpath, NoFollow(AccessMode::Link)) } {
+        return denied;
+    }
+    let ret = unsafe { link::original()(oldpath, newpath) };
+    unsafe { handle_paths(oldpath, newpath, NoFollow(AccessMode::Link), ret) }
+}
+
+intercept!(linkat: unsafe extern "C" fn(olddirfd: c_int, oldpath: *const c_char, newdirfd: c_int, newpath: *const c_char, flags: c_int) -> c_int);
+unsafe extern "C" fn linkat(
+    olddirfd: c_int,
+    oldpath: *const c_char,
+    newdirfd: c_int,
+    newpath: *const c_char,
+    flags: c_int,
+) -> c_int {
+    let denied = if flags & libc::AT_SYMLINK_FOLLOW != 0 {
+        unsafe {
+            enforce_paths(
+                PathAt(olddirfd, oldpath),
+                PathAt(newdirfd, newpath),
+                AccessMode::Link,
+            )
+        }
+    } else {
+        unsafe {
+            enforce_paths(
+                PathAt(olddirfd, oldpath),
+                PathAt(newdirfd, newpath),
+                NoFollow(AccessMode::Link),
+            )
+        }
+    };
+    if let Some(denied) = denied {
+        return denied;
+    }
+    let ret = unsafe { linkat::original()(olddirfd, oldpath, newdirfd, newpath, flags) };
+    let (oldpath, newpath) = (PathAt(olddirfd, oldpath), PathAt(newdirfd, newpath));
+    if flags & libc::AT_SYMLINK_FOLLOW != 0 {
+        unsafe { handle_paths(oldpath, newpath, AccessMode::Link, ret) }
+    } else {
+        unsafe { handle_paths(oldpath, newpath, NoFollow(AccessMode::Link), ret) }
+    }
+}
+
+intercept!(symlink: unsafe extern "C" fn(target: *const c_char, linkpath: *const c_char) -> c_int);
+unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    if let Some(denied) = unsafe { enforce_symlink(target, linkpath) } {
+        return denied;
+    }
+    let ret = unsafe { symlink::original()(target, linkpath) };
+    unsafe { handle_symlink(target, linkpath, ret) }
+}
+
+intercept!(symlinkat: unsafe extern "C" fn(target: *const c_char, newdirfd: c_int, linkpath: *const c_char) -> c_int);
+unsafe extern "C" fn symlinkat(
+    target: *const c_char,
+    newdirfd: c_int,
+    linkpath: *const c_char,
+) -> c_int {
+    if let Some(denied) = unsafe { enforce_symlink(target, PathAt(newdirfd, linkpath)) } {
+        return denied;
+    }
+    let ret = unsafe { symlinkat::original()(target, newdirfd, linkpath) };
+    unsafe { handle_symlink(target, PathAt(newdirfd, linkpath), ret) }
+}
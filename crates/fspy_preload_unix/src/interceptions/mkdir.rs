@@ -0,0 +1,30 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int, mode_t};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_open, handle_open,
+    },
+    macros::intercept,
+};
+
+intercept!(mkdir: unsafe extern "C" fn(path: *const c_char, mode: mode_t) -> c_int);
+unsafe extern "C" fn mkdir(path: *const c_char, mode: mode_t) -> c_int {
+    if let Some(denied) = unsafe { enforce_open(path, NoFollow(AccessMode::CreateDir)) } {
+        return denied;
+    }
+    let ret = unsafe { mkdir::original()(path, mode) };
+    unsafe { handle_open(path, NoFollow(AccessMode::CreateDir), ret) }
+}
+
+intercept!(mkdirat: unsafe extern "C" fn(dirfd: c_int, path: *const c_char, mode: mode_t) -> c_int);
+unsafe extern "C" fn mkdirat(dirfd: c_int, path: *const c_char, mode: mode_t) -> c_int {
+    if let Some(denied) =
+        unsafe { enforce_open(PathAt(dirfd, path), NoFollow(AccessMode::CreateDir)) }
+    {
+        return denied;
+    }
+    let ret = unsafe { mkdirat::original()(dirfd, path, mode) };
+    unsafe { handle_open(PathAt(dirfd, path), NoFollow(AccessMode::CreateDir), ret) }
+}
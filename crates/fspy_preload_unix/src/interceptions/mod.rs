@@ -2,3 +2,12 @@ mod open;
 mod spawn;
 mod dirent;
 mod stat;
+mod rename;
+mod remove;
+mod mkdir;
+mod link;
+mod readlink;
+mod access;
+mod realpath;
+mod env;
+mod chdir;
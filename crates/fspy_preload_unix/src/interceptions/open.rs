@@ -3,7 +3,7 @@ use libc::FILE;
 use crate::{
     client::{
         convert::{ModeStr, OpenFlags, PathAt},
-        handle_open,
+        enforce_open, handle_open,
     },
     libc::{c_char, c_int},
     macros::intercept,
@@ -27,13 +27,16 @@ type Mode = c_int;
 
 intercept!(open(64): unsafe extern "C" fn(*const c_char, c_int, args: ...) -> c_int);
 unsafe extern "C" fn open(path: *const c_char, flags: c_int, mut args: ...) -> c_int {
-    unsafe { handle_open(path, OpenFlags(flags)) };
-    if has_mode_arg(flags) {
+    if let Some(denied) = unsafe { enforce_open(path, OpenFlags(flags)) } {
+        return denied;
+    }
+    let ret = if has_mode_arg(flags) {
         let mode: Mode = unsafe { args.arg() };
         unsafe { open::original()(path, flags, mode) }
     } else {
         unsafe { open::original()(path, flags) }
-    }
+    };
+    unsafe { handle_open(path, OpenFlags(flags), ret) }
 }
 
 intercept!(openat(64): unsafe extern "C" fn(c_int, *const c_char, c_int, ...) -> c_int);
@@ -43,21 +46,26 @@ unsafe extern "C" fn openat(
     flags: c_int,
     mut args: ...
 ) -> c_int {
-    unsafe { handle_open(PathAt(dirfd, path), OpenFlags(flags)) };
-
-    if has_mode_arg(flags) {
+    if let Some(denied) = unsafe { enforce_open(PathAt(dirfd, path), OpenFlags(flags)) } {
+        return denied;
+    }
+    let ret = if has_mode_arg(flags) {
         // https://github.com/tailhook/openat/issues/21#issuecomment-535914957
         let mode: Mode = unsafe { args.arg() };
         unsafe { openat::original()(dirfd, path, flags, mode) }
     } else {
         unsafe { openat::original()(dirfd, path, flags) }
-    }
+    };
+    unsafe { handle_open(PathAt(dirfd, path), OpenFlags(flags), ret) }
 }
 
 intercept!(fopen(64): unsafe extern "C" fn(path: *const c_char, mode: *const c_char) -> *mut FILE);
 unsafe extern "C" fn fopen(path: *const c_char, mode: *const c_char) -> *mut libc::FILE {
-    unsafe { handle_open(path, ModeStr(mode)) };
-    unsafe { fopen::original()(path, mode) }
+    if let Some(denied) = unsafe { enforce_open(path, ModeStr(mode)) } {
+        return denied;
+    }
+    let ret = unsafe { fopen::original()(path, mode) };
+    unsafe { handle_open(path, ModeStr(mode), ret) }
 }
 
 intercept!(freopen(64): unsafe extern "C" fn(path: *const c_char, mode: *const c_char, stream: *mut FILE) -> *mut FILE);
@@ -66,6 +74,9 @@ unsafe extern "C" fn freopen(
     mode: *const c_char,
     stream: *mut FILE,
 ) -> *mut FILE {
-    unsafe { handle_open(path, ModeStr(mode)) };
-    unsafe { freopen::original()(path, mode, stream) }
+    if let Some(denied) = unsafe { enforce_open(path, ModeStr(mode)) } {
+        return denied;
+    }
+    let ret = unsafe { freopen::original()(path, mode, stream) };
+    unsafe { handle_open(path, ModeStr(mode), ret) }
 }
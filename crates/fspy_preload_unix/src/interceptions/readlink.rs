@@ -0,0 +1,33 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int, size_t, ssize_t};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_open, handle_open,
+    },
+    macros::intercept,
+};
+
+intercept!(readlink: unsafe extern "C" fn(path: *const c_char, buf: *mut c_char, bufsiz: size_t) -> ssize_t);
+unsafe extern "C" fn readlink(path: *const c_char, buf: *mut c_char, bufsiz: size_t) -> ssize_t {
+    if let Some(denied) = unsafe { enforce_open(path, NoFollow(AccessMode::Read)) } {
+        return denied;
+    }
+    let ret = unsafe { readlink::original()(path, buf, bufsiz) };
+    unsafe { handle_open(path, NoFollow(AccessMode::Read), ret) }
+}
+
+intercept!(readlinkat: unsafe extern "C" fn(dirfd: c_int, path: *const c_char, buf: *mut c_char, bufsiz: size_t) -> ssize_t);
+unsafe extern "C" fn readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: size_t,
+) -> ssize_t {
+    if let Some(denied) = unsafe { enforce_open(PathAt(dirfd, path), NoFollow(AccessMode::Read)) } {
+        return denied;
+    }
+    let ret = unsafe { readlinkat::original()(dirfd, path, buf, bufsiz) };
+    unsafe { handle_open(PathAt(dirfd, path), NoFollow(AccessMode::Read), ret) }
+}
@@ -0,0 +1,16 @@
+use fspy_shared::ipc::AccessMode;
+use libc::c_char;
+
+use crate::{
+    client::{enforce_open, handle_open},
+    macros::intercept,
+};
+
+intercept!(realpath: unsafe extern "C" fn(path: *const c_char, resolved_path: *mut c_char) -> *mut c_char);
+unsafe extern "C" fn realpath(path: *const c_char, resolved_path: *mut c_char) -> *mut c_char {
+    if let Some(denied) = unsafe { enforce_open(path, AccessMode::Read) } {
+        return denied;
+    }
+    let ret = unsafe { realpath::original()(path, resolved_path) };
+    unsafe { handle_open(path, AccessMode::Read, ret) }
+}
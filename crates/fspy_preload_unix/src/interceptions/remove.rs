@@ -0,0 +1,38 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_open, handle_open,
+    },
+    macros::intercept,
+};
+
+intercept!(unlink: unsafe extern "C" fn(path: *const c_char) -> c_int);
+unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    if let Some(denied) = unsafe { enforce_open(path, NoFollow(AccessMode::Remove)) } {
+        return denied;
+    }
+    let ret = unsafe { unlink::original()(path) };
+    unsafe { handle_open(path, NoFollow(AccessMode::Remove), ret) }
+}
+
+intercept!(unlinkat: unsafe extern "C" fn(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int);
+unsafe extern "C" fn unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    if let Some(denied) = unsafe { enforce_open(PathAt(dirfd, path), NoFollow(AccessMode::Remove)) }
+    {
+        return denied;
+    }
+    let ret = unsafe { unlinkat::original()(dirfd, path, flags) };
+    unsafe { handle_open(PathAt(dirfd, path), NoFollow(AccessMode::Remove), ret) }
+}
+
+intercept!(rmdir: unsafe extern "C" fn(path: *const c_char) -> c_int);
+unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    if let Some(denied) = unsafe { enforce_open(path, NoFollow(AccessMode::Remove)) } {
+        return denied;
+    }
+    let ret = unsafe { rmdir::original()(path) };
+    unsafe { handle_open(path, NoFollow(AccessMode::Remove), ret) }
+}
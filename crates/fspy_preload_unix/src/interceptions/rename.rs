@@ -0,0 +1,94 @@
+use fspy_shared::ipc::AccessMode;
+use libc::{c_char, c_int};
+
+use crate::{
+    client::{
+        convert::{NoFollow, PathAt},
+        enforce_paths, handle_paths,
+    },
+    macros::intercept,
+};
+
+intercept!(rename: unsafe extern "C" fn(oldpath: *const c_char, newpath: *const c_char) -> c_int);
+unsafe extern "C" fn rename(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    if let Some(denied) = unsafe { enforce_paths(oldpath, newpath, NoFollow(AccessMode::Rename)) } {
+        return denied;
+    }
+    let ret = unsafe { rename::original()(oldpath, newpath) };
+    unsafe { handle_paths(oldpath, newpath, NoFollow(AccessMode::Rename), ret) }
+}
+
+intercept!(renameat: unsafe extern "C" fn(olddirfd: c_int, oldpath: *const c_char, newdirfd: c_int, newpath: *const c_char) -> c_int);
+unsafe extern "C" fn renameat(
+    olddirfd: c_int,
+    oldpath: *const c_char,
+    newdirfd: c_int,
+    newpath: *const c_char,
+) -> c_int {
+    let denied = unsafe {
+        enforce_paths(
+            PathAt(olddirfd, oldpath),
+            PathAt(newdirfd, newpath),
+            NoFollow(AccessMode::Rename),
+        )
+    };
+    if let Some(denied) = denied {
+        return denied;
+    }
+    let ret = unsafe { renameat::original()(olddirfd, oldpath, newdirfd, newpath) };
+    unsafe {
+        handle_paths(
+            PathAt(olddirfd, oldpath),
+            PathAt(newdirfd, newpath),
+            NoFollow(AccessMode::Rename),
+            ret,
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux_only {
+    use super::*;
+    use crate::client::handle_exchange;
+
+    intercept!(renameat2: unsafe extern "C" fn(olddirfd: c_int, oldpath: *const c_char, newdirfd: c_int, newpath: *const c_char, flags: libc::c_uint) -> c_int);
+    unsafe extern "C" fn renameat2(
+        olddirfd: c_int,
+        oldpath: *const c_char,
+        newdirfd: c_int,
+        newpath: *const c_char,
+        flags: libc::c_uint,
+    ) -> c_int {
+        // Both paths are written, so a swap is allowed whenever a rename in one direction is.
+        let denied = unsafe {
+            enforce_paths(
+                PathAt(olddirfd, oldpath),
+                PathAt(newdirfd, newpath),
+                NoFollow(AccessMode::Rename),
+            )
+        };
+        if let Some(denied) = denied {
+            return denied;
+        }
+        let ret = unsafe { renameat2::original()(olddirfd, oldpath, newdirfd, newpath, flags) };
+        // RENAME_EXCHANGE swaps the two paths, which is reported as a rename in each direction.
+        if flags & libc::RENAME_EXCHANGE != 0 {
+            return unsafe {
+                handle_exchange(
+                    PathAt(olddirfd, oldpath),
+                    PathAt(newdirfd, newpath),
+                    NoFollow(AccessMode::Rename),
+                    ret,
+                )
+            };
+        }
+        unsafe {
+            handle_paths(
+                PathAt(olddirfd, oldpath),
+                PathAt(newdirfd, newpath),
+                NoFollow(AccessMode::Rename),
+                ret,
+            )
+        }
+    }
+}
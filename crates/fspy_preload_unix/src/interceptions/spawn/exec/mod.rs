@@ -187,20 +187,19 @@ mod linux_only {
         flags: c_int, // TODO: conform to semantics of flags
     ) -> libc::c_int {
         let _unused = execveat::original;
+        let mut abs_path = None;
         let abs_path_result = unsafe {
             PathAt(dirfd, pathname).to_absolute_path(|path| {
-                let Some(path) = path else {
-                    return Ok(None);
-                };
-                Ok(Some(CString::new(path.deref()).unwrap()))
+                abs_path = path.map(|path| CString::new(path.deref()).unwrap());
+                nix::Result::Ok(())
             })
         };
-        let abs_path = match abs_path_result {
-            Ok(None) => {
+        let abs_path = match (abs_path_result, &abs_path) {
+            (Ok(()), None) => {
                 return unsafe { execveat::original()(dirfd, pathname, argv, envp, flags) };
             }
-            Ok(Some(path)) => path.as_ptr(),
-            Err(errno) => {
+            (Ok(()), Some(path)) => path.as_ptr(),
+            (Err(errno), _) => {
                 errno.set();
                 return -1;
             }
@@ -3,35 +3,34 @@ use libc::{c_char, c_int, stat as stat_struct};
 
 use crate::{
     client::{
-        convert::{Fd, PathAt},
-        handle_open,
+        convert::{Fd, NoFollow, PathAt},
+        enforce_open, handle_open,
     },
     macros::intercept,
 };
 
 intercept!(stat(64): unsafe extern "C" fn(path: *const c_char, buf: *mut stat_struct) -> c_int);
 unsafe extern "C" fn stat(path: *const c_char, buf: *mut stat_struct) -> c_int {
-    unsafe {
-        handle_open(path, AccessMode::Read);
+    if let Some(denied) = unsafe { enforce_open(path, AccessMode::Read) } {
+        return denied;
     }
-    unsafe { stat::original()(path, buf) }
+    let ret = unsafe { stat::original()(path, buf) };
+    unsafe { handle_open(path, AccessMode::Read, ret) }
 }
 
 intercept!(lstat(64): unsafe extern "C" fn(path: *const c_char, buf: *mut stat_struct) -> c_int);
 unsafe extern "C" fn lstat(path: *const c_char, buf: *mut stat_struct) -> c_int {
-    // TODO: add accessmode ReadNoFollow
-    unsafe {
-        handle_open(path, AccessMode::Read);
+    if let Some(denied) = unsafe { enforce_open(path, NoFollow(AccessMode::Read)) } {
+        return denied;
     }
-    unsafe { lstat::original()(path, buf) }
+    let ret = unsafe { lstat::original()(path, buf) };
+    unsafe { handle_open(path, NoFollow(AccessMode::Read), ret) }
 }
 
 intercept!(fstat(64): unsafe extern "C" fn(fd: c_int, buf: *mut stat_struct) -> c_int);
 unsafe extern "C" fn fstat(fd: c_int, buf: *mut stat_struct) -> c_int {
-    unsafe {
-        handle_open(Fd(fd), AccessMode::Read);
-    }
-    unsafe { fstat::original()(fd, buf) }
+    let ret = unsafe { fstat::original()(fd, buf) };
+    unsafe { handle_open(Fd(fd), AccessMode::Read, ret) }
 }
 
 intercept!(fstatat(64): unsafe extern "C" fn(dirfd: c_int, pathname: *const c_char, buf: *mut stat_struct, flags: c_int) -> c_int);
@@ -41,8 +40,54 @@ unsafe extern "C" fn fstatat(
     buf: *mut stat_struct,
     flags: c_int,
 ) -> c_int {
-    unsafe {
-        handle_open(PathAt(dirfd, pathname), AccessMode::Read);
+    let denied = if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+        unsafe { enforce_open(PathAt(dirfd, pathname), NoFollow(AccessMode::Read)) }
+    } else {
+        unsafe { enforce_open(PathAt(dirfd, pathname), AccessMode::Read) }
+    };
+    if let Some(denied) = denied {
+        return denied;
+    }
+    let ret = unsafe { fstatat::original()(dirfd, pathname, buf, flags) };
+    if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+        unsafe { handle_open(PathAt(dirfd, pathname), NoFollow(AccessMode::Read), ret) }
+    } else {
+        unsafe { handle_open(PathAt(dirfd, pathname), AccessMode::Read, ret) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux_only {
+    use super::*;
+
+    intercept!(statx: unsafe extern "C" fn(dirfd: c_int, pathname: *const c_char, flags: c_int, mask: libc::c_uint, statxbuf: *mut libc::statx) -> c_int);
+    unsafe extern "C" fn statx(
+        dirfd: c_int,
+        pathname: *const c_char,
+        flags: c_int,
+        mask: libc::c_uint,
+        statxbuf: *mut libc::statx,
+    ) -> c_int {
+        // A null path is `dirfd` itself, whose access isn't checked like other calls on file descriptors.
+        if !pathname.is_null() {
+            let denied = if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+                unsafe { enforce_open(PathAt(dirfd, pathname), NoFollow(AccessMode::Read)) }
+            } else {
+                unsafe { enforce_open(PathAt(dirfd, pathname), AccessMode::Read) }
+            };
+            if let Some(denied) = denied {
+                return denied;
+            }
+        }
+        let ret = unsafe { statx::original()(dirfd, pathname, flags, mask, statxbuf) };
+        // Since Linux 6.11, the path can be null with AT_EMPTY_PATH to stat `dirfd` itself.
+        if pathname.is_null() {
+            return unsafe { handle_open(Fd(dirfd), AccessMode::Read, ret) };
+        }
+        if flags & libc::AT_SYMLINK_NOFOLLOW != 0 {
+            unsafe { handle_open(PathAt(dirfd, pathname), NoFollow(AccessMode::Read), ret) }
+        } else {
+            unsafe { handle_open(PathAt(dirfd, pathname), AccessMode::Read, ret) }
+        }
     }
-    unsafe { fstatat::original()(dirfd, pathname, buf, flags) }
 }
@@ -18,4 +18,7 @@ unsafe extern "C" {
     ) -> c_int;
 
     pub unsafe fn getdirentries(fd: c_int, buf: *mut c_char, nbytes: c_int, basep: *mut c_long) -> c_int;
+
+    #[cfg(target_os = "linux")]
+    pub unsafe fn secure_getenv(name: *const c_char) -> *mut c_char;
 }
@@ -29,7 +29,7 @@ macro_rules! intercept {
             #[cfg(test)]
             #[test]
             fn symbol_64_does_not_exist() {
-               ::core::assert_eq!($crate::macros::symbol_exists(::core::stringify!($name)), false);
+               ::core::assert_eq!($crate::macros::symbol_exists(::core::concat!(::core::stringify!($name), 64)), false);
             }
         }
     }
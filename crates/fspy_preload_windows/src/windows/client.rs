@@ -1,5 +1,6 @@
 use std::{
     cell::SyncUnsafeCell,
+    os::windows::ffi::OsStrExt as _,
     ffi::{CStr, c_void},
     fs::OpenOptions,
     hint::black_box,
@@ -16,7 +17,7 @@ use std::{
 use bincode::{borrow_decode_from_slice, encode_into_std_write, encode_to_vec};
 use dashmap::DashSet;
 use fspy_shared::{
-    ipc::{BINCODE_CONFIG, PathAccess},
+    ipc::{BINCODE_CONFIG, NativeStr, PathAccess, ProcessInfo, Record},
     windows::{PAYLOAD_ID, Payload},
 };
 use ms_detours::DetourCopyPayloadToProcess;
@@ -25,10 +26,14 @@ use smallvec::SmallVec;
 use winapi::{
     shared::minwindef::{BOOL, DWORD, FALSE},
     um::{
-        fileapi::WriteFile, handleapi::DuplicateHandle, processthreadsapi::GetCurrentProcess,
+        fileapi::WriteFile,
+        handleapi::DuplicateHandle,
+        processenv::GetCommandLineW,
+        processthreadsapi::{GetCurrentProcess, GetCurrentProcessId},
         winnt::HANDLE,
     },
 };
+use widestring::U16CStr;
 use winsafe::GetLastError;
 
 use crate::stack_once::{StackOnceGuard, stack_once_token};
@@ -74,7 +79,8 @@ impl<'a> PathAccessSender<'a> {
     pub unsafe fn send(&self, access: PathAccess<'_>) {
         // TODO: send cwd as dir if the path is relative
         let mut buf = SmallVec::<u8, 256>::new();
-        encode_into_std_write(access, &mut buf, BINCODE_CONFIG).unwrap();
+        let record = Record::access(unsafe { GetCurrentProcessId() }, access);
+        encode_into_std_write(record, &mut buf, BINCODE_CONFIG).unwrap();
 
         self.messages.insert(buf);
     }
@@ -92,6 +98,20 @@ impl<'a> Client<'a> {
         }
     }
     pub fn finish(&self) {
+        let program = std::env::current_exe().unwrap();
+        let program = program.as_os_str().encode_wide().collect::<Vec<u16>>();
+        let command_line = unsafe { U16CStr::from_ptr_str(GetCommandLineW()) };
+        let record = Record::process(
+            unsafe { GetCurrentProcessId() },
+            ProcessInfo {
+                ppid: self.payload.parent_pid,
+                program: NativeStr::from_wide(&program),
+                args: NativeStr::from_wide(command_line.as_slice()),
+            },
+        );
+        let msg = encode_to_vec(record, BINCODE_CONFIG).unwrap();
+        unsafe { write_pipe_message(self.payload.pipe_handle as _, &msg) };
+
         for msg in self.messages.iter() {
             unsafe { write_pipe_message(self.payload.pipe_handle as _, &msg) };
         }
@@ -100,7 +120,8 @@ impl<'a> Client<'a> {
     pub unsafe fn send(&self, access: PathAccess<'_>) {
         // TODO: send cwd as dir if the path is relative
         let mut buf = SmallVec::<u8, 256>::new();
-        encode_into_std_write(access, &mut buf, BINCODE_CONFIG).unwrap();
+        let record = Record::access(unsafe { GetCurrentProcessId() }, access);
+        encode_into_std_write(record, &mut buf, BINCODE_CONFIG).unwrap();
 
         self.messages.insert(buf);
     }
@@ -131,6 +152,7 @@ impl<'a> Client<'a> {
         }
 
         payload.pipe_handle = handle_in_child as usize;
+        payload.parent_pid = unsafe { GetCurrentProcessId() };
 
         let payload_bytes = encode_to_vec(payload, BINCODE_CONFIG).unwrap();
         unsafe {
@@ -6,7 +6,7 @@ use crate::windows::{
 };
 
 use fspy_shared::{
-    ipc::{AccessMode, NativeStr, PathAccess},
+    ipc::{AccessMode, AccessOutcome, NativeStr, PathAccess},
 };
 use ms_detours::{DetourCreateProcessWithDllExA, DetourCreateProcessWithDllExW};
 use widestring::U16CStr;
@@ -73,6 +73,11 @@ static DETOUR_CREATE_PROCESS_W: Detour<
                         path: NativeStr::from_wide(
                             U16CStr::from_ptr_str(lp_application_name).as_slice(),
                         ),
+                        dest: None,
+                        outcome: AccessOutcome::Unknown,
+                        follow_symlinks: true,
+                        original_path: None,
+                        original_dest: None,
                     });
                 }
             }
@@ -198,6 +203,11 @@ static DETOUR_CREATE_PROCESS_A: Detour<
                         path: NativeStr::from_bytes(
                             CStr::from_ptr(lp_application_name).to_bytes(),
                         ),
+                        dest: None,
+                        outcome: AccessOutcome::Unknown,
+                        follow_symlinks: true,
+                        original_path: None,
+                        original_dest: None,
                     });
                 }
             }
@@ -1,7 +1,7 @@
 use std::{cell::Cell, ffi::CStr, ops::Deref, slice, sync::LazyLock};
 
 use arrayvec::ArrayVec;
-use fspy_shared::ipc::{AccessMode, NativeStr, PathAccess};
+use fspy_shared::ipc::{AccessMode, AccessOutcome, NativeStr, PathAccess};
 use ntapi::ntioapi::{
     FILE_INFORMATION_CLASS, NtQueryDirectoryFile, NtQueryFullAttributesFile,
     NtQueryInformationByName, PFILE_BASIC_INFORMATION, PFILE_NETWORK_OPEN_INFORMATION,
@@ -51,11 +51,21 @@ unsafe fn to_path_access<F: FnOnce(PathAccess<'_>)>(
         f(PathAccess {
             mode,
             path: NativeStr::from_wide(abs_path.to_u16_str().as_slice()),
+            dest: None,
+            outcome: AccessOutcome::Unknown,
+            follow_symlinks: true,
+            original_path: None,
+            original_dest: None,
         })
     } else {
         f(PathAccess {
             mode,
             path: NativeStr::from_wide(filename_slice),
+            dest: None,
+            outcome: AccessOutcome::Unknown,
+            follow_symlinks: true,
+            original_path: None,
+            original_dest: None,
         })
     }
 }
@@ -218,11 +228,21 @@ unsafe fn handle_open(acces_mode: impl ToAccessMode, path: impl ToAbsolutePath)
                     PathAccess {
                         mode: AccessMode::ReadDir,
                         path: NativeStr::from_wide(&path[..slash_pos]),
+                        dest: None,
+                        outcome: AccessOutcome::Unknown,
+                        follow_symlinks: true,
+                        original_path: None,
+                        original_dest: None,
                     }
                 } else {
                     PathAccess {
                         mode: acces_mode.to_access_mode(),
                         path: NativeStr::from_wide(path),
+                        dest: None,
+                        outcome: AccessOutcome::Unknown,
+                        follow_symlinks: true,
+                        original_path: None,
+                        original_dest: None,
                     }
                 };
             client.send(path_access);
@@ -1,7 +1,7 @@
 mod native_str;
 pub mod shm;
 
-use bincode::{BorrowDecode, Encode, config::Configuration};
+use bincode::{BorrowDecode, Decode, Encode, config::Configuration};
 pub use native_str::NativeStr;
 
 #[cfg(unix)]
@@ -9,19 +9,97 @@ pub use native_str::NativeString;
 
 pub const BINCODE_CONFIG: Configuration = bincode::config::standard();
 
-#[derive(Encode, BorrowDecode, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+/// New variants must be added at the end, because reports persist them by their indices.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AccessMode {
     Read,
     Write,
     ReadWrite,
     ReadDir,
+    /// The path is renamed to `PathAccess::dest` (`rename`, `renameat`, `renameat2`).
+    Rename,
+    /// The path is removed (`unlink`, `unlinkat`, `rmdir`).
+    Remove,
+    /// A directory is created at the path (`mkdir`, `mkdirat`).
+    CreateDir,
+    /// A hard link or a symlink pointing to the path is created at `PathAccess::dest`.
+    Link,
+}
+
+#[cfg(unix)]
+impl AccessMode {
+    /// The access mode of opening a file with `flags` (`O_RDONLY`, `O_WRONLY` or `O_RDWR`).
+    pub fn from_open_flags(flags: libc::c_int) -> Self {
+        match flags & libc::O_ACCMODE {
+            libc::O_RDWR => Self::ReadWrite,
+            libc::O_WRONLY => Self::Write,
+            _ => Self::Read,
+        }
+    }
+}
+
+/// The result of the call that made an access.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessOutcome {
+    /// The result is not observed. Accesses seen by the seccomp supervisor are recorded
+    /// before the syscall runs, and the Windows preload library doesn't capture results yet.
+    Unknown,
+    Succeeded,
+    /// The call failed with the raw OS error code (`errno` on Unix).
+    Failed(i32),
+}
+
+impl AccessOutcome {
+    /// Returns the outcome of a call, given whether its return value indicates failure.
+    /// The error code is taken from the last OS error, so it must be called right after the call.
+    pub fn from_last_os_error(failed: bool) -> Self {
+        if failed {
+            Self::Failed(std::io::Error::last_os_error().raw_os_error().unwrap_or(0))
+        } else {
+            Self::Succeeded
+        }
+    }
+
+    /// Whether the access failed because the path doesn't exist, or one of its ancestors is not a directory.
+    /// These failed lookups are what callers need to know to be invalidated when the path gets created.
+    pub fn is_not_found(self) -> bool {
+        let Self::Failed(code) = self else {
+            return false;
+        };
+        matches!(
+            std::io::Error::from_raw_os_error(code).kind(),
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+        )
+    }
+}
+
+#[cfg(unix)]
+impl<T> From<&nix::Result<T>> for AccessOutcome {
+    fn from(result: &nix::Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Succeeded,
+            Err(errno) => Self::Failed(*errno as i32),
+        }
+    }
 }
 
 #[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
 pub struct PathAccess<'a> {
     pub mode: AccessMode,
     pub path: NativeStr<'a>,
-    // TODO: add follow_symlinks (O_NOFOLLOW)
+    /// The destination path of `AccessMode::Rename` and `AccessMode::Link`. Always `None` for other modes.
+    pub dest: Option<NativeStr<'a>>,
+    pub outcome: AccessOutcome,
+    /// Whether a symlink at the path is followed. It's false for calls on the symlink itself,
+    /// like `lstat`, `readlink`, `unlink`, `rename` and opening with `O_NOFOLLOW`.
+    /// Symlinks in the parent directories of the path are always followed.
+    pub follow_symlinks: bool,
+    /// The path as the traced process spelled it, if path normalization changed it and is set to keep it.
+    pub original_path: Option<NativeStr<'a>>,
+    /// The destination path as the traced process spelled it, like `original_path`.
+    pub original_dest: Option<NativeStr<'a>>,
 }
 
 impl<'a> PathAccess<'a> {
@@ -29,12 +107,127 @@ impl<'a> PathAccess<'a> {
         Self {
             mode: AccessMode::Read,
             path: path.into(),
+            dest: None,
+            outcome: AccessOutcome::Unknown,
+            follow_symlinks: true,
+            original_path: None,
+            original_dest: None,
         }
     }
     pub fn read_dir(path: impl Into<NativeStr<'a>>) -> Self {
         Self {
             mode: AccessMode::ReadDir,
             path: path.into(),
+            dest: None,
+            outcome: AccessOutcome::Unknown,
+            follow_symlinks: true,
+            original_path: None,
+            original_dest: None,
+        }
+    }
+    pub fn with_dest(
+        mode: AccessMode,
+        path: impl Into<NativeStr<'a>>,
+        dest: impl Into<NativeStr<'a>>,
+    ) -> Self {
+        Self {
+            mode,
+            path: path.into(),
+            dest: Some(dest.into()),
+            outcome: AccessOutcome::Unknown,
+            follow_symlinks: true,
+            original_path: None,
+            original_dest: None,
+        }
+    }
+    pub fn with_outcome(self, outcome: AccessOutcome) -> Self {
+        Self { outcome, ..self }
+    }
+    pub fn with_follow_symlinks(self, follow_symlinks: bool) -> Self {
+        Self {
+            follow_symlinks,
+            ..self
+        }
+    }
+}
+
+/// The process that a `Record` is from. It's reported when the process starts a program, and when it's forked.
+#[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
+pub struct ProcessInfo<'a> {
+    pub ppid: u32,
+    /// The path of the executable that the process is running.
+    pub program: NativeStr<'a>,
+    /// The arguments including argv[0], each followed by a NUL byte (the format of `/proc/<pid>/cmdline`).
+    /// On Windows, it's the command line.
+    pub args: NativeStr<'a>,
+}
+
+/// A read of an environment variable, by `getenv` or `secure_getenv`.
+/// Only the first read of each variable in a process is reported.
+#[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
+pub struct EnvRead<'a> {
+    pub name: NativeStr<'a>,
+    pub is_set: bool,
+    /// The value that was read. It's `None` unless the recording of values is enabled, as they may be secrets.
+    pub value: Option<NativeStr<'a>>,
+}
+
+/// A change of the working directory of a process, by `chdir` or `fchdir`.
+/// Relative paths in the accesses that follow it are resolved against the new one.
+#[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
+pub struct CwdChange<'a> {
+    /// The new working directory. It's the one `getcwd` returns after the change when it's known,
+    /// otherwise the path passed to `chdir`, resolved against the previous working directory.
+    pub path: NativeStr<'a>,
+    pub outcome: AccessOutcome,
+}
+
+#[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
+pub enum Event<'a> {
+    Access(PathAccess<'a>),
+    Process(ProcessInfo<'a>),
+    EnvRead(EnvRead<'a>),
+    CwdChange(CwdChange<'a>),
+    /// An access denied by the access policy. Its outcome is the error it failed with.
+    Violation(PathAccess<'a>),
+}
+
+/// What tracked processes send to the parent: an event and the pid of the process it's from.
+#[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
+pub struct Record<'a> {
+    pub pid: u32,
+    pub event: Event<'a>,
+}
+
+impl<'a> Record<'a> {
+    pub fn access(pid: u32, path_access: PathAccess<'a>) -> Self {
+        Self {
+            pid,
+            event: Event::Access(path_access),
+        }
+    }
+    pub fn process(pid: u32, process_info: ProcessInfo<'a>) -> Self {
+        Self {
+            pid,
+            event: Event::Process(process_info),
+        }
+    }
+    pub fn env_read(pid: u32, env_read: EnvRead<'a>) -> Self {
+        Self {
+            pid,
+            event: Event::EnvRead(env_read),
+        }
+    }
+    pub fn cwd_change(pid: u32, cwd_change: CwdChange<'a>) -> Self {
+        Self {
+            pid,
+            event: Event::CwdChange(cwd_change),
+        }
+    }
+    pub fn violation(pid: u32, path_access: PathAccess<'a>) -> Self {
+        Self {
+            pid,
+            event: Event::Violation(path_access),
         }
     }
 }
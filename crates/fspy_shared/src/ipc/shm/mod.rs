@@ -1 +1,81 @@
-// TODO
+//! The format of the shared memory chunks that path accesses are written to.
+//!
+//! A chunk is a sequence of records. Each record is a flag byte followed by a bincode-encoded `Record`.
+//! The flag is set after the encoded data is written, so readers can tail a chunk while it's being written.
+//! A chunk is written by only one thread, so a record with an unset flag means there is nothing more to read yet.
+
+use std::sync::atomic::{AtomicU8, Ordering, fence};
+
+use bincode::{
+    borrow_decode_from_slice, enc::write::SizeWriter, encode_into_slice, encode_into_writer,
+    error::EncodeError,
+};
+
+use super::{BINCODE_CONFIG, Record};
+
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// The size of `record` in a chunk, including the flag byte.
+pub fn record_size(record: &Record<'_>) -> Result<usize, EncodeError> {
+    let mut size_writer = SizeWriter::default();
+    encode_into_writer(record, &mut size_writer, BINCODE_CONFIG)?;
+    Ok(1 + size_writer.bytes_written)
+}
+
+/// Writes `record` into `buf`, which should be `record_size(record)` long and zeroed.
+pub fn write_record(buf: &mut [u8], record: &Record<'_>) -> Result<(), EncodeError> {
+    let (flag_buf, data_buf) = buf.split_first_mut().expect("record buf should not be empty");
+    let written_size = encode_into_slice(record, data_buf, BINCODE_CONFIG)?;
+    debug_assert_eq!(written_size, data_buf.len());
+
+    fence(Ordering::Release);
+    unsafe { AtomicU8::from_ptr(flag_buf) }.store(1, Ordering::Release);
+    Ok(())
+}
+
+/// Reads the record at `*position` in `chunk`, and advances `*position` past it.
+///
+/// Returns `None` if the record at `*position` is not written yet.
+pub fn read_record<'a>(chunk: &'a [u8], position: &mut usize) -> Option<Record<'a>> {
+    let (flag_buf, data_buf) = chunk.get(*position..)?.split_first()?;
+    let atomic_flag = unsafe { AtomicU8::from_ptr((flag_buf as *const u8).cast_mut()) };
+    if atomic_flag.load(Ordering::Acquire) == 0 {
+        return None;
+    };
+    fence(Ordering::Acquire);
+    let (record, decoded_size) =
+        borrow_decode_from_slice::<Record<'_>, _>(data_buf, BINCODE_CONFIG).unwrap();
+
+    *position += decoded_size + 1;
+    Some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::{Event, PathAccess};
+
+    #[test]
+    fn tail_chunk() {
+        let mut chunk = vec![0u8; 64];
+        let mut read_position = 0usize;
+        assert!(read_record(&chunk, &mut read_position).is_none());
+
+        let mut write_position = 0usize;
+        for path in ["/foo", "/bar"] {
+            let record = Record::access(42, PathAccess::read(path));
+            let size = record_size(&record).unwrap();
+            write_record(&mut chunk[write_position..write_position + size], &record).unwrap();
+            write_position += size;
+
+            let read = read_record(&chunk, &mut read_position).unwrap();
+            assert_eq!(read.pid, 42);
+            let Event::Access(path_access) = read.event else {
+                panic!("expected an access, got {:?}", read.event);
+            };
+            assert_eq!(path_access.path.as_bstr(), path);
+            assert_eq!(read_position, write_position);
+            assert!(read_record(&chunk, &mut read_position).is_none());
+        }
+    }
+}
@@ -10,5 +10,7 @@ DEFINE_GUID!(PAYLOAD_ID, 0xfc4845f1, 0x3a8b, 0x4f05, 0xa3, 0xd3, 0xa5, 0xe9, 0xe
 #[derive(Encode, BorrowDecode, Debug, Clone, Copy)]
 pub struct Payload<'a> {
     pub pipe_handle: usize,
+    /// The pid of the process that created the process receiving the payload.
+    pub parent_pid: u32,
     pub asni_dll_path_with_nul: &'a [u8],
 }
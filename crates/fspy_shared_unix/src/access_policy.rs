@@ -0,0 +1,183 @@
+use bincode::{Decode, Encode};
+use fspy_shared::ipc::{AccessMode, PathAccess};
+use nix::errno::Errno;
+
+use crate::{
+    path::PathNormalization,
+    path_filter::{matches_pattern, normalize_pattern},
+};
+
+/// Which files the traced processes are allowed to access, to check that a command only uses
+/// the inputs and produces the outputs it declares.
+///
+/// Accesses outside of the allowed roots fail with `errno`, as if the files were inaccessible
+/// (`EACCES`, the default) or missing (`ENOENT`), and are recorded as violations instead of accesses.
+/// The preload library fails the intercepted call without making it, and the seccomp supervisor
+/// fails the syscall.
+///
+/// Paths are checked with their symlinks resolved, so that a symlink doesn't lead out of the roots.
+/// Calls on file descriptors are not checked, as the files were when they were opened.
+///
+/// It's meant to catch undeclared dependencies, not to contain hostile programs: a file can be
+/// replaced between the check and the access, and syscalls that dynamically linked programs make
+/// without going through libc are not intercepted.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct AccessPolicy {
+    read_roots: Vec<Vec<u8>>,
+    write_roots: Vec<Vec<u8>>,
+    errno: i32,
+}
+
+impl Default for AccessPolicy {
+    /// Denies every access with `EACCES`.
+    fn default() -> Self {
+        Self {
+            read_roots: vec![],
+            write_roots: vec![],
+            errno: Errno::EACCES as i32,
+        }
+    }
+}
+
+impl AccessPolicy {
+    /// Allows reading `root` and everything under it.
+    /// Roots are absolute paths, and can contain wildcards like the patterns of `PathFilter`.
+    pub fn allow_read(mut self, root: impl AsRef<[u8]>) -> Self {
+        self.read_roots.push(normalize_pattern(root.as_ref()));
+        self
+    }
+
+    /// Allows reading, writing, creating and removing `root` and everything under it.
+    pub fn allow_write(mut self, root: impl AsRef<[u8]>) -> Self {
+        self.write_roots.push(normalize_pattern(root.as_ref()));
+        self
+    }
+
+    /// Sets the raw error code that denied accesses fail with.
+    pub fn deny_with(mut self, errno: i32) -> Self {
+        self.errno = errno;
+        self
+    }
+
+    /// The raw error code that denied accesses fail with.
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    /// Checks `path_access`, and returns the error code to fail it with if it's denied.
+    /// `realpath` resolves symlinks, like for `PathNormalization::normalize`.
+    pub fn check(
+        &self,
+        path_access: &PathAccess<'_>,
+        realpath: impl Fn(&[u8]) -> Option<Vec<u8>>,
+    ) -> Result<(), i32> {
+        let canonicalize = PathNormalization {
+            canonicalize: true,
+            ..Default::default()
+        };
+        let path = canonicalize.normalize(
+            path_access.path.as_bstr(),
+            path_access.follow_symlinks,
+            &realpath,
+        );
+        if !self.allows(&path, writes_path(path_access.mode)) {
+            return Err(self.errno);
+        }
+        // The destination of a rename or a link is always written.
+        if let Some(dest) = path_access.dest {
+            let dest = canonicalize.normalize(dest.as_bstr(), false, &realpath);
+            if !self.allows(&dest, true) {
+                return Err(self.errno);
+            }
+        }
+        Ok(())
+    }
+
+    fn allows(&self, path: &[u8], write: bool) -> bool {
+        let matches_any = |roots: &[Vec<u8>]| roots.iter().any(|root| matches_pattern(root, path));
+        matches_any(&self.write_roots) || (!write && matches_any(&self.read_roots))
+    }
+}
+
+/// Whether an access in `mode` changes the file at its path.
+/// The path of a link is the file linked to, which is only read.
+fn writes_path(mode: AccessMode) -> bool {
+    match mode {
+        AccessMode::Read | AccessMode::ReadDir | AccessMode::Link => false,
+        AccessMode::Write
+        | AccessMode::ReadWrite
+        | AccessMode::Rename
+        | AccessMode::Remove
+        | AccessMode::CreateDir => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &AccessPolicy, path_access: PathAccess<'_>) -> Result<(), i32> {
+        // `/src/link` is a symlink to `/etc`.
+        policy.check(&path_access, |path| match path {
+            b"/src/link" => Some(b"/etc".to_vec()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn roots() {
+        let policy = AccessPolicy::default()
+            .allow_read("/src")
+            .allow_write("/out/");
+        assert_eq!(check(&policy, PathAccess::read("/src/a.c")), Ok(()));
+        assert_eq!(check(&policy, PathAccess::read("/out/a.o")), Ok(()));
+        assert_eq!(
+            check(&policy, PathAccess::read("/etc/hosts")),
+            Err(libc::EACCES)
+        );
+        let write = |path| PathAccess {
+            mode: AccessMode::Write,
+            ..PathAccess::read(path)
+        };
+        assert_eq!(check(&policy, write("/out/a.o")), Ok(()));
+        assert_eq!(check(&policy, write("/src/a.c")), Err(libc::EACCES));
+    }
+
+    #[test]
+    fn dest() {
+        let policy = AccessPolicy::default()
+            .allow_read("/src")
+            .allow_write("/out");
+        let link = |path, dest| PathAccess::with_dest(AccessMode::Link, path, dest);
+        assert_eq!(check(&policy, link("/src/a", "/out/a")), Ok(()));
+        assert_eq!(check(&policy, link("/out/a", "/src/a")), Err(libc::EACCES));
+        let rename = |path, dest| PathAccess::with_dest(AccessMode::Rename, path, dest);
+        assert_eq!(
+            check(&policy, rename("/src/a", "/out/a")),
+            Err(libc::EACCES)
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let policy = AccessPolicy::default()
+            .allow_read("/src")
+            .deny_with(libc::ENOENT);
+        assert_eq!(
+            check(&policy, PathAccess::read("/src/../etc/hosts")),
+            Err(libc::ENOENT)
+        );
+        assert_eq!(
+            check(&policy, PathAccess::read("/src/link")),
+            Err(libc::ENOENT)
+        );
+        // The symlink itself is in the root.
+        assert_eq!(
+            check(
+                &policy,
+                PathAccess::read("/src/link").with_follow_symlinks(false)
+            ),
+            Ok(())
+        );
+    }
+}
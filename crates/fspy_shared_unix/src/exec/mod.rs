@@ -3,7 +3,7 @@ mod shebang;
 mod which;
 
 use bstr::{BStr, BString, ByteSlice};
-use fspy_shared::ipc::{AccessMode, PathAccess};
+use fspy_shared::ipc::PathAccess;
 use nix::unistd::{AccessFlags, access};
 
 use std::{
@@ -111,11 +111,9 @@ impl Exec {
                 self.program.as_ref(),
                 path,
                 |path| {
-                    on_path_access(PathAccess {
-                        path: path.into(),
-                        mode: AccessMode::Read,
-                    });
-                    access(OsStr::from_bytes(path), AccessFlags::X_OK)
+                    let result = access(OsStr::from_bytes(path), AccessFlags::X_OK);
+                    on_path_access(PathAccess::read(path).with_outcome((&result).into()));
+                    result
                 },
                 |program| Ok(program.to_owned()),
             )?;
@@ -134,8 +132,9 @@ impl Exec {
     ) -> nix::Result<()> {
         if let Some(shebang) = parse_shebang(
             |path, buf| {
-                on_path_access(PathAccess::read(path));
-                peek_executable(path, buf)
+                let result = peek_executable(path, buf);
+                on_path_access(PathAccess::read(path).with_outcome((&result).into()));
+                result
             },
             Path::new(OsStr::from_bytes(&self.program)),
             options,
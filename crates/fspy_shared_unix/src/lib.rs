@@ -1,6 +1,9 @@
 #![cfg(unix)]
 
+pub mod access_policy;
 pub mod exec;
 pub mod spawn;
+pub mod path;
+pub mod path_filter;
 pub mod payload;
 pub(crate) mod open_exec;
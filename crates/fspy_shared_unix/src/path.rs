@@ -0,0 +1,278 @@
+//! Resolving the paths passed to syscalls into the absolute paths that accesses are recorded with.
+//!
+//! Both the preload library and the seccomp supervisor resolve paths with these, so that an access
+//! is recorded with the same path whichever of them sees it.
+
+use std::borrow::Cow;
+
+use bincode::{Decode, Encode};
+use fspy_shared::ipc::{NativeStr, PathAccess};
+
+pub fn is_absolute(path: &[u8]) -> bool {
+    path.first() == Some(&b'/')
+}
+
+/// Resolves `path` against `dir`, the absolute path of the directory it's relative to, unless it's absolute.
+/// An empty `path` refers to `dir` itself, like with `AT_EMPTY_PATH`.
+pub fn resolve_at<'a>(dir: &[u8], path: &'a [u8]) -> Cow<'a, [u8]> {
+    if is_absolute(path) {
+        return Cow::Borrowed(path);
+    }
+    let mut abs_path = Vec::with_capacity(dir.len() + 1 + path.len());
+    abs_path.extend_from_slice(dir);
+    if !path.is_empty() {
+        if !dir.ends_with(b"/") {
+            abs_path.push(b'/');
+        }
+        abs_path.extend_from_slice(path);
+    }
+    Cow::Owned(abs_path)
+}
+
+/// How the paths of accesses are normalized before they are filtered and recorded.
+/// By default they are recorded as the traced process spelled them, only made absolute.
+///
+/// Normalization is done in the traced processes at the time of the access, so canonicalization
+/// sees the symlinks as they were then, not after the process changed or removed them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+pub struct PathNormalization {
+    /// Removes `.` and empty components, trailing slashes, and `..` along with the component before it.
+    /// This is purely lexical, so `link/..` becomes the directory of `link` even if `link` is a symlink
+    /// to a directory elsewhere, unlike in the kernel.
+    pub lexical: bool,
+    /// Resolves all symlinks like `realpath`. A path that doesn't exist (yet), or is accessed without
+    /// following the symlink at it, has its parent directory resolved instead.
+    /// Paths are normalized lexically as well where they can't be resolved.
+    pub canonicalize: bool,
+    /// Keeps the paths as spelled by the traced process in `PathAccess::original_path` and
+    /// `PathAccess::original_dest`, when normalization changed them.
+    pub keep_original: bool,
+}
+
+impl PathNormalization {
+    pub fn is_enabled(&self) -> bool {
+        self.lexical || self.canonicalize
+    }
+
+    /// Normalizes the absolute `path`, a symlink at which is followed if `follow_symlinks` is set.
+    /// `realpath` returns the path of an existing file with all symlinks resolved, or `None` if it can't.
+    pub fn normalize<'a>(
+        &self,
+        path: &'a [u8],
+        follow_symlinks: bool,
+        realpath: impl Fn(&[u8]) -> Option<Vec<u8>>,
+    ) -> Cow<'a, [u8]> {
+        if !is_absolute(path) {
+            return Cow::Borrowed(path);
+        }
+        if self.canonicalize {
+            if let Some(canonical_path) = canonicalize(path, follow_symlinks, realpath) {
+                return Cow::Owned(canonical_path);
+            }
+        } else if !self.lexical {
+            return Cow::Borrowed(path);
+        }
+        normalize_lexically(path)
+    }
+
+    /// Calls `f` with `path_access` with its paths normalized. See `normalize` for `realpath`.
+    pub fn normalize_access<'a, R>(
+        &self,
+        path_access: PathAccess<'a>,
+        realpath: impl Fn(&[u8]) -> Option<Vec<u8>>,
+        f: impl FnOnce(PathAccess<'_>) -> R,
+    ) -> R {
+        if !self.is_enabled() {
+            return f(path_access);
+        }
+        let path = self.normalize(
+            path_access.path.as_bstr(),
+            path_access.follow_symlinks,
+            &realpath,
+        );
+        // The destination of a rename or a link is replaced or created, so a symlink at it is not followed.
+        let dest = path_access
+            .dest
+            .map(|dest| self.normalize(dest.as_bstr(), false, &realpath));
+        let original = |original: NativeStr<'a>, normalized: &[u8]| {
+            (self.keep_original && *original.as_bstr() != *normalized).then_some(original)
+        };
+        f(PathAccess {
+            path: NativeStr::from_bytes(&path),
+            dest: dest.as_deref().map(NativeStr::from_bytes),
+            original_path: path_access
+                .original_path
+                .or_else(|| original(path_access.path, &path)),
+            original_dest: path_access
+                .original_dest
+                .or_else(|| original(path_access.dest?, dest.as_deref()?)),
+            ..path_access
+        })
+    }
+}
+
+/// Resolves the symlinks in the absolute `path` with `realpath`, or only the ones in its parent directory
+/// if the symlink at `path` is not followed or `path` doesn't exist. Returns `None` if neither can be resolved.
+fn canonicalize(
+    path: &[u8],
+    follow_symlinks: bool,
+    realpath: impl Fn(&[u8]) -> Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let trimmed = path.strip_suffix(b"/").unwrap_or(path);
+    let (parent, name) = match trimmed.iter().rposition(|ch| *ch == b'/') {
+        Some(slash) => (&trimmed[..slash.max(1)], &trimmed[slash + 1..]),
+        None => (&b"/"[..], trimmed),
+    };
+    // `.` and `..` are directories, never symlinks, so the whole path is resolved either way.
+    if follow_symlinks || matches!(name, b"" | b"." | b"..") {
+        if let Some(canonical_path) = realpath(path) {
+            return Some(canonical_path);
+        }
+        if matches!(name, b"" | b"." | b"..") {
+            return None;
+        }
+    }
+    let canonical_parent = realpath(parent)?;
+    Some(resolve_at(&canonical_parent, name).into_owned())
+}
+
+/// Normalizes the absolute `path` lexically, see `PathNormalization::lexical`.
+pub fn normalize_lexically(path: &[u8]) -> Cow<'_, [u8]> {
+    let is_normal = path == b"/"
+        || path
+            .split(|ch| *ch == b'/')
+            .skip(1)
+            .all(|component| !matches!(component, b"" | b"." | b".."));
+    if is_normal || !is_absolute(path) {
+        return Cow::Borrowed(path);
+    }
+    let mut normalized = Vec::with_capacity(path.len());
+    for component in path.split(|ch| *ch == b'/') {
+        match component {
+            b"" | b"." => {}
+            b".." => {
+                let parent_len = normalized.iter().rposition(|ch| *ch == b'/').unwrap_or(0);
+                normalized.truncate(parent_len);
+            }
+            _ => {
+                normalized.push(b'/');
+                normalized.extend_from_slice(component);
+            }
+        }
+    }
+    if normalized.is_empty() {
+        normalized.push(b'/');
+    }
+    Cow::Owned(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use fspy_shared::ipc::AccessMode;
+
+    use super::*;
+
+    fn resolve(dir: &str, path: &str) -> String {
+        String::from_utf8(resolve_at(dir.as_bytes(), path.as_bytes()).into_owned()).unwrap()
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(resolve("/home/user", "a/b"), "/home/user/a/b");
+        assert_eq!(resolve("/home/user/", "a"), "/home/user/a");
+        assert_eq!(resolve("/", "a"), "/a");
+        assert_eq!(resolve("/home/user", ""), "/home/user");
+    }
+
+    #[test]
+    fn absolute() {
+        assert_eq!(resolve("/home/user", "/etc/hosts"), "/etc/hosts");
+        assert!(matches!(resolve_at(b"/", b"/etc"), Cow::Borrowed(_)));
+    }
+
+    fn normalize(normalization: PathNormalization, path: &str, follow_symlinks: bool) -> String {
+        // `/link` is a symlink to `/target`, which is the only other existing file.
+        let realpath = |path: &[u8]| match path {
+            b"/" | b"/target" => Some(path.to_vec()),
+            b"/link" | b"/link/" | b"/target/." => Some(b"/target".to_vec()),
+            _ => None,
+        };
+        let normalized = normalization.normalize(path.as_bytes(), follow_symlinks, realpath);
+        String::from_utf8(normalized.into_owned()).unwrap()
+    }
+
+    #[test]
+    fn lexical() {
+        let lexical = PathNormalization {
+            lexical: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize(lexical, "/a/./b//c/", true), "/a/b/c");
+        assert_eq!(
+            normalize(lexical, "/app/../.oxlintrc.json", true),
+            "/.oxlintrc.json"
+        );
+        assert_eq!(normalize(lexical, "/a/b/..", true), "/a");
+        assert_eq!(normalize(lexical, "/../..", true), "/");
+        assert_eq!(normalize(lexical, "/link/..", true), "/");
+        assert!(matches!(normalize_lexically(b"/a/b"), Cow::Borrowed(_)));
+        assert!(matches!(normalize_lexically(b"/"), Cow::Borrowed(_)));
+        assert_eq!(
+            normalize(PathNormalization::default(), "/a//b", true),
+            "/a//b"
+        );
+    }
+
+    #[test]
+    fn canonicalize() {
+        let canonicalize = PathNormalization {
+            canonicalize: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize(canonicalize, "/link", true), "/target");
+        assert_eq!(normalize(canonicalize, "/link/", true), "/target");
+        // The symlink itself is accessed.
+        assert_eq!(normalize(canonicalize, "/link", false), "/link");
+        // Only the parent exists.
+        assert_eq!(normalize(canonicalize, "/link/new", true), "/target/new");
+        assert_eq!(
+            normalize(canonicalize, "/target/./new", false),
+            "/target/new"
+        );
+        // Neither exists.
+        assert_eq!(normalize(canonicalize, "/missing/../a//b", true), "/a/b");
+    }
+
+    #[test]
+    fn keep_original() {
+        let normalization = PathNormalization {
+            lexical: true,
+            keep_original: true,
+            ..Default::default()
+        };
+        let access = PathAccess::with_dest(AccessMode::Rename, "/a/../b", "/c");
+        normalization.normalize_access(
+            access,
+            |_| None,
+            |access| {
+                assert_eq!(access.path.as_bstr(), "/b");
+                assert_eq!(access.original_path.unwrap().as_bstr(), "/a/../b");
+                assert_eq!(access.dest.unwrap().as_bstr(), "/c");
+                assert!(access.original_dest.is_none());
+            },
+        );
+
+        let normalization = PathNormalization {
+            keep_original: false,
+            ..normalization
+        };
+        normalization.normalize_access(
+            PathAccess::read("/a/../b"),
+            |_| None,
+            |access| {
+                assert_eq!(access.path.as_bstr(), "/b");
+                assert!(access.original_path.is_none());
+            },
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use bincode::{Decode, Encode};
+use bstr::ByteSlice as _;
+use fspy_shared::ipc::PathAccess;
+
+/// Decides which accesses are recorded by their absolute paths.
+///
+/// A path is recorded if it matches any of the include patterns (or there are none),
+/// and none of the exclude patterns. Accesses with a destination (renames and links) are
+/// recorded if either path is.
+///
+/// A pattern matches a path and everything under it, so `/usr/lib` matches `/usr/lib/libc.so`
+/// but not `/usr/libexec`. In patterns, `*` matches any part of a path component, `?` matches
+/// a single character other than `/`, and `**` matches anything including `/`.
+/// For example, `**/node_modules/.cache` matches that directory in any project.
+///
+/// Filtering is done in the traced processes before the accesses are sent, so excluded
+/// accesses cost nearly nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct PathFilter {
+    include: Vec<Vec<u8>>,
+    include_literal: Vec<Vec<u8>>,
+    exclude: Vec<Vec<u8>>,
+}
+
+impl Default for PathFilter {
+    /// Excludes device files, and on Linux the virtual filesystems `/proc` and `/sys`.
+    fn default() -> Self {
+        let filter = Self::empty().exclude("/dev");
+        if cfg!(target_os = "linux") {
+            filter.exclude("/proc").exclude("/sys")
+        } else {
+            filter
+        }
+    }
+}
+
+impl PathFilter {
+    /// A filter that records every access.
+    pub fn empty() -> Self {
+        Self {
+            include: vec![],
+            include_literal: vec![],
+            exclude: vec![],
+        }
+    }
+
+    /// Only records paths matching `pattern` or other include patterns.
+    pub fn include(mut self, pattern: impl AsRef<[u8]>) -> Self {
+        self.include.push(normalize_pattern(pattern.as_ref()));
+        self
+    }
+
+    /// Only records paths under `path` or matching other include patterns.
+    /// Unlike in [`PathFilter::include`], `*` and `?` in `path` match only themselves.
+    pub fn include_literal(mut self, path: impl AsRef<[u8]>) -> Self {
+        self.include_literal.push(normalize_pattern(path.as_ref()));
+        self
+    }
+
+    /// Doesn't record paths matching `pattern`.
+    pub fn exclude(mut self, pattern: impl AsRef<[u8]>) -> Self {
+        self.exclude.push(normalize_pattern(pattern.as_ref()));
+        self
+    }
+
+    pub fn matches(&self, path: &[u8]) -> bool {
+        ((self.include.is_empty() && self.include_literal.is_empty())
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches_pattern(pattern, path))
+            || self
+                .include_literal
+                .iter()
+                .any(|literal| is_under(literal, path)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| matches_pattern(pattern, path))
+    }
+
+    /// Whether `path_access` should be recorded.
+    pub fn matches_access(&self, path_access: &PathAccess<'_>) -> bool {
+        self.matches(path_access.path.as_bstr())
+            || path_access
+                .dest
+                .is_some_and(|dest| self.matches(dest.as_bstr()))
+    }
+}
+
+/// Trailing slashes are removed so that `/dev/` matches `/dev` as well.
+pub(crate) fn normalize_pattern(pattern: &[u8]) -> Vec<u8> {
+    pattern.trim_end_with(|ch| ch == '/').to_vec()
+}
+
+/// Whether `path` is `ancestor` or under it.
+fn is_under(ancestor: &[u8], path: &[u8]) -> bool {
+    path.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"))
+}
+
+/// A wildcard at the start of a pattern: its length, and whether it matches `/` too.
+fn wildcard_at(pattern: &[u8]) -> Option<(usize, bool)> {
+    match pattern {
+        [b'*', b'*', ..] => Some((2, true)),
+        [b'*', ..] => Some((1, false)),
+        _ => None,
+    }
+}
+
+/// Whether `pattern` matches `path` or one of its ancestors.
+///
+/// The positions in `pattern` that the consumed part of `path` can end at are tracked all at once,
+/// so matching takes O(pattern × path) time even with many wildcards, unlike backtracking.
+pub(crate) fn matches_pattern(pattern: &[u8], path: &[u8]) -> bool {
+    let mut states = vec![false; pattern.len() + 1];
+    let mut next_states = vec![false; pattern.len() + 1];
+    states[0] = true;
+    for &ch in path {
+        skip_wildcards(pattern, &mut states);
+        // The whole pattern matches an ancestor of `path`.
+        if states[pattern.len()] && ch == b'/' {
+            return true;
+        }
+        next_states.fill(false);
+        for (position, _) in states.iter().enumerate().filter(|(_, state)| **state) {
+            let rest = &pattern[position..];
+            match (wildcard_at(rest), rest.first()) {
+                (Some((_, matches_slash)), _) => {
+                    if matches_slash || ch != b'/' {
+                        next_states[position] = true;
+                    }
+                }
+                (None, Some(b'?')) => {
+                    if ch != b'/' {
+                        next_states[position + 1] = true;
+                    }
+                }
+                (None, Some(pattern_ch)) => {
+                    if *pattern_ch == ch {
+                        next_states[position + 1] = true;
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        if !next_states.contains(&true) {
+            return false;
+        }
+        std::mem::swap(&mut states, &mut next_states);
+    }
+    skip_wildcards(pattern, &mut states);
+    states[pattern.len()]
+}
+
+/// Adds the positions after the wildcards at `states`, as wildcards match empty strings as well.
+fn skip_wildcards(pattern: &[u8], states: &mut [bool]) {
+    // Skipping only moves forward, so a single pass covers consecutive wildcards.
+    for position in 0..pattern.len() {
+        if states[position]
+            && let Some((len, _)) = wildcard_at(&pattern[position..])
+        {
+            states[position + len] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        matches_pattern(&normalize_pattern(pattern.as_bytes()), path.as_bytes())
+    }
+
+    #[test]
+    fn prefix() {
+        assert!(matches("/usr/lib", "/usr/lib"));
+        assert!(matches("/usr/lib", "/usr/lib/libc.so"));
+        assert!(matches("/usr/lib/", "/usr/lib"));
+        assert!(!matches("/usr/lib", "/usr/libexec"));
+        assert!(!matches("/usr/lib", "/usr"));
+        assert!(matches("/", "/etc"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("/home/*/.cache", "/home/user/.cache/x"));
+        assert!(!matches("/home/*/.cache", "/home/a/b/.cache"));
+        assert!(matches("/tmp/*.log", "/tmp/a.log"));
+        assert!(!matches("/tmp/*.log", "/tmp/a.logs"));
+        assert!(matches("/tmp/?", "/tmp/a"));
+        assert!(!matches("/tmp/?", "/tmp/ab"));
+        assert!(matches(
+            "**/node_modules/.cache",
+            "/p/node_modules/.cache/a"
+        ));
+        assert!(matches("**/node_modules/.cache", "/node_modules/.cache"));
+        assert!(!matches(
+            "**/node_modules/.cache",
+            "/p/node_modules/.cached"
+        ));
+        assert!(matches("/a/*", "/a/"));
+        assert!(matches("/a/**/b", "/a/x/y/b"));
+        assert!(!matches("/a/*/b", "/a/x/y/b"));
+    }
+
+    #[test]
+    fn many_wildcards() {
+        // Backtracking would take exponential time.
+        let path = format!("{}/y", "/a".repeat(200));
+        assert!(!matches(&format!("{}x", "**/".repeat(30)), &path));
+        assert!(matches(&format!("{}y", "**/".repeat(30)), &path));
+        assert!(!matches(
+            &format!("/{}x", "*a".repeat(30)),
+            &format!("/{}", "a".repeat(200))
+        ));
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = PathFilter::empty().include("/src").exclude("/src/gen");
+        assert!(filter.matches(b"/src/main.rs"));
+        assert!(!filter.matches(b"/src/gen/a.rs"));
+        assert!(!filter.matches(b"/etc/hosts"));
+
+        let filter = PathFilter::empty().include_literal("/src/*");
+        assert!(filter.matches(b"/src/*/main.rs"));
+        assert!(!filter.matches(b"/src/a/main.rs"));
+        assert!(!filter.matches(b"/src/*a"));
+
+        let filter = PathFilter::default();
+        assert!(!filter.matches(b"/dev/null"));
+        assert!(filter.matches(b"/devices"));
+    }
+}
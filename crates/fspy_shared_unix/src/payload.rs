@@ -3,6 +3,8 @@ use bincode::{Decode, Encode, config::standard};
 use bstr::BString;
 use fspy_shared::ipc::NativeString;
 
+use crate::{access_policy::AccessPolicy, path::PathNormalization, path_filter::PathFilter};
+
 use std::{
     os::{
         fd::RawFd,
@@ -14,6 +16,14 @@ use std::{
 pub struct Payload {
     pub ipc_fd: RawFd,
     pub preload_path: NativeString,
+    /// Which accesses the preload library and the seccomp supervisor record.
+    pub path_filter: PathFilter,
+    /// How the paths are normalized, before they are filtered.
+    pub path_normalization: PathNormalization,
+    /// Which accesses are allowed, if they are enforced.
+    pub access_policy: Option<AccessPolicy>,
+    /// Whether the values of environment variables are recorded along with their names.
+    pub record_env_values: bool,
 
     #[cfg(target_os = "macos")]
     pub fixtures: Fixtures,
@@ -12,7 +12,7 @@ mod os_specific;
 pub use os_specific::COREUTILS_FUNCTIONS as COREUTILS_FUNCTIONS_FOR_TEST;
 
 use bstr::ByteSlice;
-use fspy_shared::ipc::{AccessMode, PathAccess};
+use fspy_shared::ipc::PathAccess;
 
 use crate::exec::ExecResolveConfig;
 
@@ -34,16 +34,13 @@ pub fn handle_exec(
                 std::path::absolute(path_access.path.as_os_str()).expect("Failed to get cwd");
             on_path_access(PathAccess {
                 path: path.as_path().into(),
-                mode: path_access.mode,
+                ..path_access
             });
         }
     };
 
     command.resolve(&mut on_path_access, config)?;
-    on_path_access(PathAccess {
-        mode: AccessMode::Read,
-        path: command.program.as_bstr().into(),
-    });
+    on_path_access(PathAccess::read(command.program.as_bstr()));
 
     os_specific::handle_exec(command, encoded_payload)
 }
@@ -0,0 +1,169 @@
+/// A syscall ABI, identified by `seccomp_data.arch` and, for x32, by `__X32_SYSCALL_BIT` in
+/// `seccomp_data.nr`.
+///
+/// Besides the native one, the ABIs that the kernel can run on the same host are filtered too, so
+/// that a target can't bypass the filter by running a 32-bit binary, which has its own syscall numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arch {
+    X86_64,
+    /// The ILP32 ABI of x86_64, which uses its syscall numbers with `__X32_SYSCALL_BIT` set.
+    X32,
+    /// i386, which x86_64 runs with `CONFIG_IA32_EMULATION`.
+    X86,
+    Aarch64,
+}
+
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+const AUDIT_ARCH_I386: u32 = 0x4000_0003;
+const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+impl Arch {
+    #[cfg(target_arch = "x86_64")]
+    pub const NATIVE: Self = Self::X86_64;
+    #[cfg(target_arch = "aarch64")]
+    pub const NATIVE: Self = Self::Aarch64;
+
+    /// All the ABIs, which the seccomp filter tells apart from the ones it doesn't know.
+    pub const ALL: [Self; 4] = [Self::X86_64, Self::X32, Self::X86, Self::Aarch64];
+
+    /// Identifies the ABI of a syscall from `seccomp_data`.
+    pub fn from_seccomp_data(data: &libc::seccomp_data) -> Option<Self> {
+        match data.arch {
+            AUDIT_ARCH_X86_64 if data.nr as u32 & X32_SYSCALL_BIT != 0 => Some(Self::X32),
+            AUDIT_ARCH_X86_64 => Some(Self::X86_64),
+            AUDIT_ARCH_I386 => Some(Self::X86),
+            AUDIT_ARCH_AARCH64 => Some(Self::Aarch64),
+            _ => None,
+        }
+    }
+
+    /// The `AUDIT_ARCH_*` value of `seccomp_data.arch` for the ABI.
+    pub fn audit_arch(self) -> u32 {
+        match self {
+            Self::X86_64 | Self::X32 => AUDIT_ARCH_X86_64,
+            Self::X86 => AUDIT_ARCH_I386,
+            Self::Aarch64 => AUDIT_ARCH_AARCH64,
+        }
+    }
+
+    /// The name of the syscall numbered `nr` in the ABI.
+    /// It's `None` for x32, and for the ABIs whose syscall tables aren't built for this host.
+    pub fn syscall_name(self, nr: u32) -> Option<&'static str> {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Self::X86_64 => syscalls::x86_64::Sysno::new(nr as usize).map(|sysno| sysno.name()),
+            Self::X86 => syscalls::x86::Sysno::new(nr as usize).map(|sysno| sysno.name()),
+            #[cfg(target_arch = "aarch64")]
+            Self::Aarch64 => syscalls::aarch64::Sysno::new(nr as usize).map(|sysno| sysno.name()),
+            _ => None,
+        }
+    }
+
+    /// The number of the syscall named `name` in the ABI, the reverse of `syscall_name`.
+    pub fn syscall_nr(self, name: &str) -> Option<u32> {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Self::X86_64 => name
+                .parse::<syscalls::x86_64::Sysno>()
+                .ok()
+                .map(|sysno| sysno as u32),
+            Self::X86 => name
+                .parse::<syscalls::x86::Sysno>()
+                .ok()
+                .map(|sysno| sysno as u32),
+            #[cfg(target_arch = "aarch64")]
+            Self::Aarch64 => name
+                .parse::<syscalls::aarch64::Sysno>()
+                .ok()
+                .map(|sysno| sysno as u32),
+            _ => None,
+        }
+    }
+
+    /// The number in the ABI of the native syscall `native_nr`, if the ABI has a syscall of the same name.
+    pub fn nr_of_native(self, native_nr: u32) -> Option<u32> {
+        self.syscall_nr(Self::NATIVE.syscall_name(native_nr)?)
+    }
+
+    /// The number of the native syscall of the same name as the syscall `nr` of the ABI, the reverse of `nr_of_native`.
+    pub fn native_nr(self, nr: u32) -> Option<u32> {
+        Self::NATIVE.syscall_nr(self.syscall_name(nr)?)
+    }
+
+    /// The size of pointers and of `long` in the ABI.
+    pub fn word_size(self) -> usize {
+        match self {
+            Self::X86_64 | Self::Aarch64 => 8,
+            Self::X32 | Self::X86 => 4,
+        }
+    }
+}
+
+/// Returns the x32 syscall number of `sysno`.
+///
+/// x32 uses the x86_64 numbers, except for the syscalls that take structures whose layout differs
+/// between the ABIs, which have their own numbers from 512. The x86_64 numbers of those are not
+/// available to x32.
+#[cfg(target_arch = "x86_64")]
+pub const fn x32_nr(sysno: syscalls::x86_64::Sysno) -> u32 {
+    use syscalls::x86_64::Sysno;
+    let nr = match sysno {
+        Sysno::rt_sigaction => 512,
+        Sysno::rt_sigreturn => 513,
+        Sysno::ioctl => 514,
+        Sysno::readv => 515,
+        Sysno::writev => 516,
+        Sysno::recvfrom => 517,
+        Sysno::sendmsg => 518,
+        Sysno::recvmsg => 519,
+        Sysno::execve => 520,
+        Sysno::ptrace => 521,
+        Sysno::rt_sigpending => 522,
+        Sysno::rt_sigtimedwait => 523,
+        Sysno::rt_sigqueueinfo => 524,
+        Sysno::sigaltstack => 525,
+        Sysno::timer_create => 526,
+        Sysno::mq_notify => 527,
+        Sysno::kexec_load => 528,
+        Sysno::waitid => 529,
+        Sysno::set_robust_list => 530,
+        Sysno::get_robust_list => 531,
+        Sysno::vmsplice => 532,
+        Sysno::move_pages => 533,
+        Sysno::preadv => 534,
+        Sysno::pwritev => 535,
+        Sysno::rt_tgsigqueueinfo => 536,
+        Sysno::recvmmsg => 537,
+        Sysno::sendmmsg => 538,
+        Sysno::process_vm_readv => 539,
+        Sysno::process_vm_writev => 540,
+        Sysno::setsockopt => 541,
+        Sysno::getsockopt => 542,
+        Sysno::io_setup => 543,
+        Sysno::io_submit => 544,
+        Sysno::execveat => 545,
+        Sysno::preadv2 => 546,
+        Sysno::pwritev2 => 547,
+        other => other as u32,
+    };
+    nr | X32_SYSCALL_BIT
+}
+
+/// Expands to the syscall number of a syscall of an `Arch`, like `syscall_nr!(X86, stat64)`.
+#[macro_export]
+macro_rules! syscall_nr {
+    (X86_64, $syscall:ident) => {
+        ::syscalls::x86_64::Sysno::$syscall as u32
+    };
+    (X32, $syscall:ident) => {
+        $crate::arch::x32_nr(::syscalls::x86_64::Sysno::$syscall)
+    };
+    (X86, $syscall:ident) => {
+        ::syscalls::x86::Sysno::$syscall as u32
+    };
+    (Aarch64, $syscall:ident) => {
+        ::syscalls::aarch64::Sysno::$syscall as u32
+    };
+}
@@ -40,6 +40,63 @@ pub fn notif_recv(fd: BorrowedFd<'_>, notif_buf: &mut Alloced<seccomp_notif>) ->
     Ok(())
 }
 
+/// Opens a pidfd of the thread `tid`. Before Linux 6.9, which added `PIDFD_THREAD`, it's a pidfd
+/// of its process, which only works if `tid` is the thread group leader.
+pub fn pidfd_open(tid: u32) -> nix::Result<OwnedFd> {
+    const PIDFD_THREAD: libc::c_uint = libc::O_EXCL as _;
+    let mut ret = unsafe { syscall(libc::SYS_pidfd_open, tid, PIDFD_THREAD) };
+    if ret < 0 && nix::Error::last() == nix::Error::EINVAL {
+        ret = unsafe { syscall(libc::SYS_pidfd_open, tid, 0) };
+    }
+    if ret < 0 {
+        return Err(nix::Error::last());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(c_int::try_from(ret).unwrap()) })
+}
+
+/// Duplicates the file descriptor `fd` of the process of `pidfd` into this process.
+pub fn pidfd_getfd(pidfd: BorrowedFd<'_>, fd: c_int) -> nix::Result<OwnedFd> {
+    let ret = unsafe { syscall(libc::SYS_pidfd_getfd, pidfd.as_raw_fd(), fd, 0) };
+    if ret < 0 {
+        return Err(nix::Error::last());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(c_int::try_from(ret).unwrap()) })
+}
+
+/// Fails with `ENOENT` if the notification `id` is no longer pending.
+pub fn notif_id_valid(fd: BorrowedFd<'_>, id: u64) -> nix::Result<()> {
+    const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 1074274562;
+    let ret = unsafe { libc::ioctl(fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID, &raw const id) };
+    if ret < 0 {
+        return Err(nix::Error::last());
+    }
+    Ok(())
+}
+
+/// `struct seccomp_notif_addfd` of `linux/seccomp.h`.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SeccompNotifAddfd {
+    pub id: u64,
+    pub flags: u32,
+    pub srcfd: u32,
+    pub newfd: u32,
+    pub newfd_flags: u32,
+}
+
+pub const SECCOMP_ADDFD_FLAG_SETFD: u32 = 1;
+pub const SECCOMP_ADDFD_FLAG_SEND: u32 = 2;
+
+/// Installs `addfd.srcfd` in the target, and returns its number there.
+pub fn notif_addfd(fd: BorrowedFd<'_>, addfd: &mut SeccompNotifAddfd) -> nix::Result<c_int> {
+    const SECCOMP_IOCTL_NOTIF_ADDFD: libc::c_ulong = 1075323139;
+    let ret = unsafe { libc::ioctl(fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ADDFD, &raw mut *addfd) };
+    if ret < 0 {
+        return Err(nix::Error::last());
+    }
+    Ok(ret)
+}
+
 pub fn install_unotify_filter(prog: &[libc::sock_filter]) -> nix::Result<OwnedFd> {
     let mut filter = libc::sock_fprog {
         len: prog.len().try_into().unwrap(),
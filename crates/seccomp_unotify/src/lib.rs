@@ -1,5 +1,6 @@
 #![cfg(target_os = "linux")]
 
+pub mod arch;
 mod bindings;
 pub mod payload;
 #[cfg(feature = "target")]
@@ -1,5 +1,8 @@
 use bincode::{Decode, Encode};
 
+#[cfg(feature = "supervisor")]
+use crate::arch::Arch;
+
 #[derive(Debug, Encode, Decode, Clone, Copy)]
 pub(crate) struct CodableSockFilter {
     code: u16,
@@ -9,9 +12,16 @@ pub(crate) struct CodableSockFilter {
 }
 
 #[cfg(feature = "supervisor")]
-impl From<seccompiler::sock_filter> for CodableSockFilter {
-    fn from(c_filter: seccompiler::sock_filter) -> Self {
-        let seccompiler::sock_filter { code, jt, jf, k } = c_filter;
+impl CodableSockFilter {
+    const fn stmt(code: u16, k: u32) -> Self {
+        Self {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+    const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
         Self { code, jt, jf, k }
     }
 }
@@ -26,3 +36,138 @@ impl From<CodableSockFilter> for libc::sock_filter {
 
 #[derive(Encode, Decode, Debug, Clone)]
 pub struct Filter(pub(crate) Vec<CodableSockFilter>);
+
+#[cfg(feature = "supervisor")]
+mod bpf {
+    pub const LD_W_ABS: u16 = 0x20;
+    pub const JMP_JA: u16 = 0x05;
+    pub const JMP_JEQ_K: u16 = 0x15;
+    pub const RET_K: u16 = 0x06;
+
+    /// Offsets of the fields of `struct seccomp_data`.
+    pub const NR_OFFSET: u32 = 0;
+    pub const ARCH_OFFSET: u32 = 4;
+}
+
+#[cfg(feature = "supervisor")]
+impl Filter {
+    /// Builds a filter that notifies the supervisor of `syscalls`, given by arch and number, and
+    /// allows the others of the arches that `Arch` knows.
+    ///
+    /// The syscalls of other arches, like 32-bit ARM on aarch64, kill the process instead. Their
+    /// numbers and arguments can't be decoded, so the process couldn't be traced, and notifying
+    /// the supervisor of them would only delay that.
+    pub(crate) fn notify(syscalls: &[(Arch, u32)]) -> Self {
+        // The syscall numbers to notify of, by `seccomp_data.arch`.
+        let mut groups: Vec<(u32, Vec<u32>)> = vec![];
+        for arch in Arch::ALL {
+            let audit_arch = arch.audit_arch();
+            if !groups
+                .iter()
+                .any(|(group_arch, _)| *group_arch == audit_arch)
+            {
+                groups.push((audit_arch, vec![]));
+            }
+        }
+        for &(arch, nr) in syscalls {
+            let audit_arch = arch.audit_arch();
+            let (_, nrs) = groups
+                .iter_mut()
+                .find(|(group_arch, _)| *group_arch == audit_arch)
+                .unwrap();
+            nrs.push(nr);
+        }
+
+        let allow = CodableSockFilter::stmt(bpf::RET_K, libc::SECCOMP_RET_ALLOW);
+        let notify = CodableSockFilter::stmt(bpf::RET_K, libc::SECCOMP_RET_USER_NOTIF);
+        let kill = CodableSockFilter::stmt(bpf::RET_K, libc::SECCOMP_RET_KILL_PROCESS);
+
+        // Jumps to the group of the arch, with `JA` whose offset isn't limited to 255 like the
+        // ones of conditional jumps.
+        let mut program = vec![CodableSockFilter::stmt(bpf::LD_W_ABS, bpf::ARCH_OFFSET)];
+        let mut group_start = program.len() + groups.len() * 2 + 1;
+        for (audit_arch, nrs) in &groups {
+            program.push(CodableSockFilter::jump(bpf::JMP_JEQ_K, *audit_arch, 0, 1));
+            let offset = group_start - (program.len() + 1);
+            program.push(CodableSockFilter::stmt(
+                bpf::JMP_JA,
+                offset.try_into().unwrap(),
+            ));
+            group_start += nrs.len() * 2 + 2;
+        }
+        program.push(kill);
+
+        for (_, nrs) in &groups {
+            program.push(CodableSockFilter::stmt(bpf::LD_W_ABS, bpf::NR_OFFSET));
+            for nr in nrs {
+                program.push(CodableSockFilter::jump(bpf::JMP_JEQ_K, *nr, 0, 1));
+                program.push(notify);
+            }
+            program.push(allow);
+        }
+        Self(program)
+    }
+}
+
+#[cfg(all(test, feature = "supervisor"))]
+mod tests {
+    use super::*;
+
+    /// Runs `filter` on a syscall like the kernel does, and returns the action.
+    fn run(filter: &Filter, audit_arch: u32, nr: u32) -> u32 {
+        let mut accumulator = 0;
+        let mut pc = 0;
+        loop {
+            let CodableSockFilter { code, jt, jf, k } = filter.0[pc];
+            pc += 1;
+            match code {
+                bpf::LD_W_ABS => {
+                    accumulator = match k {
+                        bpf::NR_OFFSET => nr,
+                        bpf::ARCH_OFFSET => audit_arch,
+                        _ => unreachable!(),
+                    }
+                }
+                bpf::JMP_JA => pc += k as usize,
+                bpf::JMP_JEQ_K => pc += usize::from(if accumulator == k { jt } else { jf }),
+                bpf::RET_K => return k,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn long_filter() {
+        // More than 255 instructions between the jumps to the groups and the last group.
+        let syscalls: Vec<_> = [Arch::NATIVE, Arch::X86]
+            .into_iter()
+            .flat_map(|arch| (0..200).map(move |nr| (arch, nr)))
+            .collect();
+        let filter = Filter::notify(&syscalls);
+        assert!(filter.0.len() > 800);
+
+        for arch in [Arch::NATIVE, Arch::X86] {
+            let audit_arch = arch.audit_arch();
+            assert_eq!(run(&filter, audit_arch, 0), libc::SECCOMP_RET_USER_NOTIF);
+            assert_eq!(run(&filter, audit_arch, 199), libc::SECCOMP_RET_USER_NOTIF);
+            assert_eq!(run(&filter, audit_arch, 200), libc::SECCOMP_RET_ALLOW);
+        }
+    }
+
+    #[test]
+    fn other_arches() {
+        let filter = Filter::notify(&[(Arch::NATIVE, 1)]);
+        // Known, without syscalls to notify of.
+        let other_known = if Arch::NATIVE == Arch::Aarch64 {
+            Arch::X86_64
+        } else {
+            Arch::Aarch64
+        };
+        assert_eq!(
+            run(&filter, other_known.audit_arch(), 1),
+            libc::SECCOMP_RET_ALLOW
+        );
+        // AUDIT_ARCH_ARM, which `Arch` doesn't know.
+        assert_eq!(run(&filter, 0x4000_0028, 1), libc::SECCOMP_RET_KILL_PROCESS);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    ffi::{CStr, OsString}, io, mem::{transmute, MaybeUninit}, os::{fd::RawFd, raw::c_void}
+    ffi::{CStr, OsString}, io, mem::{transmute, MaybeUninit}, os::{fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd}, raw::c_void}
 };
 
 use arrayvec::ArrayVec;
@@ -7,18 +7,81 @@ use bytes::BufMut;
 use libc::{pid_t, seccomp_notif};
 use tokio::io::ReadBuf;
 
+use super::StaleNotification;
+use crate::arch::Arch;
+use crate::bindings::{notif_id_valid, pidfd_getfd, pidfd_open};
+
+/// The process that made the notified syscall, whose memory and file descriptors the arguments refer to.
+///
+/// It's only meant to be used while the notification is handled, as it refers to the notify fd.
+#[derive(Debug, Clone, Copy)]
+pub struct Caller {
+    pid: u32,
+    arch: Arch,
+    notify_id: u64,
+    notify_fd: RawFd,
+}
+
+impl Caller {
+    /// Returns `None` if the syscall is of an arch that `Arch` doesn't know, whose arguments can't be decoded.
+    pub(crate) fn new(notif: &seccomp_notif, notify_fd: RawFd) -> Option<Self> {
+        Some(Self {
+            pid: notif.pid,
+            arch: Arch::from_seccomp_data(&notif.data)?,
+            notify_id: notif.id,
+            notify_fd,
+        })
+    }
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+    /// The arch of the syscall, which the arguments are decoded with.
+    pub fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    /// Checks that the caller is still waiting for the notification to be handled.
+    ///
+    /// Otherwise the syscall is interrupted or the caller is killed, and its pid may have been
+    /// reused by another process, so what's been read from `/proc/<pid>` or its memory is not its.
+    /// Fails with `StaleNotification` then.
+    pub fn check_valid(&self) -> io::Result<()> {
+        let notify_fd = unsafe { BorrowedFd::borrow_raw(self.notify_fd) };
+        match notif_id_valid(notify_fd, self.notify_id) {
+            Ok(()) => Ok(()),
+            Err(nix::Error::ENOENT) => Err(StaleNotification.into()),
+            Err(other_error) => Err(other_error.into()),
+        }
+    }
+
+    /// Returns `read` from the caller, or from its `/proc/<pid>`, if the notification is still valid
+    /// after it's done. A failure of `read` because the caller is gone is reported as `StaleNotification` too.
+    pub fn validate<T>(&self, read: io::Result<T>) -> io::Result<T> {
+        self.check_valid()?;
+        read
+    }
+}
+
 pub trait FromSyscallArg: Sized {
-    fn from_syscall_arg(pid: u32, arg: u64) -> io::Result<Self>;
+    fn from_syscall_arg(caller: Caller, arg: u64) -> io::Result<Self>;
 }
 
 #[derive(Debug)]
 pub struct CStrPtr {
-    pid: pid_t,
+    caller: Caller,
     remote_ptr: *mut c_void,
 }
 
 impl CStrPtr {
+    /// Reads the string into `buf`, without the null terminator.
+    /// Fails with `StaleNotification` if the caller is gone.
     pub fn read<B: BufMut>(&self, buf: &mut B) -> io::Result<()> {
+        self.caller.validate(self.read_unchecked(buf))
+    }
+
+    fn read_unchecked<B: BufMut>(&self, buf: &mut B) -> io::Result<()> {
+        // How many bytes have been read by previous partial reads.
+        let mut offset = 0usize;
         loop {
             let chunk = buf.chunk_mut();
             if chunk.len() == 0 {
@@ -34,13 +97,13 @@ impl CStrPtr {
             };
 
             let remote_iov = libc::iovec {
-                iov_base: self.remote_ptr,
+                iov_base: self.remote_ptr.wrapping_byte_add(offset),
                 iov_len: chunk.len(),
             };
 
             let read_size = unsafe {
                 libc::process_vm_readv(
-                    self.pid,
+                    self.caller.pid as pid_t,
                     &local_iov,
                     1,
                     &remote_iov,
@@ -58,6 +121,7 @@ impl CStrPtr {
             let Some(nul_index) = chunk.iter().position(|byte| *byte == b'\0') else {
                 // No '\0' found, could be a partitial read, advance all of `read_size` and continue reading.
                 unsafe { buf.advance_mut(read_size) };
+                offset += read_size;
                 continue;
             };
             unsafe { buf.advance_mut(nul_index) };
@@ -72,10 +136,96 @@ impl CStrPtr {
     }
 }
 
+impl CStrPtr {
+    pub fn caller(&self) -> Caller {
+        self.caller
+    }
+    pub fn pid(&self) -> u32 {
+        self.caller.pid
+    }
+    pub fn is_null(&self) -> bool {
+        self.remote_ptr.is_null()
+    }
+}
+
 impl FromSyscallArg for CStrPtr {
-    fn from_syscall_arg(pid: u32, arg: u64) -> io::Result<Self> {
+    fn from_syscall_arg(caller: Caller, arg: u64) -> io::Result<Self> {
+        Ok(Self {
+            caller,
+            remote_ptr: arg as _,
+        })
+    }
+}
+
+/// A pointer to a null-terminated array of C string pointers in the target process, like `argv` of `execve`.
+pub struct CStrPtrArray {
+    caller: Caller,
+    remote_ptr: *mut c_void,
+}
+
+impl CStrPtrArray {
+    pub fn caller(&self) -> Caller {
+        self.caller
+    }
+    pub fn pid(&self) -> u32 {
+        self.caller.pid
+    }
+
+    /// Reads the strings in the array. A null array is read as an empty one, as Linux does for `argv`.
+    /// Fails with `StaleNotification` if the caller is gone.
+    pub fn read(&self) -> io::Result<Vec<Vec<u8>>> {
+        self.caller.validate(self.read_unchecked())
+    }
+
+    fn read_unchecked(&self) -> io::Result<Vec<Vec<u8>>> {
+        let mut strings = Vec::new();
+        if self.remote_ptr.is_null() {
+            return Ok(strings);
+        }
+        // Pointers in the array are as wide as the ones of the arch of the caller.
+        let ptr_size = self.caller.arch.word_size();
+        loop {
+            let mut ptr_bytes = [0u8; size_of::<u64>()];
+            let local_iov = libc::iovec {
+                iov_base: ptr_bytes.as_mut_ptr().cast(),
+                iov_len: ptr_size,
+            };
+            let remote_iov = libc::iovec {
+                iov_base: self.remote_ptr.wrapping_byte_add(strings.len() * ptr_size),
+                iov_len: ptr_size,
+            };
+            let read_size = unsafe {
+                libc::process_vm_readv(self.caller.pid as pid_t, &local_iov, 1, &remote_iov, 1, 0)
+            };
+            if read_size != ptr_size as isize {
+                return Err(if read_size < 0 {
+                    io::Error::last_os_error()
+                } else {
+                    io::Error::from_raw_os_error(libc::EFAULT)
+                });
+            }
+            let str_ptr = match ptr_size {
+                4 => u32::from_ne_bytes(ptr_bytes[..4].try_into().unwrap()) as usize,
+                _ => u64::from_ne_bytes(ptr_bytes) as usize,
+            } as *mut c_void;
+            if str_ptr.is_null() {
+                return Ok(strings);
+            }
+            let mut string = Vec::new();
+            CStrPtr {
+                caller: self.caller,
+                remote_ptr: str_ptr,
+            }
+            .read_unchecked(&mut string)?;
+            strings.push(string);
+        }
+    }
+}
+
+impl FromSyscallArg for CStrPtrArray {
+    fn from_syscall_arg(caller: Caller, arg: u64) -> io::Result<Self> {
         Ok(Self {
-            pid: pid as _,
+            caller,
             remote_ptr: arg as _,
         })
     }
@@ -84,61 +234,154 @@ impl FromSyscallArg for CStrPtr {
 #[derive(Debug)]
 pub struct Ignored(());
 impl FromSyscallArg for Ignored {
-    fn from_syscall_arg(_pid: u32, _arg: u64) -> io::Result<Self> {
+    fn from_syscall_arg(_caller: Caller, _arg: u64) -> io::Result<Self> {
         Ok(Ignored(()))
     }
 }
 
 #[derive(Debug)]
 pub struct Fd {
-    pid: u32,
+    caller: Caller,
     fd: RawFd,
 }
 impl FromSyscallArg for Fd {
-    fn from_syscall_arg(pid: u32, arg: u64) -> io::Result<Self> {
-        Ok(Self { pid, fd: arg as _ })
+    fn from_syscall_arg(caller: Caller, arg: u64) -> io::Result<Self> {
+        Ok(Self {
+            caller,
+            fd: arg as _,
+        })
+    }
+}
+
+impl FromSyscallArg for libc::c_int {
+    fn from_syscall_arg(_caller: Caller, arg: u64) -> io::Result<Self> {
+        Ok(arg as _)
+    }
+}
+
+impl FromSyscallArg for libc::c_uint {
+    fn from_syscall_arg(_caller: Caller, arg: u64) -> io::Result<Self> {
+        Ok(arg as _)
     }
 }
 
 impl Fd {
+    /// The current working directory of the process, the same as passing `AT_FDCWD` as a dirfd.
+    pub fn cwd(caller: Caller) -> Self {
+        Self {
+            caller,
+            fd: libc::AT_FDCWD,
+        }
+    }
+    pub fn caller(&self) -> Caller {
+        self.caller
+    }
+    pub fn pid(&self) -> u32 {
+        self.caller.pid
+    }
+    /// Duplicates the file descriptor into the supervisor with `pidfd_getfd`, which requires Linux 5.6.
+    /// Fails with `StaleNotification` if the caller is gone.
+    pub fn dup(&self) -> io::Result<OwnedFd> {
+        let pidfd = pidfd_open(self.caller.pid).map_err(io::Error::from);
+        // The pidfd is of the caller if the notification is still valid after it's opened,
+        // and keeps referring to it even if the pid is reused later.
+        let pidfd = self.caller.validate(pidfd)?;
+        Ok(pidfd_getfd(pidfd.as_fd(), self.fd)?)
+    }
+
+    /// Gets the path of the file descriptor.
+    ///
+    /// The file descriptor is duplicated into the supervisor, and its path is read from the
+    /// supervisor's own `/proc/self/fd`, so that it doesn't depend on accessing `/proc/<pid>` of the
+    /// caller, which may be restricted in containers. `/proc/<pid>` is read if duplicating isn't
+    /// supported, and for the working directory, which is not a file descriptor.
+    ///
+    /// Fails with `StaleNotification` if the caller is gone.
     // TODO: allocate in arena
-    pub fn get_path(&self) -> nix::Result<OsString> {
-        nix::fcntl::readlink(
+    pub fn get_path(&self) -> io::Result<OsString> {
+        if self.fd != libc::AT_FDCWD {
+            match self.dup() {
+                Ok(local_fd) => {
+                    let path = format!("/proc/self/fd/{}", local_fd.as_raw_fd());
+                    return Ok(nix::fcntl::readlink(path.as_str())?);
+                }
+                Err(err) if StaleNotification::is(&err) => return Err(err),
+                Err(_) => {}
+            }
+        }
+        let path = nix::fcntl::readlink(
             if self.fd == libc::AT_FDCWD {
-                format!("/proc/{}/cwd", self.pid)
+                format!("/proc/{}/cwd", self.caller.pid)
             } else {
-                format!("/proc/{}/fd/{}", self.pid, self.fd)
+                format!("/proc/{}/fd/{}", self.caller.pid, self.fd)
             }
             .as_str(),
-        )
+        );
+        self.caller.validate(path.map_err(io::Error::from))
+    }
+}
+
+/// A file descriptor argument duplicated into the supervisor, which refers to the same open file as
+/// the one of the caller. See `Fd::dup`.
+#[derive(Debug)]
+pub struct DupFd {
+    fd: Fd,
+    local_fd: Option<OwnedFd>,
+}
+
+impl DupFd {
+    /// The file descriptor in the caller.
+    pub fn fd(&self) -> &Fd {
+        &self.fd
+    }
+    /// The duplicated file descriptor, or `None` if the argument is not a file descriptor open in
+    /// the caller, like `AT_FDCWD`, in which case the syscall fails or doesn't take a file.
+    pub fn local_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.local_fd.as_ref().map(AsFd::as_fd)
+    }
+}
+
+impl FromSyscallArg for DupFd {
+    fn from_syscall_arg(caller: Caller, arg: u64) -> io::Result<Self> {
+        let fd = Fd::from_syscall_arg(caller, arg)?;
+        let local_fd = if fd.fd < 0 {
+            None
+        } else {
+            match fd.dup() {
+                Ok(local_fd) => Some(local_fd),
+                Err(err) if err.raw_os_error() == Some(libc::EBADF) => None,
+                Err(err) => return Err(err),
+            }
+        };
+        Ok(Self { fd, local_fd })
     }
 }
 
 pub trait FromNotify: Sized {
-    fn from_notify(notif: &seccomp_notif) -> io::Result<Self>;
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self>;
 }
 
 impl<T: FromSyscallArg> FromNotify for (T,) {
-    fn from_notify(notif: &seccomp_notif) -> io::Result<Self> {
-        Ok((T::from_syscall_arg(notif.pid, notif.data.args[0])?,))
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self> {
+        Ok((T::from_syscall_arg(caller, notif.data.args[0])?,))
     }
 }
 
 impl<T1: FromSyscallArg, T2: FromSyscallArg> FromNotify for (T1, T2) {
-    fn from_notify(notif: &seccomp_notif) -> io::Result<Self> {
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self> {
         Ok((
-            T1::from_syscall_arg(notif.pid, notif.data.args[0])?,
-            T2::from_syscall_arg(notif.pid, notif.data.args[1])?,
+            T1::from_syscall_arg(caller, notif.data.args[0])?,
+            T2::from_syscall_arg(caller, notif.data.args[1])?,
         ))
     }
 }
 
 impl<T1: FromSyscallArg, T2: FromSyscallArg, T3: FromSyscallArg> FromNotify for (T1, T2, T3) {
-    fn from_notify(notif: &seccomp_notif) -> io::Result<Self> {
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self> {
         Ok((
-            T1::from_syscall_arg(notif.pid, notif.data.args[0])?,
-            T2::from_syscall_arg(notif.pid, notif.data.args[1])?,
-            T3::from_syscall_arg(notif.pid, notif.data.args[2])?,
+            T1::from_syscall_arg(caller, notif.data.args[0])?,
+            T2::from_syscall_arg(caller, notif.data.args[1])?,
+            T3::from_syscall_arg(caller, notif.data.args[2])?,
         ))
     }
 }
@@ -146,12 +389,31 @@ impl<T1: FromSyscallArg, T2: FromSyscallArg, T3: FromSyscallArg> FromNotify for
 impl<T1: FromSyscallArg, T2: FromSyscallArg, T3: FromSyscallArg, T4: FromSyscallArg> FromNotify
     for (T1, T2, T3, T4)
 {
-    fn from_notify(notif: &seccomp_notif) -> io::Result<Self> {
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self> {
+        Ok((
+            T1::from_syscall_arg(caller, notif.data.args[0])?,
+            T2::from_syscall_arg(caller, notif.data.args[1])?,
+            T3::from_syscall_arg(caller, notif.data.args[2])?,
+            T4::from_syscall_arg(caller, notif.data.args[3])?,
+        ))
+    }
+}
+
+impl<
+    T1: FromSyscallArg,
+    T2: FromSyscallArg,
+    T3: FromSyscallArg,
+    T4: FromSyscallArg,
+    T5: FromSyscallArg,
+> FromNotify for (T1, T2, T3, T4, T5)
+{
+    fn from_notify(notif: &seccomp_notif, caller: Caller) -> io::Result<Self> {
         Ok((
-            T1::from_syscall_arg(notif.pid, notif.data.args[0])?,
-            T2::from_syscall_arg(notif.pid, notif.data.args[1])?,
-            T3::from_syscall_arg(notif.pid, notif.data.args[2])?,
-            T4::from_syscall_arg(notif.pid, notif.data.args[3])?,
+            T1::from_syscall_arg(caller, notif.data.args[0])?,
+            T2::from_syscall_arg(caller, notif.data.args[1])?,
+            T3::from_syscall_arg(caller, notif.data.args[2])?,
+            T4::from_syscall_arg(caller, notif.data.args[3])?,
+            T5::from_syscall_arg(caller, notif.data.args[4])?,
         ))
     }
 }
@@ -1,28 +1,198 @@
 pub mod arg;
 
-use std::io;
+use crate::arch::Arch;
+use arg::Caller;
 use libc::seccomp_notif;
+use std::{
+    error::Error,
+    fmt, io,
+    os::fd::{OwnedFd, RawFd},
+};
+
+/// How the supervisor replies to a notification.
+///
+/// Except for `Continue`, the syscall is not run in the target, and the supervisor emulates it.
+#[derive(Debug, Default)]
+pub enum NotifyResponse {
+    /// Lets the syscall run, as if it wasn't intercepted.
+    #[default]
+    Continue,
+    /// Fails the syscall with the raw error code.
+    Error(i32),
+    /// Returns the value as the result of the syscall.
+    Value(i64),
+    /// Installs a file descriptor in the target, and returns its number as the result of the syscall.
+    Fd(AddFd),
+}
+
+/// A file descriptor of the supervisor to install in the target with `SECCOMP_IOCTL_NOTIF_ADDFD`,
+/// which requires Linux 5.14.
+#[derive(Debug)]
+pub struct AddFd {
+    pub fd: OwnedFd,
+    /// The number of the file descriptor in the target, replacing the one already open there like
+    /// `dup2`. The lowest available number is used if it's `None`.
+    pub target_fd: Option<RawFd>,
+    /// Whether to set `FD_CLOEXEC` on the file descriptor in the target.
+    pub cloexec: bool,
+}
+
+impl From<OwnedFd> for AddFd {
+    fn from(fd: OwnedFd) -> Self {
+        Self {
+            fd,
+            target_fd: None,
+            cloexec: false,
+        }
+    }
+}
+
+impl From<()> for NotifyResponse {
+    fn from((): ()) -> Self {
+        Self::Continue
+    }
+}
+
+/// The error of reading the arguments of a notification whose caller is no longer waiting for it,
+/// because the syscall is interrupted or the caller is killed.
+///
+/// What's been read may belong to another process that reused the pid, so it shouldn't be used.
+/// There is nothing to reply to, and the supervisor goes on with the next notification when a
+/// handler fails with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleNotification;
+
+impl fmt::Display for StaleNotification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the seccomp notification is no longer valid")
+    }
+}
+
+impl Error for StaleNotification {}
+
+impl From<StaleNotification> for io::Error {
+    fn from(stale: StaleNotification) -> Self {
+        io::Error::new(io::ErrorKind::NotFound, stale)
+    }
+}
+
+impl StaleNotification {
+    /// Whether `error` is a `StaleNotification`.
+    pub fn is(error: &io::Error) -> bool {
+        error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<StaleNotification>())
+    }
+}
 
 pub trait SeccompNotifyHandler {
-    fn syscalls() -> &'static [syscalls::Sysno];
-    fn handle_notify(&mut self, notify: &seccomp_notif) -> io::Result<()>;
+    /// The syscalls to be notified of, by arch and number.
+    fn syscalls() -> Vec<(Arch, u32)>;
+    /// Handles `notify`, whose arguments are read from `caller`.
+    fn handle_notify(
+        &mut self,
+        notify: &seccomp_notif,
+        caller: Caller,
+    ) -> io::Result<NotifyResponse>;
 }
 
+/// Implements `SeccompNotifyHandler` by dispatching notifications to the methods named after the syscalls.
+///
+/// A method takes the arguments as a tuple of `FromNotify` types, and returns `io::Result` of
+/// `NotifyResponse`, or of `()` to always let the syscall run. Reading the arguments fails with
+/// `StaleNotification` if the caller is gone.
+///
+/// The syscalls listed first are of the native arch, and of x32 on x86_64, which shares their names.
+/// Other arches are handled if they have a section after them, like `; X86: stat64 => stat`. The syscalls
+/// of the arch with the names of the native ones are dispatched to the same methods, and the ones listed in
+/// the section to the methods after `=>`, which take the same arguments, or else to the methods of their names.
+/// A section can have attributes like `#[cfg(...)]` before its arch.
+/// The arguments are decoded with the widths of the arch of the caller.
 #[macro_export]
 macro_rules! impl_handler {
-    ($type: ty, $($syscall:ident)*) => {
+    (@call $self:ident, $args:expr, $syscall:ident) => {
+        $self.$syscall($args)
+    };
+    (@call $self:ident, $args:expr, $syscall:ident => $method:ident) => {
+        $self.$method($args)
+    };
+    (
+        $type: ty,
+        $($(#[$attr:meta])* $syscall:ident)*
+        $(; $(#[$arch_attr:meta])* $arch:ident: $($arch_syscall:ident $(=> $method:ident)?)*)*
+    ) => {
 
     impl $crate::supervisor::handler::SeccompNotifyHandler for $type {
-        fn syscalls() -> &'static [::syscalls::Sysno] {
-            &[ $( ::syscalls::Sysno:: $syscall ),* ]
+        fn syscalls() -> ::std::vec::Vec<($crate::arch::Arch, u32)> {
+            let mut syscalls = ::std::vec::Vec::new();
+            $(
+                $(#[$attr])*
+                syscalls.push(($crate::arch::Arch::NATIVE, ::syscalls::Sysno::$syscall as u32));
+                $(#[$attr])*
+                #[cfg(target_arch = "x86_64")]
+                syscalls.push(($crate::arch::Arch::X32, $crate::arch::x32_nr(::syscalls::Sysno::$syscall)));
+            )*
+            $(
+                $(#[$arch_attr])*
+                {
+                    let native_nrs: ::std::vec::Vec<u32> = syscalls
+                        .iter()
+                        .filter(|(arch, _)| *arch == $crate::arch::Arch::NATIVE)
+                        .map(|(_, nr)| *nr)
+                        .collect();
+                    syscalls.extend(native_nrs.into_iter().filter_map(|native_nr| {
+                        let nr = $crate::arch::Arch::$arch.nr_of_native(native_nr)?;
+                        Some(($crate::arch::Arch::$arch, nr))
+                    }));
+                    $(
+                        syscalls.push(($crate::arch::Arch::$arch, $crate::syscall_nr!($arch, $arch_syscall)));
+                    )*
+                }
+            )*
+            syscalls
         }
-        fn handle_notify(&mut self, notify: &::libc::seccomp_notif) -> ::std::io::Result<()> {
+        fn handle_notify(
+            &mut self,
+            notify: &::libc::seccomp_notif,
+            caller: $crate::supervisor::handler::arg::Caller,
+        ) -> ::std::io::Result<$crate::supervisor::handler::NotifyResponse> {
+            let arch = caller.arch();
+            let nr = notify.data.nr as u32;
+            // The number of the native syscall to dispatch as.
+            #[allow(unused_mut)]
+            let mut native_nr = (arch == $crate::arch::Arch::NATIVE).then_some(nr);
             $(
-                if notify.data.nr == ::syscalls::Sysno::$syscall as _ {
-                    return self.$syscall($crate::supervisor::handler::arg::FromNotify::from_notify(notify)?)
+                $(#[$arch_attr])*
+                if arch == $crate::arch::Arch::$arch {
+                    $(
+                        if nr == $crate::syscall_nr!($arch, $arch_syscall) {
+                            return $crate::impl_handler!(
+                                @call self,
+                                $crate::supervisor::handler::arg::FromNotify::from_notify(notify, caller)?,
+                                $arch_syscall $(=> $method)?
+                            )
+                            .map(::std::convert::Into::into);
+                        }
+                    )*
+                    native_nr = arch.native_nr(nr);
+                }
+            )*
+            $(
+                $(#[$attr])*
+                if native_nr == Some(::syscalls::Sysno::$syscall as u32) {
+                    return self
+                        .$syscall($crate::supervisor::handler::arg::FromNotify::from_notify(notify, caller)?)
+                        .map(::std::convert::Into::into);
+                }
+                $(#[$attr])*
+                #[cfg(target_arch = "x86_64")]
+                if arch == $crate::arch::Arch::X32 && nr == $crate::arch::x32_nr(::syscalls::Sysno::$syscall) {
+                    return self
+                        .$syscall($crate::supervisor::handler::arg::FromNotify::from_notify(notify, caller)?)
+                        .map(::std::convert::Into::into);
                 }
             )*
-            Ok(())
+            Ok($crate::supervisor::handler::NotifyResponse::Continue)
         }
     }
     };
@@ -4,9 +4,11 @@ use std::{
 };
 use tracing::trace;
 
+use super::handler::{AddFd, NotifyResponse};
 use crate::bindings::{
+    SECCOMP_ADDFD_FLAG_SEND, SECCOMP_ADDFD_FLAG_SETFD, SeccompNotifAddfd,
     alloc::{Alloced, alloc_seccomp_notif},
-    notif_recv,
+    notif_addfd, notif_recv,
 };
 use tokio::io::unix::AsyncFd;
 
@@ -32,17 +34,29 @@ impl AsFd for NotifyListener {
 
 const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 3222806785;
 const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 3226476800;
-const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 1074274562;
 
 impl NotifyListener {
-    pub fn send_continue(
+    /// Replies to the notification `req_id` with `response`.
+    pub fn send_response(
         &self,
         req_id: u64,
+        response: NotifyResponse,
         buf: &mut Alloced<seccomp_notif_resp>,
     ) -> io::Result<()> {
         let resp = buf.zeroed();
         resp.id = req_id;
-        resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as _;
+        match response {
+            NotifyResponse::Continue => {
+                resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as _;
+            }
+            NotifyResponse::Error(errno) => {
+                resp.error = -errno;
+            }
+            NotifyResponse::Value(val) => {
+                resp.val = val;
+            }
+            NotifyResponse::Fd(add_fd) => return self.send_fd(req_id, add_fd),
+        }
 
         let ret = unsafe {
             libc::ioctl(
@@ -52,15 +66,33 @@ impl NotifyListener {
             )
         };
         if ret < 0 {
-            let err = nix::Error::last();
-            // ignore error if target process's syscall was interrupted
-            if err == nix::Error::ENOENT {
-                return Ok(());
-            };
-            return Err(err.into());
+            return ignore_interrupted(nix::Error::last());
         };
         Ok(())
     }
+
+    /// Installs `add_fd` in the target, and replies to the notification `req_id` with its number
+    /// in one step, so that the target doesn't get a file descriptor for a syscall that fails.
+    fn send_fd(&self, req_id: u64, add_fd: AddFd) -> io::Result<()> {
+        let mut addfd = SeccompNotifAddfd {
+            id: req_id,
+            flags: SECCOMP_ADDFD_FLAG_SEND,
+            srcfd: add_fd.fd.as_raw_fd() as u32,
+            ..Default::default()
+        };
+        if let Some(target_fd) = add_fd.target_fd {
+            addfd.flags |= SECCOMP_ADDFD_FLAG_SETFD;
+            addfd.newfd = target_fd as u32;
+        }
+        if add_fd.cloexec {
+            addfd.newfd_flags = libc::O_CLOEXEC as u32;
+        }
+        // `add_fd.fd` is closed in the supervisor after it's installed in the target.
+        match notif_addfd(self.async_fd.as_fd(), &mut addfd) {
+            Ok(_) => Ok(()),
+            Err(err) => ignore_interrupted(err),
+        }
+    }
     pub async fn next(&mut self) -> io::Result<Option<&seccomp_notif>> {
         loop {
             let mut ready_guard = self.async_fd.readable().await?;
@@ -84,3 +116,11 @@ impl NotifyListener {
         }
     }
 }
+
+fn ignore_interrupted(err: nix::Error) -> io::Result<()> {
+    // ignore error if target process's syscall was interrupted
+    if err == nix::Error::ENOENT {
+        return Ok(());
+    };
+    Err(err.into())
+}
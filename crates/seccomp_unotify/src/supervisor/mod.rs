@@ -6,7 +6,8 @@ use std::{
     os::fd::{AsFd, AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
 };
 
-pub use handler::SeccompNotifyHandler;
+use handler::arg::Caller;
+pub use handler::{AddFd, NotifyResponse, SeccompNotifyHandler, StaleNotification};
 use listener::NotifyListener;
 use nix::{
     cmsg_space,
@@ -14,9 +15,8 @@ use nix::{
     sys::socket::{ControlMessageOwned, MsgFlags, recvmsg},
 };
 use passfd::tokio::FdPassingExt;
-use seccompiler::{BpfProgram, SeccompAction, SeccompFilter};
 use tokio::{io::Interest, net::UnixStream, task::JoinSet};
-use tracing::{Level, span};
+use tracing::{Level, span, trace, warn};
 
 use crate::{
     bindings::alloc::alloc_seccomp_notif_resp,
@@ -41,28 +41,19 @@ impl PreExec {
 
 pub fn supervise<H: SeccompNotifyHandler + Default + Send + 'static>()
 -> io::Result<Supervisor<impl Future<Output = io::Result<Vec<H>>> + Send>> {
+    supervise_with(H::default)
+}
+
+/// Like `supervise`, but creates the handler of each notify fd with `new_handler`,
+/// so that handlers can share state with the caller.
+pub fn supervise_with<H: SeccompNotifyHandler + Send + 'static>(
+    new_handler: impl Fn() -> H + Send + 'static,
+) -> io::Result<Supervisor<impl Future<Output = io::Result<Vec<H>>> + Send>> {
     let (notify_fd_receiver, notify_fd_sender) = UnixStream::pair()?;
     let notify_fd_sender = notify_fd_sender.into_std()?;
     notify_fd_sender.set_nonblocking(false)?;
 
-    let filter = SeccompFilter::new(
-        H::syscalls()
-            .iter()
-            .map(|sysno| (sysno.id().into(), vec![]))
-            .collect(),
-        SeccompAction::Allow,
-        SeccompAction::Raw(libc::SECCOMP_RET_USER_NOTIF),
-        std::env::consts::ARCH.try_into().unwrap(),
-    )
-    .unwrap();
-
-    let filter = Filter(
-        BpfProgram::try_from(filter)
-            .unwrap()
-            .into_iter()
-            .map(|sock_filter| sock_filter.into())
-            .collect(),
-    );
+    let filter = Filter::notify(&H::syscalls());
 
     let payload = SeccompPayload {
         ipc_fd: notify_fd_sender.as_raw_fd(),
@@ -85,17 +76,42 @@ pub fn supervise<H: SeccompNotifyHandler + Default + Send + 'static>()
             };
             let mut listener = NotifyListener::try_from(notify_fd)?;
 
-            let mut handler = H::default();
+            let mut handler = new_handler();
             let mut resp_buf = alloc_seccomp_notif_resp();
 
             join_set.spawn(async move {
+                let notify_fd = listener.as_fd().as_raw_fd();
                 while let Some(notify) = listener.next().await? {
                     let _span = span!(Level::TRACE, "notify loop tick");
-                    // Errors on the supervisor side shouldn't block the syscall.
-                    let handle_result = handler.handle_notify(notify);
                     let notify_id = notify.id;
-                    listener.send_continue(notify_id, &mut resp_buf)?;
-                    handle_result?;
+                    let Some(caller) = Caller::new(notify, notify_fd) else {
+                        // The filter kills the processes making syscalls of other arches instead of
+                        // notifying of them, so this isn't expected. The syscall can't be checked.
+                        listener.send_response(
+                            notify_id,
+                            NotifyResponse::Error(libc::ENOSYS),
+                            &mut resp_buf,
+                        )?;
+                        continue;
+                    };
+                    match handler.handle_notify(notify, caller) {
+                        Ok(response) => {
+                            listener.send_response(notify_id, response, &mut resp_buf)?;
+                        }
+                        Err(err) if StaleNotification::is(&err) => {
+                            trace!("notification {} is stale", notify_id);
+                        }
+                        Err(err) => {
+                            // An error handling one notification shouldn't block the syscall,
+                            // nor stop the supervision of the other ones.
+                            warn!("failed to handle notification {}: {}", notify_id, err);
+                            listener.send_response(
+                                notify_id,
+                                NotifyResponse::Continue,
+                                &mut resp_buf,
+                            )?;
+                        }
+                    }
                 }
                 io::Result::Ok(handler)
             });
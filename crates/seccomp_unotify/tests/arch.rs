@@ -0,0 +1,204 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use std::{arch::asm, error::Error, io, ptr, slice, time::Duration};
+
+use assertables::assert_contains;
+use seccomp_unotify::{
+    arch::{Arch, x32_nr},
+    impl_handler,
+    supervisor::{
+        NotifyResponse, Supervisor,
+        handler::arg::{CStrPtr, CStrPtrArray, Fd},
+        supervise,
+    },
+    target::install_target,
+};
+use syscalls::x86_64::Sysno;
+use tokio::{process::Command, task::spawn_blocking, time::timeout};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Syscall {
+    Openat {
+        arch: Arch,
+        path: Vec<u8>,
+    },
+    Execve {
+        arch: Arch,
+        path: Vec<u8>,
+        args: Vec<Vec<u8>>,
+    },
+}
+
+/// Records the syscalls on the paths under `/arch`, and fails them with `EXDEV`.
+#[derive(Default, Debug)]
+struct ArchRecorder(Vec<Syscall>);
+impl ArchRecorder {
+    fn openat(&mut self, (_, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        let arch = path.caller().arch();
+        let path = path.read_with_buf::<32768, _, _>(|path| Ok(path.to_vec()))?;
+        if !path.starts_with(b"/arch/") {
+            return Ok(NotifyResponse::Continue);
+        }
+        self.0.push(Syscall::Openat { arch, path });
+        Ok(NotifyResponse::Error(libc::EXDEV))
+    }
+    fn execve(&mut self, (path, args): (CStrPtr, CStrPtrArray)) -> io::Result<NotifyResponse> {
+        let arch = path.caller().arch();
+        let path = path.read_with_buf::<32768, _, _>(|path| Ok(path.to_vec()))?;
+        if !path.starts_with(b"/arch/") {
+            return Ok(NotifyResponse::Continue);
+        }
+        let args = args.read()?;
+        self.0.push(Syscall::Execve { arch, path, args });
+        Ok(NotifyResponse::Error(libc::EXDEV))
+    }
+}
+
+// The i386 syscalls of the native names are handled without listing them.
+impl_handler!(ArchRecorder, openat execve; X86:);
+
+/// Makes an i386 syscall with `int 0x80`, which a 64-bit process can make too.
+unsafe fn syscall_x86(nr: u32, arg0: u32, arg1: u32, arg2: u32) -> i32 {
+    let ret: u32;
+    unsafe {
+        asm!(
+            // rbx can't be an operand, as LLVM uses it.
+            "xchg {arg0:r}, rbx",
+            "int 0x80",
+            "xchg {arg0:r}, rbx",
+            arg0 = inout(reg) u64::from(arg0) => _,
+            inlateout("eax") nr => ret,
+            in("ecx") arg1,
+            in("edx") arg2,
+            out("r8") _,
+            out("r9") _,
+            out("r10") _,
+            out("r11") _,
+        );
+    }
+    ret as i32
+}
+
+#[tokio::test]
+async fn x86_syscalls() -> Result<(), Box<dyn Error>> {
+    let syscalls = timeout(Duration::from_secs(5), async move {
+        let mut cmd = Command::new("/bin/echo");
+        let Supervisor {
+            payload,
+            handling_loop,
+            mut pre_exec,
+        } = supervise::<ArchRecorder>()?;
+
+        unsafe {
+            cmd.pre_exec(move || {
+                install_target(&payload)?;
+                pre_exec.run()?;
+
+                // i386 syscalls can only take pointers below 4GiB.
+                let low_mem = libc::mmap(
+                    ptr::null_mut(),
+                    4096,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_32BIT,
+                    -1,
+                    0,
+                );
+                if low_mem == libc::MAP_FAILED {
+                    return Err(io::Error::last_os_error());
+                }
+                let addr = |offset: usize| low_mem.addr() as u32 + offset as u32;
+                let low_mem = slice::from_raw_parts_mut(low_mem.cast::<u8>(), 4096);
+                low_mem[..10].copy_from_slice(b"/arch/x86\0");
+                low_mem[64..66].copy_from_slice(b"a\0");
+                low_mem[80..83].copy_from_slice(b"bb\0");
+                for (i, ptr) in [addr(0), addr(64), addr(80), 0].into_iter().enumerate() {
+                    low_mem[128 + i * 4..][..4].copy_from_slice(&ptr.to_ne_bytes());
+                }
+
+                const OPENAT_X86: u32 = 295;
+                const EXECVE_X86: u32 = 11;
+                let ret = syscall_x86(OPENAT_X86, libc::AT_FDCWD as u32, addr(0), 0);
+                if ret != -libc::EXDEV {
+                    return Err(io::Error::other("the i386 openat should be handled"));
+                }
+                let ret = syscall_x86(EXECVE_X86, addr(0), addr(128), 0);
+                if ret != -libc::EXDEV {
+                    return Err(io::Error::other("the i386 execve should be handled"));
+                }
+                // x32 syscalls reach the filter even if the kernel doesn't run x32 binaries.
+                low_mem[256..266].copy_from_slice(b"/arch/x32\0");
+                let ret = libc::syscall(
+                    x32_nr(Sysno::openat).into(),
+                    libc::AT_FDCWD,
+                    addr(256),
+                    libc::O_RDONLY,
+                );
+                if ret != -1 || io::Error::last_os_error().raw_os_error() != Some(libc::EXDEV) {
+                    return Err(io::Error::other("the x32 openat should be handled"));
+                }
+                // execve has an x32 number of its own.
+                let ret = libc::syscall(x32_nr(Sysno::execve).into(), addr(256), addr(128), 0);
+                if ret != -1 || io::Error::last_os_error().raw_os_error() != Some(libc::EXDEV) {
+                    return Err(io::Error::other("the x32 execve should be handled"));
+                }
+                let ret = libc::openat(libc::AT_FDCWD, c"/arch/x86_64".as_ptr(), libc::O_RDONLY);
+                if ret != -1 || io::Error::last_os_error().raw_os_error() != Some(libc::EXDEV) {
+                    return Err(io::Error::other("the x86_64 openat should be handled"));
+                }
+                Ok(())
+            });
+        }
+        let child_fut = spawn_blocking(move || cmd.spawn());
+        let (recorders, exit_status) = futures_util::future::try_join(handling_loop, async move {
+            child_fut.await.unwrap()?.wait().await
+        })
+        .await?;
+        assert!(exit_status.success());
+        io::Result::Ok(
+            recorders
+                .into_iter()
+                .flat_map(|recorder| recorder.0)
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await??;
+
+    assert_contains!(
+        syscalls,
+        &Syscall::Openat {
+            arch: Arch::X86,
+            path: b"/arch/x86".to_vec(),
+        }
+    );
+    assert_contains!(
+        syscalls,
+        &Syscall::Execve {
+            arch: Arch::X86,
+            path: b"/arch/x86".to_vec(),
+            args: vec![b"/arch/x86".to_vec(), b"a".to_vec(), b"bb".to_vec()],
+        }
+    );
+    assert_contains!(
+        syscalls,
+        &Syscall::Openat {
+            arch: Arch::X32,
+            path: b"/arch/x32".to_vec(),
+        }
+    );
+    assert_contains!(
+        syscalls,
+        &Syscall::Execve {
+            arch: Arch::X32,
+            path: b"/arch/x32".to_vec(),
+            args: vec![b"/arch/x86".to_vec(), b"a".to_vec(), b"bb".to_vec()],
+        }
+    );
+    assert_contains!(
+        syscalls,
+        &Syscall::Openat {
+            arch: Arch::X86_64,
+            path: b"/arch/x86_64".to_vec(),
+        }
+    );
+    Ok(())
+}
@@ -2,7 +2,7 @@
 
 use assertables::assert_contains;
 use nix::fcntl::{AT_FDCWD, OFlag, openat};
-use nix::sys::stat::Mode;
+use nix::sys::stat::{Mode, fstat};
 use seccomp_unotify::supervisor::Supervisor;
 use tokio::time::timeout;
 
@@ -12,6 +12,7 @@ use std::ffi::OsString;
 use std::ffi::{CString, OsStr};
 use std::io;
 use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::MetadataExt;
 use std::time::Duration;
 use test_log::test;
 use tracing::{Level, span, trace};
@@ -19,7 +20,7 @@ use tracing::{Level, span, trace};
 use seccomp_unotify::{
     impl_handler,
     supervisor::{
-        handler::arg::{CStrPtr, Fd},
+        handler::arg::{CStrPtr, CStrPtrArray, DupFd, Fd},
         supervise,
     },
     target::install_target,
@@ -29,6 +30,8 @@ use tokio::{process::Command, task::spawn_blocking};
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum Syscall {
     Openat { at_dir: OsString, path: OsString },
+    Execve { path: OsString, args: Vec<OsString> },
+    Fchdir { ino: Option<u64> },
 }
 
 #[derive(Default, Clone, Debug)]
@@ -42,9 +45,25 @@ impl SyscallRecorder {
         self.0.push(Syscall::Openat { at_dir, path });
         Ok(())
     }
+    fn execve(&mut self, (path, args): (CStrPtr, CStrPtrArray)) -> io::Result<()> {
+        let path = path.read_with_buf::<32768, _, _>(|path: &[u8]| {
+            Ok(OsStr::from_bytes(path).to_os_string())
+        })?;
+        let args = args.read()?.into_iter().map(OsString::from_vec).collect();
+        self.0.push(Syscall::Execve { path, args });
+        Ok(())
+    }
+    fn fchdir(&mut self, (dir,): (DupFd,)) -> io::Result<()> {
+        let ino = match dir.local_fd() {
+            Some(local_fd) => Some(fstat(local_fd)?.st_ino),
+            None => None,
+        };
+        self.0.push(Syscall::Fchdir { ino });
+        Ok(())
+    }
 }
 
-impl_handler!(SyscallRecorder, openat);
+impl_handler!(SyscallRecorder, openat execve fchdir);
 
 async fn run_in_pre_exec(
     mut f: impl FnMut() -> io::Result<()> + Send + Sync + 'static,
@@ -157,20 +176,73 @@ async fn path_long() -> Result<(), Box<dyn Error>> {
 async fn path_overflow() -> Result<(), Box<dyn Error>> {
     let long_path = [b'a'].repeat(40000);
     let long_path_cstr = CString::new(long_path.as_slice()).unwrap();
-    let ret = run_in_pre_exec(move || {
+    let syscalls = run_in_pre_exec(move || {
         let _ = openat(
             AT_FDCWD,
             long_path_cstr.as_c_str(),
             OFlag::O_RDONLY,
             Mode::empty(),
         );
+        let _ = openat(AT_FDCWD, c"short", OFlag::O_RDONLY, Mode::empty());
+        Ok(())
+    })
+    .await?;
+    // The path that doesn't fit in the buffer fails to be handled, which doesn't stop the supervisor.
+    assert!(!syscalls.iter().any(
+        |syscall| matches!(syscall, Syscall::Openat { path, .. } if path.len() == long_path.len())
+    ));
+    assert_contains!(
+        syscalls,
+        &Syscall::Openat {
+            at_dir: current_dir().unwrap().into(),
+            path: OsString::from("short"),
+        }
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn str_array() -> Result<(), Box<dyn Error>> {
+    let long_arg = [b'a'].repeat(1000);
+    let long_arg_cstr = CString::new(long_arg.as_slice()).unwrap();
+    let syscalls = run_in_pre_exec(move || {
+        // Fails with ENOENT, after which the child goes on to exec /bin/echo.
+        let _ = nix::unistd::execv(c"/nonexistent", &[c"arg0", long_arg_cstr.as_c_str()]);
         Ok(())
     })
-    .await;
-    let err = ret.unwrap_err();
-    assert_eq!(
-        err.downcast::<io::Error>().unwrap().kind(),
-        io::ErrorKind::InvalidFilename
+    .await?;
+    assert_contains!(
+        syscalls,
+        &Syscall::Execve {
+            path: "/nonexistent".into(),
+            args: vec!["arg0".into(), OsString::from_vec(long_arg)],
+        }
+    );
+    assert_contains!(
+        syscalls,
+        &Syscall::Execve {
+            path: "/bin/echo".into(),
+            args: vec!["/bin/echo".into()],
+        }
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn dup_fd() -> Result<(), Box<dyn Error>> {
+    let syscalls = run_in_pre_exec(|| {
+        let tmp_fd = nix::fcntl::open(c"/tmp", OFlag::O_PATH, Mode::empty())?;
+        nix::unistd::fchdir(&tmp_fd)?;
+        let _ = unsafe { libc::fchdir(-1) };
+        Ok(())
+    })
+    .await?;
+    assert_contains!(
+        syscalls,
+        &Syscall::Fchdir {
+            ino: Some(std::fs::metadata("/tmp")?.ino()),
+        }
     );
+    assert_contains!(syscalls, &Syscall::Fchdir { ino: None });
     Ok(())
 }
@@ -0,0 +1,138 @@
+#![cfg(target_os = "linux")]
+
+use std::{error::Error, io, os::fd::AsRawFd, time::Duration};
+
+use nix::{
+    errno::Errno,
+    fcntl::{AT_FDCWD, FcntlArg, FdFlag, OFlag, fcntl, openat},
+    sys::stat::Mode,
+    unistd::{pipe, read, write},
+};
+use seccomp_unotify::{
+    impl_handler,
+    supervisor::{
+        AddFd, NotifyResponse, Supervisor,
+        handler::arg::{CStrPtr, Fd},
+        supervise,
+    },
+    target::install_target,
+};
+use tokio::{process::Command, task::spawn_blocking, time::timeout};
+
+/// Emulates opening the paths under `/emulated`, and lets other opens run.
+#[derive(Default, Debug)]
+struct Emulator;
+impl Emulator {
+    fn openat(&mut self, (_, path): (Fd, CStrPtr)) -> io::Result<NotifyResponse> {
+        path.read_with_buf::<32768, _, _>(|path: &[u8]| {
+            Ok(match path {
+                b"/emulated/denied" => NotifyResponse::Error(libc::EACCES),
+                b"/emulated/value" => NotifyResponse::Value(1234),
+                b"/emulated/pipe" | b"/emulated/pipe_at_100" => {
+                    let (read_end, write_end) = pipe()?;
+                    write(write_end, b"injected")?;
+                    NotifyResponse::Fd(AddFd {
+                        target_fd: (path == b"/emulated/pipe_at_100").then_some(100),
+                        cloexec: true,
+                        ..AddFd::from(read_end)
+                    })
+                }
+                _ => NotifyResponse::Continue,
+            })
+        })
+    }
+}
+
+impl_handler!(Emulator, openat);
+
+/// Runs `check` in a child supervised by `Emulator`. A failure of `check` fails the test.
+async fn check_in_target(
+    mut check: impl FnMut() -> io::Result<()> + Send + Sync + 'static,
+) -> Result<(), Box<dyn Error>> {
+    timeout(Duration::from_secs(5), async move {
+        let mut cmd = Command::new("/bin/echo");
+        let Supervisor {
+            payload,
+            handling_loop,
+            mut pre_exec,
+        } = supervise::<Emulator>()?;
+
+        unsafe {
+            cmd.pre_exec(move || {
+                install_target(&payload)?;
+                pre_exec.run()?;
+                // A failure here fails the spawn.
+                check()
+            });
+        }
+        // The child waits for the supervisor in `pre_exec`, so the spawn runs along with the handling loop.
+        let child_fut = spawn_blocking(move || cmd.spawn());
+        let (_, exit_status) = futures_util::future::try_join(handling_loop, async move {
+            child_fut.await.unwrap()?.wait().await
+        })
+        .await?;
+        assert!(exit_status.success());
+        io::Result::Ok(())
+    })
+    .await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn error_response() -> Result<(), Box<dyn Error>> {
+    check_in_target(|| {
+        let denied = openat(
+            AT_FDCWD,
+            c"/emulated/denied",
+            OFlag::O_RDONLY,
+            Mode::empty(),
+        );
+        if denied.err() != Some(Errno::EACCES) {
+            return Err(io::Error::other("/emulated/denied should be denied"));
+        }
+        openat(AT_FDCWD, c"/", OFlag::O_RDONLY, Mode::empty())?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn value_response() -> Result<(), Box<dyn Error>> {
+    check_in_target(|| {
+        let ret =
+            unsafe { libc::openat(libc::AT_FDCWD, c"/emulated/value".as_ptr(), libc::O_RDONLY) };
+        if ret != 1234 {
+            return Err(io::Error::other("openat should return 1234"));
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn fd_response() -> Result<(), Box<dyn Error>> {
+    check_in_target(|| {
+        for (path, expected_fd) in [
+            (c"/emulated/pipe", None),
+            (c"/emulated/pipe_at_100", Some(100)),
+        ] {
+            let fd = openat(AT_FDCWD, path, OFlag::O_RDONLY, Mode::empty())?;
+            if expected_fd.is_some_and(|expected_fd| fd.as_raw_fd() != expected_fd) {
+                return Err(io::Error::other("the fd should be installed at 100"));
+            }
+            let fd_flag = FdFlag::from_bits_retain(fcntl(&fd, FcntlArg::F_GETFD)?);
+            if !fd_flag.contains(FdFlag::FD_CLOEXEC) {
+                return Err(io::Error::other("the fd should be close-on-exec"));
+            }
+            let mut buf = [0u8; 16];
+            let n = read(&fd, &mut buf)?;
+            if &buf[..n] != b"injected" {
+                return Err(io::Error::other(
+                    "the fd should be the read end of the pipe",
+                ));
+            }
+        }
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,99 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    error::Error,
+    io,
+    os::unix::process::ExitStatusExt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use nix::{
+    fcntl::{AT_FDCWD, OFlag, openat},
+    sys::{
+        stat::Mode,
+        wait::{Id, WaitPidFlag, waitid},
+    },
+    unistd::Pid,
+};
+use seccomp_unotify::{
+    impl_handler,
+    supervisor::{
+        StaleNotification, Supervisor,
+        handler::arg::{CStrPtr, Fd},
+        supervise_with,
+    },
+    target::install_target,
+};
+use tokio::{process::Command, task::spawn_blocking, time::timeout};
+
+/// Whether reading the path and the dir of the open of `/kill_me` is stale.
+#[derive(Default, Debug)]
+struct Outcomes {
+    path_stale: bool,
+    dir_stale: bool,
+}
+
+/// Kills the caller opening `/kill_me` before reading the arguments again.
+#[derive(Debug)]
+struct Killer(Arc<Mutex<Outcomes>>);
+impl Killer {
+    fn openat(&mut self, (dir, path): (Fd, CStrPtr)) -> io::Result<()> {
+        let kill_me = path.read_with_buf::<32768, _, _>(|path: &[u8]| Ok(path == b"/kill_me"))?;
+        if !kill_me {
+            return Ok(());
+        }
+        let pid = Pid::from_raw(path.pid() as _);
+        if unsafe { libc::kill(pid.as_raw(), libc::SIGKILL) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Wait until the caller is dead, without reaping it.
+        waitid(Id::Pid(pid), WaitPidFlag::WEXITED | WaitPidFlag::WNOWAIT)?;
+
+        let mut outcomes = self.0.lock().unwrap();
+        let path_result = path.read_with_buf::<32768, _, _>(|_| Ok(()));
+        outcomes.path_stale = path_result.is_err_and(|err| StaleNotification::is(&err));
+        let dir_result = dir.get_path();
+        outcomes.dir_stale = dir_result.is_err_and(|err| StaleNotification::is(&err));
+        // Failing with `StaleNotification` doesn't fail the supervisor.
+        Err(StaleNotification.into())
+    }
+}
+
+impl_handler!(Killer, openat);
+
+#[tokio::test]
+async fn killed_caller() -> Result<(), Box<dyn Error>> {
+    let outcomes = Arc::new(Mutex::new(Outcomes::default()));
+    let handler_outcomes = Arc::clone(&outcomes);
+    timeout(Duration::from_secs(5), async move {
+        let mut cmd = Command::new("/bin/echo");
+        let Supervisor {
+            payload,
+            handling_loop,
+            mut pre_exec,
+        } = supervise_with(move || Killer(Arc::clone(&handler_outcomes)))?;
+
+        unsafe {
+            cmd.pre_exec(move || {
+                install_target(&payload)?;
+                pre_exec.run()?;
+                let _ = openat(AT_FDCWD, c"/kill_me", OFlag::O_RDONLY, Mode::empty());
+                Ok(())
+            });
+        }
+        let child_fut = spawn_blocking(move || cmd.spawn());
+        let (_, exit_status) = futures_util::future::try_join(handling_loop, async move {
+            child_fut.await.unwrap()?.wait().await
+        })
+        .await?;
+        assert_eq!(exit_status.signal(), Some(libc::SIGKILL));
+        io::Result::Ok(())
+    })
+    .await??;
+
+    let outcomes = outcomes.lock().unwrap();
+    assert!(outcomes.path_stale);
+    assert!(outcomes.dir_stale);
+    Ok(())
+}